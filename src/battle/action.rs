@@ -1,17 +1,16 @@
-use ggez::{Context, GameResult};
+use crate::battle::damage::Attacker;
+use crate::battle::element::Element;
 use crate::battle::state::BattleState;
-use crate::menu::notification::Notification;
+use crate::battle::status_effect::StatusEffect;
 use crate::party::InventoryElement;
 use crate::party::item::InventoryItem;
+use crate::random::GameRng;
+
+// Runs an ability that deals no damage, such as Steal, against the target's state and returns what to tell the player
+pub type NoneTypeAction = fn(&mut [InventoryElement], &mut GameRng, &ActionParameters, &mut BattleState) -> Option<String>;
 
 pub enum DamageType {
-  None(for<'r, 's, 't1, 't2, 't3> fn(
-    &'r mut Context,
-    &'s mut Vec<InventoryElement>,
-    &'t1 ActionParameters,
-    &'t2 mut BattleState,
-    &'t3 mut Option<Notification>
-  ) -> GameResult<()>),
+  None(NoneTypeAction),
   Item(InventoryItem),
   Physical,
   Magical,
@@ -32,16 +31,59 @@ impl Clone for DamageType {
   }
 }
 
+// Who an action hits around the selected target, positions are (column, row) with the party as column 0
+#[derive(Clone, Copy, PartialEq)]
+pub enum TargetScope {
+  Single,
+  All,       // Everyone on the target's side, all enemies or all allies
+  Column,    // The target's enemy column, the whole party when aimed at it
+  Row,       // Enemies at the target's height in every column
+  Random(u8) // This many hits on random targets of the target's side
+}
+
+impl TargetScope {
+  pub fn from_name(name: &str, hits: u8) -> Option<TargetScope> {
+    match name {
+      "single" => Some(TargetScope::Single),
+      "all"    => Some(TargetScope::All),
+      "column" => Some(TargetScope::Column),
+      "row"    => Some(TargetScope::Row),
+      "random" if hits > 0 => Some(TargetScope::Random(hits)),
+      _ => None
+    }
+  }
+
+  // Scopes the player can switch to single targeting during target selection
+  pub fn is_toggleable(self) -> bool {
+    matches!(self, TargetScope::All | TargetScope::Column | TargetScope::Row)
+  }
+
+  pub fn includes(self, selected: (usize, usize), other: (usize, usize)) -> bool {
+    let same_side = (selected.0 == 0) == (other.0 == 0);
+    match self {
+      TargetScope::All    => same_side,
+      TargetScope::Column => selected.0 == other.0,
+      TargetScope::Row    => same_side && selected.1 == other.1,
+      _ => selected == other
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct ActionParameters {
   pub damage_type: DamageType,
   pub power: u16,
   pub dead_change: f32,
   pub revive: bool,
-  pub poison_change: f32,
-  pub cure_poison: bool,
-  pub sleep_change: f32,
-  pub cure_sleep: bool
+  pub status_effects: Vec<(StatusEffect, f32)>, // Inflicted on the target, each with its own chance
+  pub cures: Vec<StatusEffect>,
+  pub element: Option<Element>,
+  pub attacker: Option<Attacker>,
+  pub scope: TargetScope,
+  pub group: bool, // Hits the whole scope instead of only the selected target, toggled during target selection
+  pub split: bool, // Power is divided between everyone hit, otherwise each target takes its own full roll
+  pub long_range: bool, // Reaches from and into the back row without losing physical damage
+  pub mp_cost: u16 // Paid by whoever acts, when the action happens
 }
 
 impl ActionParameters {
@@ -50,20 +92,23 @@ impl ActionParameters {
     power: u16,
     dead_change: f32,
     revive: bool,
-    poison_change: f32,
-    cure_poison: bool,
-    sleep_change: f32,
-    cure_sleep: bool
+    status_effects: Vec<(StatusEffect, f32)>,
+    cures: Vec<StatusEffect>
   ) -> ActionParameters {
     ActionParameters {
       damage_type,
       power,
       dead_change,
       revive,
-      poison_change,
-      cure_poison,
-      sleep_change,
-      cure_sleep
+      status_effects,
+      cures,
+      element: None,
+      attacker: None,
+      scope: TargetScope::Single,
+      group: false,
+      split: false,
+      long_range: false,
+      mp_cost: 0
     }
   }
 }
\ No newline at end of file
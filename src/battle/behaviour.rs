@@ -0,0 +1,171 @@
+use rand::Rng;
+use crate::battle::action::{ActionParameters, DamageType};
+use crate::battle::enemy::SELF_TARGET;
+use crate::battle::state::BattleState;
+use crate::battle::status_effect::{StatusEffect, ForcedAction};
+use crate::random::GameRng;
+
+// When a rule takes the turn, rules without one are the weighted fallbacks
+#[derive(Clone, Copy)]
+pub enum Condition {
+  HpBelow(f32), // Fraction of the enemy's own max HP
+  PartyHasEffect(StatusEffect),
+  EveryTurns(u32) // Turns of this enemy, 3 acts on its third, sixth and so on
+}
+
+impl Condition {
+  pub fn from_name(name: &str, value: f32, effect: Option<&str>) -> Option<Condition> {
+    match name {
+      "hp_below" => Some(Condition::HpBelow(value)),
+      "party_has_effect" => effect.and_then(StatusEffect::from_name).map(Condition::PartyHasEffect),
+      "every_turns" if value >= 1. => Some(Condition::EveryTurns(value as u32)),
+      _ => None
+    }
+  }
+}
+
+// Who gets picked among the party members still standing, a random one when nobody fits
+#[derive(Clone, Copy, PartialEq)]
+pub enum TargetPreference {
+  Random,
+  LowestHp,
+  BackRow,
+  Caster, // Highest magic
+  Itself,
+  Affected // Someone with the effect of a party_has_effect condition
+}
+
+impl TargetPreference {
+  pub fn from_name(name: &str) -> Option<TargetPreference> {
+    match name {
+      "random"    => Some(TargetPreference::Random),
+      "lowest_hp" => Some(TargetPreference::LowestHp),
+      "back_row"  => Some(TargetPreference::BackRow),
+      "caster"    => Some(TargetPreference::Caster),
+      "self"      => Some(TargetPreference::Itself),
+      "affected"  => Some(TargetPreference::Affected),
+      _ => None
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Rule {
+  pub name: String, // Attack, Nothing or the ability's name
+  pub action: Option<ActionParameters>, // None does nothing
+  pub condition: Option<Condition>,
+  pub weight: f32,
+  pub target: TargetPreference
+}
+
+impl Rule {
+  // Spells need the enemy to be able to cast and pay their MP, the cost is spent by whoever carries out the turn
+  fn is_usable(&self, state: &BattleState) -> bool {
+    match &self.action {
+      Some(action_parameters) if action_parameters.mp_cost > 0 => state.can_cast() && state.mp >= action_parameters.mp_cost,
+      _ => true
+    }
+  }
+}
+
+// A boss stage entered once HP drops below the threshold
+#[derive(Clone)]
+pub struct Phase {
+  pub hp_below: f32, // Fraction of max HP
+  pub rules: Vec<Rule>, // Replace the current rules, empty keeps them
+  pub spritefile: Option<String>,
+  pub dialogue: Vec<String>
+}
+
+// Decides what an enemy does with its turns. The first usable rule whose condition holds acts,
+// otherwise one of the usable rules without a condition is picked by weight.
+#[derive(Clone)]
+pub struct Behaviour {
+  pub rules: Vec<Rule>,
+  phases: Vec<Phase>, // Ordered by descending threshold
+  turns: u32
+}
+
+impl Behaviour {
+  pub fn new(rules: Vec<Rule>, phases: Vec<Phase>) -> Behaviour {
+    Behaviour {rules, phases, turns: 0}
+  }
+
+  // Enters the next phase if HP dropped below its threshold, a killing blow skips it
+  pub fn update_phase(&mut self, state: &BattleState) -> Option<Phase> {
+    let entered = self.phases.first().is_some_and(|phase| state.hp > 0 && (state.hp as f32) < phase.hp_below * state.max_hp as f32);
+    if !entered {
+      return None;
+    }
+    let phase = self.phases.remove(0);
+    if !phase.rules.is_empty() {
+      self.rules = phase.rules.clone();
+    }
+    Some(phase)
+  }
+
+  // The target, rule name and action of a turn, or None to skip it. Party targets are 0-3 for members still standing.
+  // Berserk enemies always attack, confused ones hit themselves half the time.
+  pub fn choose(&mut self, rng: &mut GameRng, state: &BattleState, party: &[(u8, &BattleState)]) -> Option<(u8, String, ActionParameters)> {
+    self.turns += 1;
+    if party.is_empty() {
+      return None;
+    }
+    let attack = ActionParameters::new(DamageType::Physical, state.attack, 0., false, Vec::new(), Vec::new());
+    match state.get_forced_action() {
+      Some(ForcedAction::AttackEnemy) => return Some((random_target(rng, party), "Attack".to_owned(), attack)),
+      Some(ForcedAction::AttackAnyone) => return Some(match rng.gen::<f32>() < 0.5 {
+        true  => (SELF_TARGET, "Attack".to_owned(), attack),
+        false => (random_target(rng, party), "Attack".to_owned(), attack)
+      }),
+      None => ()
+    }
+    let rule = match self.rules.iter().find(|rule| rule.is_usable(state) && rule.condition.is_some_and(|condition| self.holds(condition, state, party))) {
+      Some(rule) => rule,
+      None => self.pick_fallback(rng, state)?
+    };
+    let target = pick_target(rng, rule.target, rule.condition, party);
+    rule.action.clone().map(|action_parameters| (target, rule.name.to_owned(), action_parameters))
+  }
+
+  fn holds(&self, condition: Condition, state: &BattleState, party: &[(u8, &BattleState)]) -> bool {
+    match condition {
+      Condition::HpBelow(fraction) => (state.hp as f32) < fraction * state.max_hp as f32,
+      Condition::PartyHasEffect(effect) => party.iter().any(|(_, member)| member.has_effect(effect)),
+      Condition::EveryTurns(turns) => self.turns.is_multiple_of(turns)
+    }
+  }
+
+  fn pick_fallback(&self, rng: &mut GameRng, state: &BattleState) -> Option<&Rule> {
+    let fallbacks: Vec<&Rule> = self.rules.iter().filter(|rule| rule.condition.is_none() && rule.weight > 0. && rule.is_usable(state)).collect();
+    let mut roll = rng.gen::<f32>() * fallbacks.iter().map(|rule| rule.weight).sum::<f32>();
+    for rule in fallbacks.iter() {
+      if roll < rule.weight {
+        return Some(rule);
+      }
+      roll -= rule.weight;
+    }
+    fallbacks.last().copied()
+  }
+}
+
+fn random_target(rng: &mut GameRng, party: &[(u8, &BattleState)]) -> u8 {
+  party[rng.gen_range(0, party.len())].0
+}
+
+fn pick_target(rng: &mut GameRng, preference: TargetPreference, condition: Option<Condition>, party: &[(u8, &BattleState)]) -> u8 {
+  let candidates: Vec<u8> = match (preference, condition) {
+    (TargetPreference::Itself, _) => return SELF_TARGET,
+    (TargetPreference::LowestHp, _) => party.iter().min_by_key(|(_, member)| member.hp).map(|(target, _)| *target).into_iter().collect(),
+    (TargetPreference::Caster, _) => party.iter().max_by_key(|(_, member)| member.magic).map(|(target, _)| *target).into_iter().collect(),
+    (TargetPreference::BackRow, _) => party.iter().filter(|(_, member)| member.back_row).map(|(target, _)| *target).collect(),
+    (TargetPreference::Affected, Some(Condition::PartyHasEffect(effect))) => {
+      party.iter().filter(|(_, member)| member.has_effect(effect)).map(|(target, _)| *target).collect()
+    },
+    _ => Vec::new()
+  };
+  match candidates.is_empty() {
+    true  => random_target(rng, party),
+    false => candidates[rng.gen_range(0, candidates.len())]
+  }
+}
\ No newline at end of file
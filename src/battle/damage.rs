@@ -0,0 +1,97 @@
+use rand::Rng;
+use std::cmp::max;
+use std::rc::Rc;
+use crate::battle::action::{ActionParameters, DamageType};
+use crate::data::content::CombatDefinition;
+use crate::random::GameRng;
+
+// A combatant's stats as they go into a damage roll, after status effects
+#[derive(Clone, Default)]
+pub struct CombatStats {
+  pub attack: u16,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8,
+  pub accuracy: f32 // Multiplier of the chance to hit
+}
+
+// Who performs an action and by which rules, filled in by BattleState::prepare_action
+#[derive(Clone)]
+pub struct Attacker {
+  pub stats: CombatStats,
+  pub formula: Rc<dyn DamageFormula>,
+  pub back_row: bool
+}
+
+pub enum DamageRoll {
+  Miss,
+  Hit(u16),
+  Critical(u16)
+}
+
+// Decides how much an action hurts before elemental affinities, which BattleState applies afterwards
+pub trait DamageFormula {
+  fn roll(&self, rng: &mut GameRng, attacker: &CombatStats, defender: &CombatStats, action_parameters: &ActionParameters) -> DamageRoll;
+}
+
+// The original power * 3 / defence, which never misses or varies and ignores the attacker
+pub struct ClassicFormula;
+
+impl DamageFormula for ClassicFormula {
+  fn roll(&self, _rng: &mut GameRng, _attacker: &CombatStats, defender: &CombatStats, action_parameters: &ActionParameters) -> DamageRoll {
+    let power = action_parameters.power as u32 * 3;
+    let damage = match action_parameters.damage_type {
+      DamageType::Physical => power / max(defender.defence, 1) as u32,
+      DamageType::Magical  => power / max(defender.resistance, 1) as u32,
+      DamageType::Pure     => power,
+      _ => 0
+    };
+    DamageRoll::Hit(to_damage(damage as f32))
+  }
+}
+
+// Attacker's stat against the defender's, with hit rolls and criticals for physical attacks and variance for everything
+pub struct StandardFormula {
+  tuning: CombatDefinition
+}
+
+impl DamageFormula for StandardFormula {
+  fn roll(&self, rng: &mut GameRng, attacker: &CombatStats, defender: &CombatStats, action_parameters: &ActionParameters) -> DamageRoll {
+    let tuning = &self.tuning;
+    let power = action_parameters.power as f32 * 3.;
+    let mut damage = match action_parameters.damage_type {
+      DamageType::Physical => power * (1. + attacker.attack as f32 * tuning.stat_scaling) / max(defender.defence, 1) as f32,
+      DamageType::Magical  => power * (1. + attacker.magic as f32 * tuning.stat_scaling) / max(defender.resistance, 1) as f32,
+      DamageType::Pure     => power,
+      _ => return DamageRoll::Hit(0)
+    };
+    let mut critical = false;
+    if let DamageType::Physical = action_parameters.damage_type {
+      let evasion = (defender.agility as f32 - attacker.agility as f32).max(0.) * tuning.evasion_per_agility;
+      if rng.gen::<f32>() >= tuning.hit_chance * attacker.accuracy - evasion {
+        return DamageRoll::Miss;
+      }
+      critical = rng.gen::<f32>() < tuning.critical_chance;
+    }
+    if tuning.variance > 0. {
+      damage *= 1. + rng.gen_range(-tuning.variance, tuning.variance);
+    }
+    match critical {
+      true  => DamageRoll::Critical(to_damage(damage * tuning.critical_multiplier)),
+      false => DamageRoll::Hit(to_damage(damage))
+    }
+  }
+}
+
+// Rounds into the range HP can take, however large the numbers got
+fn to_damage(damage: f32) -> u16 {
+  damage.round().max(0.).min(u16::MAX as f32) as u16
+}
+
+pub fn get_formula(definition: &CombatDefinition) -> Rc<dyn DamageFormula> {
+  match definition.formula.as_str() {
+    "classic" => Rc::new(ClassicFormula),
+    _ => Rc::new(StandardFormula {tuning: definition.clone()})
+  }
+}
\ No newline at end of file
@@ -0,0 +1,46 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum Element {
+  Fire,
+  Ice,
+  Thunder,
+  Water,
+  Wind,
+  Earth,
+  Holy,
+  Dark
+}
+
+pub const ELEMENTS: [Element; 8] = [
+  Element::Fire, Element::Ice, Element::Thunder, Element::Water, Element::Wind, Element::Earth, Element::Holy, Element::Dark
+];
+
+impl Element {
+  pub fn from_name(name: &str) -> Option<Element> {
+    ELEMENTS.iter().find(|element| element.get_name() == name).copied()
+  }
+
+  pub fn get_name(self) -> &'static str {
+    match self {
+      Element::Fire    => "fire",
+      Element::Ice     => "ice",
+      Element::Thunder => "thunder",
+      Element::Water   => "water",
+      Element::Wind    => "wind",
+      Element::Earth   => "earth",
+      Element::Holy    => "holy",
+      Element::Dark    => "dark"
+    }
+  }
+}
+
+// Damage multiplier of one element, 2 is a weakness, 0.5 a resistance, 0 an immunity and below 0 heals instead
+pub type Affinity = (Element, f32);
+
+// Combines affinities from several sources, like an enemy's own and its status effects.
+// Absorbing wins over everything else, the rest multiply, so a weakness and a resistance cancel out.
+pub fn combine_affinities(multipliers: &[f32]) -> f32 {
+  match multipliers.iter().copied().filter(|multiplier| *multiplier < 0.).reduce(f32::min) {
+    Some(absorb) => absorb,
+    None => multipliers.iter().product()
+  }
+}
\ No newline at end of file
@@ -0,0 +1,82 @@
+use rand::Rng;
+use crate::battle::state::BattleState;
+use crate::battle::status_effect::Stat;
+use crate::data::content::CombatDefinition;
+use crate::random::GameRng;
+
+// Left edges of the party's sprites and the side of the enemies they face
+const PARTY_X: f32 = 200.;
+const PARTY_MIRRORED_X: f32 = 816.; // Mirrors PARTY_X across the window for 64 pixel wide sprites
+const PARTY_PINCER_X: f32 = 480.;
+
+// How a battle starts, scripted battles are always Normal
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encounter {
+  Normal,
+  Preemptive, // The party's gauges start full
+  BackAttack, // The enemies come from behind, the party stands mirrored with its rows swapped and empty gauges
+  Pincer      // Enemies on both sides of the party
+}
+
+impl Encounter {
+  // Agility makes preemptive strikes likelier and ambushes rarer, alert equipment prevents ambushes altogether
+  pub fn roll(rng: &mut GameRng, tuning: &CombatDefinition, agility: f32, preemptive_bonus: f32, alert: bool, enemy_count: usize) -> Encounter {
+    let agility_scaling = 1. + agility * tuning.encounter_agility_scaling;
+    let preemptive = tuning.preemptive_chance * agility_scaling + preemptive_bonus;
+    let (back_attack, pincer) = match alert {
+      true  => (0., 0.),
+      false => (tuning.back_attack_chance / agility_scaling, tuning.pincer_chance / agility_scaling)
+    };
+    let roll = rng.gen::<f32>();
+    if roll < preemptive {
+      Encounter::Preemptive
+    } else if roll < preemptive + back_attack {
+      Encounter::BackAttack
+    } else if roll < preemptive + back_attack + pincer && enemy_count > 1 {
+      Encounter::Pincer
+    } else {
+      Encounter::Normal
+    }
+  }
+
+  // Fills or empties a party member's gauge for the way the battle started, true when the member's row swaps
+  pub fn start_party_member(self, state: &mut BattleState) -> bool {
+    match self {
+      Encounter::Preemptive => {
+        state.set_atb(u8::MAX);
+        false
+      },
+      Encounter::BackAttack => {
+        state.set_atb(0);
+        true
+      },
+      _ => false
+    }
+  }
+
+  // Rolls with the party's average agility, the preemptive bonus of everyone's equipment and whether anyone is alert
+  pub fn roll_for_party(rng: &mut GameRng, tuning: &CombatDefinition, members: &[&BattleState], enemy_count: usize) -> Encounter {
+    let agility = members.iter().map(|state| state.get_equipped(Stat::Agility) as f32).sum::<f32>() / members.len().max(1) as f32;
+    let preemptive_bonus = members.iter().map(|state| state.equipment.preemptive).sum();
+    let alert = members.iter().any(|state| state.equipment.alert);
+    Encounter::roll(rng, tuning, agility, preemptive_bonus, alert, enemy_count)
+  }
+
+  pub fn get_announcement(self) -> Option<&'static str> {
+    match self {
+      Encounter::Normal     => None,
+      Encounter::Preemptive => Some("Preemptive strike"),
+      Encounter::BackAttack => Some("Back attack"),
+      Encounter::Pincer     => Some("Pincer attack")
+    }
+  }
+
+  // Where the party's offsets count from, and whether it faces left
+  pub fn get_party_position(self) -> (f32, bool) {
+    match self {
+      Encounter::BackAttack => (PARTY_MIRRORED_X, true),
+      Encounter::Pincer     => (PARTY_PINCER_X, false),
+      _ => (PARTY_X, false)
+    }
+  }
+}
\ No newline at end of file
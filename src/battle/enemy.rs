@@ -1,13 +1,29 @@
 use ggez::graphics::{spritebatch, Image, DrawParam, draw, Color};
-use ggez::nalgebra::Point2;
+use ggez::nalgebra::{Point2, Vector2};
 use ggez::{Context, GameResult};
 use ggez::timer::ticks;
-use crate::battle::action::{ActionParameters, DamageType};
+use rand::Rng;
+use crate::battle::action::ActionParameters;
+use crate::battle::behaviour::{Behaviour, Phase};
 use crate::battle::state::BattleState;
+use crate::battle::status_effect::StatusEffect;
+use crate::battle::turn::{self, ActionOutcome};
 use crate::party::{Party, InventoryElement};
-use crate::party::character::{Character, Animation as CharacterAnimation};
 use crate::party::item::InventoryItem;
 use crate::menu::notification::Notification;
+use crate::data::enemies;
+use crate::random::GameRng;
+
+pub const SELF_TARGET: u8 = 4; // Target of a confused enemy hitting itself, 0-3 are the party members
+const ENEMY_X: f32 = 700.; // Left edge of the column nearest the party
+const ENEMY_MIRRORED_X: f32 = 380.; // Right edge of the nearest column on the left side
+
+// An item the enemy may leave behind, rolled once when it dies
+pub struct Drop {
+  pub item: InventoryItem,
+  pub chance: f32,
+  pub amount: u8
+}
 
 pub enum Animation {
   StartTurn(u8, ActionParameters), // 60 frames
@@ -32,6 +48,8 @@ pub struct Enemy {
   pub screen_pos: (f32, f32),
   pub selection_pos: (usize, usize),
   pub size: f32,
+  width: f32,
+  pub mirrored: bool, // On the left of the party facing right, in a back attack or one side of a pincer
   turn_active: bool,
   opacity: f32,
   pub animation: (Animation, usize, usize),
@@ -40,7 +58,10 @@ pub struct Enemy {
   pub state: BattleState,
   pub dead: bool,
   pub escapeable: bool,
-  turn_action: for<'r, 's, 't0, 't1> fn(&'r mut Context, &'s mut Enemy, &'t0 mut Party, &'t1 mut Option<Notification>) -> GameResult<()>
+  pub boss: bool,
+  pub drops: Vec<Drop>,
+  pub gold: u32,
+  pub behaviour: Behaviour
 }
 
 impl Enemy {
@@ -61,47 +82,81 @@ impl Enemy {
     resistance: u16,
     agility: u8,
     experience: u32,
-    poisoned: i8,
-    sleeping: i8,
+    status_effects: Vec<StatusEffect>,
+    immunities: Vec<StatusEffect>,
     back_row: bool,
     common_steal: Option<InventoryItem>,
     rare_steal: Option<InventoryItem>,
     escapeable: bool,
-    turn_action: for<'r, 's, 't0, 't1> fn(&'r mut Context, &'s mut Enemy, &'t0 mut Party, &'t1 mut Option<Notification>) -> GameResult<()>,
+    behaviour: Behaviour
   ) -> Enemy {
     let image = Image::new(ctx, spritefile).unwrap();
+    let width = image.width() as f32;
     let batch = spritebatch::SpriteBatch::new(image);
     Enemy {
       spritebatch: batch,
       screen_pos,
       selection_pos,
       size,
+      width,
+      mirrored: false,
       turn_active: false,
       opacity: 1.,
       animation: (Animation::EndTurn, 0, 0),
       x_offset: 0.,
       name,
-      state: BattleState::new(id, level, hp, mp, attack, defence, magic, resistance, agility, experience, poisoned, sleeping, back_row, common_steal, rare_steal, None),
+      state: BattleState::new(id, level, hp, mp, attack, defence, magic, resistance, agility, experience, status_effects, immunities, back_row, common_steal, rare_steal, None),
       dead: false,
       escapeable,
-      turn_action
+      boss: false,
+      drops: Vec::new(),
+      gold: 0,
+      behaviour
+    }
+  }
+
+  pub fn roll_drops(&self, rng: &mut GameRng) -> Vec<(InventoryItem, u8)> {
+    self.drops.iter()
+      .filter(|drop| rng.gen::<f32>() < drop.chance)
+      .map(|drop| (drop.item.clone(), drop.amount))
+      .collect()
+  }
+
+  // Top left corner of the sprite, columns count away from the party on either side
+  pub fn get_screen_pos(&self, enemy_start_draw_height: f32) -> (f32, f32) {
+    let x = match self.mirrored {
+      true  => ENEMY_MIRRORED_X - self.x_offset - self.screen_pos.0 * 70. - self.width,
+      false => ENEMY_X + self.x_offset + self.screen_pos.0 * 70.
+    };
+    (x, enemy_start_draw_height + self.screen_pos.1 * 66.)
+  }
+
+  // Enters the next phase of the behaviour once HP drops low enough, swapping the sprite if the phase has one
+  pub fn update_phase(&mut self, ctx: &mut Context) -> GameResult<Option<Phase>> {
+    let phase = match self.behaviour.update_phase(&self.state) {
+      Some(phase) => phase,
+      None => return Ok(None)
+    };
+    if let Some(spritefile) = &phase.spritefile {
+      let image = Image::new(ctx, spritefile)?;
+      self.width = image.width() as f32;
+      self.spritebatch = spritebatch::SpriteBatch::new(image);
     }
+    Ok(Some(phase))
   }
 
   pub fn update(
     &mut self,
     ctx: &mut Context,
     party: &mut Party,
-    active_turns: &mut Vec<u8>,
     current_turn: &mut u8,
     notification: &mut Option<Notification>,
     enemy_start_draw_height: f32
   ) -> GameResult<()> {
-    self.state.update(current_turn, active_turns)?;
+    self.state.update()?;
     if *current_turn == self.state.id && !self.turn_active {
       self.turn_active = true;
-      let turn_action = self.turn_action;
-      turn_action(ctx, self, party, notification)?;
+      enemies::turn_action(ctx, self, party, notification)?;
     }
     if self.animation.1 > 0 {
       self.animation.1 -= 1;
@@ -130,20 +185,32 @@ impl Enemy {
       if self.animation.1 == 0 {
         match &mut self.animation.0 {
           Animation::StartTurn(target_number, action_parameters) => {
-            let mut parameters = action_parameters.clone();
-            match target_number {
-              0 => self.act_on_target(ctx, &mut party.inventory, notification, &mut parameters, &mut party.first )?,
-              1 => self.act_on_target(ctx, &mut party.inventory, notification, &mut parameters, &mut party.second)?,
-              2 => self.act_on_target(ctx, &mut party.inventory, notification, &mut parameters, &mut party.third )?,
-              _ => self.act_on_target(ctx, &mut party.inventory, notification, &mut parameters, &mut party.fourth)?
+            let target_number = *target_number;
+            let party_alive = party.get_alive_positions();
+            let targets = turn::get_enemy_targets(&mut party.rng, &party_alive, target_number, action_parameters);
+            let parameters = turn::prepare_action(&self.state, &party.damage_formula, action_parameters, targets.len());
+            self.animation = (Animation::EndTurn, 30, ticks(ctx));
+            for target in targets {
+              let character = match target {
+                SELF_TARGET => {
+                  self.act_on_itself(ctx, &mut party.rng, notification, &parameters, enemy_start_draw_height)?;
+                  continue;
+                },
+                0 => &mut party.first,
+                1 => &mut party.second,
+                2 => &mut party.third,
+                _ => &mut party.fourth
+              };
+              character.receive_battle_action(ctx, &mut party.inventory, &mut party.rng, notification, &parameters)?;
             }
           },
           Animation::EndTurn => {
             self.turn_active = false;
             *current_turn = 0;
-            self.state.end_turn(ctx, notification, &self.name, (
-              700. + self.x_offset + self.screen_pos.0 * 70., enemy_start_draw_height + self.screen_pos.1 * 66.
-            ))?;
+            self.state.end_turn(ctx, notification, &self.name, self.get_screen_pos(enemy_start_draw_height))?;
+            if self.state.hp == 0 {
+              self.animation = (Animation::Dead, 20, ticks(ctx));
+            }
           },
           Animation::Hurt => {
             self.opacity = 1.;
@@ -161,58 +228,34 @@ impl Enemy {
     Ok(())
   }
 
-  pub fn act_on_target(
+  fn act_on_itself(
     &mut self,
     ctx: &mut Context,
-    inventory: &mut Vec<InventoryElement>,
+    rng: &mut GameRng,
     notification: &mut Option<Notification>,
-    action_parameters: &mut ActionParameters,
-    character: &mut Character
+    action_parameters: &ActionParameters,
+    enemy_start_draw_height: f32
   ) -> GameResult<()> {
-    self.animation = (Animation::EndTurn, 30, ticks(ctx));
-    match action_parameters.damage_type {
-      DamageType::Healing => {character.receive_battle_action(ctx, inventory, notification, action_parameters)},
-      _ => {
-        character.animation = (CharacterAnimation::Hurt, 60, ticks(ctx));
-        character.receive_battle_action(ctx, inventory, notification, action_parameters)
-      }
-    }
+    let outcome = turn::apply_action(rng, &mut [], &mut self.state, action_parameters);
+    let position = self.get_screen_pos(enemy_start_draw_height);
+    self.state.show_outcome(ctx, notification, &self.name, action_parameters, &outcome, position)
   }
 
   pub fn receive_battle_action(
     &mut self,
     ctx: &mut Context,
-    inventory: &mut Vec<InventoryElement>,
+    inventory: &mut [InventoryElement],
+    rng: &mut GameRng,
     notification: &mut Option<Notification>,
-    action_parameters: &mut ActionParameters,
+    action_parameters: &ActionParameters,
     enemy_start_draw_height: f32
   ) -> GameResult<()> {
-    match &action_parameters.damage_type {
-      DamageType::None(action) => self.state.receive_none_type_action(ctx, inventory, action_parameters, *action, notification),
-      DamageType::Item(used_item) => {
-        *notification = Some(Notification::new(ctx, used_item.get_name()));
-        let position = self.state.get_damage_position((700. + self.x_offset + self.screen_pos.0 * 70., enemy_start_draw_height + self.screen_pos.1 * 66.));
-        for inventory_element in inventory {
-          match inventory_element {
-            InventoryElement::Item(item, amount) => {
-              if used_item.get_name() == item.get_name() && *amount > 0 {
-                *amount -= 1;
-              }
-            }
-          }
-        }
-        used_item.apply_item_effect(ctx, &mut self.state, position)
-      },
-      DamageType::Healing => self.state.receive_healing(ctx, action_parameters, (
-          700. + self.x_offset + self.screen_pos.0 * 70., enemy_start_draw_height + self.screen_pos.1 * 66.
-        )),
-      _ => {
-        self.animation = (Animation::Hurt, 60, ticks(ctx));
-        self.state.receive_damage(ctx, notification, &self.name, action_parameters, (
-          700. + self.x_offset + self.screen_pos.0 * 70., enemy_start_draw_height + self.screen_pos.1 * 66.
-        ))
-      }
+    let outcome = turn::apply_action(rng, inventory, &mut self.state, action_parameters);
+    if let ActionOutcome::Damage(_) = outcome {
+      self.animation = (Animation::Hurt, 60, ticks(ctx));
     }
+    let position = self.get_screen_pos(enemy_start_draw_height);
+    self.state.show_outcome(ctx, notification, &self.name, action_parameters, &outcome, position)
   }
 
   pub fn draw(&mut self, ctx: &mut Context, enemy_start_draw_height: f32) -> GameResult<()> {
@@ -222,8 +265,11 @@ impl Enemy {
         _ => DrawParam::new()
       }
     );
-    let param = DrawParam::new()
-      .dest(Point2::new(700. + self.x_offset + self.screen_pos.0 * 70., enemy_start_draw_height + self.screen_pos.1 * 66.));
+    let (x, y) = self.get_screen_pos(enemy_start_draw_height);
+    let param = match self.mirrored {
+      true  => DrawParam::new().dest(Point2::new(x + self.width, y)).scale(Vector2::new(-1., 1.)),
+      false => DrawParam::new().dest(Point2::new(x, y))
+    };
     draw(ctx, &self.spritebatch, param)?;
     self.spritebatch.clear();
     self.state.draw(ctx)
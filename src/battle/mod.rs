@@ -1,31 +1,112 @@
 use ggez::{Context, GameResult};
 use ggez::timer::ticks;
+use std::rc::Rc;
 pub mod action;
+use action::ActionParameters;
 use crate::GameMode;
+use crate::input::Input;
 use crate::menu::MenuScreen;
 use crate::menu::container::MenuContainer;
+use crate::menu::dialogue::Dialogue;
+use crate::menu::item::{MenuItem, OnClickEvent};
+pub mod behaviour;
+pub mod damage;
+pub mod element;
+pub mod encounter;
 pub mod enemy;
+use encounter::Encounter;
 use enemy::Enemy;
 pub mod print_damage;
 pub mod state;
+pub mod status_effect;
+pub mod turn;
+pub mod turn_queue;
+use turn_queue::TurnQueue;
+pub mod simulator;
 use crate::party::Party;
+use crate::party::item::InventoryItem;
 use crate::party::character::Sprite;
+use crate::data::{enemy_formations, menus};
+use crate::data::content::{BattleContent, FormationDefinition};
+use crate::save::PartySnapshot;
+use state::BattleState;
+use crate::save::settings::AtbMode;
 use crate::menu::notification::Notification;
-use crate::transition::Transition;
+use crate::tilemap::Tilemap;
+use crate::transition::{Transition, TransitionStyle};
+
+const TIMELINE_LENGTH: usize = 5;
+
+// Collected from the enemies as they die, handed to the party on the victory screen
+#[derive(Default)]
+pub struct Rewards {
+  pub experience: u32,
+  pub gold: u32,
+  pub items: Vec<(InventoryItem, u8)>
+}
+
+impl Rewards {
+  pub fn add_item(&mut self, new_item: InventoryItem, new_amount: u8) {
+    match self.items.iter_mut().find(|(item, _)| item.get_name() == new_item.get_name()) {
+      Some((_, amount)) => *amount = amount.saturating_add(new_amount),
+      None => self.items.push((new_item, new_amount))
+    }
+  }
+}
 
 pub struct Battle {
   party_info_container: MenuContainer,
   pub enemies: Vec<Vec<Enemy>>,
   pub enemies_start_draw_height: f32,
-  active_turns: Vec<u8>,
+  turn_queue: TurnQueue,
+  timeline_container: MenuContainer,
+  timeline: (Vec<u8>, Vec<MenuItem>), // Predicted ids and their names, rebuilt when the prediction changes
   pub current_turn: u8, // 0 = Noone's turn, 1-4 party member's turn, 5 >= enemy's turn
   pub notification: Option<Notification>,
-  experience_gained: u32,
-  battle_over: (bool, usize)
+  dialogue: Option<Dialogue>, // Spoken by a boss entering a phase, the battle waits until it's closed
+  rewards: Rewards,
+  battle_over: (bool, usize),
+  pub content: Rc<BattleContent>, // Handed on to every battle after this one
+  formation: Option<FormationDefinition>, // Rebuilt by a retry, None before the first battle
+  encounter: Encounter, // Kept by a retry
+  snapshot: PartySnapshot, // The party as it entered the battle
+  pub allow_loss: bool, // A wiped party gets up with 1 HP and the story goes on
+  pub lost: bool
 }
 
 impl Battle {
-  pub fn new(ctx: &mut Context, enemies: Vec<Vec<Enemy>>, party: &mut Party, menu: &mut MenuScreen) -> Battle {
+  // Stands in until the first battle starts
+  pub fn none(ctx: &mut Context, content: Rc<BattleContent>, party: &Party) -> Battle {
+    Battle {
+      party_info_container: MenuContainer::new(ctx, 300., 400., 770., 300.),
+      enemies: Vec::new(),
+      enemies_start_draw_height: 0.,
+      turn_queue: TurnQueue::default(),
+      timeline_container: MenuContainer::new(ctx, 380., 104., 280., 80. + TIMELINE_LENGTH as f32 * 40.),
+      timeline: (Vec::new(), Vec::new()),
+      current_turn: 0,
+      notification: None,
+      dialogue: None,
+      rewards: Rewards::default(),
+      battle_over: (false, 0),
+      content,
+      formation: None,
+      encounter: Encounter::Normal,
+      snapshot: PartySnapshot::new(party),
+      allow_loss: false,
+      lost: false
+    }
+  }
+
+  pub fn new(
+    ctx: &mut Context,
+    content: Rc<BattleContent>,
+    formation: FormationDefinition,
+    encounter: Encounter,
+    party: &mut Party,
+    menu: &mut MenuScreen
+  ) -> GameResult<Battle> {
+    let enemies = enemy_formations::build_formation(ctx, &content, &formation, encounter)?;
     menu.open = false;
     if party.first .state.hp > 0 {party.first.sprite  = Sprite::StandRight;}
     if party.second.state.hp > 0 {party.second.sprite = Sprite::StandRight;}
@@ -41,72 +122,131 @@ impl Battle {
         max_enemy_column_length = column_length;
       }
     }
-    Battle {
-      party_info_container: MenuContainer::new(ctx, 300., 400., 770., 300.),
+    let snapshot = PartySnapshot::new(party);
+    party.start_battle(encounter);
+    Ok(Battle {
       enemies,
       enemies_start_draw_height: 184. - max_enemy_column_length * 16.,
-      active_turns: Vec::new(),
-      current_turn: 0,
-      notification: None,
-      experience_gained: 0,
-      battle_over: (false, 0)
+      notification: encounter.get_announcement().map(|announcement| Notification::new(ctx, announcement.to_owned())),
+      formation: Some(formation),
+      encounter,
+      snapshot,
+      ..Battle::none(ctx, content, party)
+    })
+  }
+
+  // Starts the same formation again with the party as it was when the battle began
+  pub fn retry(&mut self, ctx: &mut Context, party: &mut Party, menu: &mut MenuScreen, transition: &mut Transition) -> GameResult<()> {
+    let formation = match self.formation.take() {
+      Some(formation) => formation,
+      None => return Ok(())
+    };
+    self.snapshot.restore(ctx, party)?;
+    let allow_loss = self.allow_loss;
+    *self = Battle::new(ctx, self.content.clone(), formation, self.encounter, party, menu)?;
+    self.allow_loss = allow_loss;
+    transition.set(TransitionStyle::WhiteInFast(GameMode::Battle))
+  }
+
+  // Game over, unless losing is part of the story
+  fn lose_battle(&mut self, ctx: &mut Context, party: &mut Party, menu: &mut MenuScreen, transition: &mut Transition) -> GameResult<()> {
+    if ticks(ctx) - self.battle_over.1 != 120 {
+      return Ok(());
+    }
+    party.end_battle(ctx)?;
+    match self.allow_loss {
+      true => {
+        party.revive()?;
+        transition.set(TransitionStyle::BlackInFast(GameMode::Map))
+      },
+      false => {
+        *menu = menus::game_over(ctx);
+        transition.set(TransitionStyle::BlackInFast(GameMode::Menu))
+      }
     }
   }
 
   pub fn update(
     &mut self,
     ctx: &mut Context,
+    input: &Input,
     mode: &mut GameMode,
     party: &mut Party,
     menu: &mut MenuScreen,
     battle_menu: &mut MenuScreen,
+    map: &mut Tilemap,
     transition: &mut Transition
   ) -> GameResult<()> {
     if *mode == GameMode::Battle {
+      if let Some(dialogue) = &mut self.dialogue {
+        if dialogue.update(ctx, input)? {
+          self.dialogue = None;
+        }
+        return Ok(());
+      }
       if self.battle_over.0 {
         if ticks(ctx) - self.battle_over.1 > 60 {
-          party.won_battle(ctx, menu, self.battle_over.1, &mut self.experience_gained, transition)?;
+          match self.lost {
+            true  => self.lose_battle(ctx, party, menu, transition)?,
+            false => party.won_battle(ctx, menu, self.battle_over.1, &mut self.rewards, transition)?
+          }
         }
       } else {
-        if self.current_turn == 0 {
-          if let Some(next_turn) = self.active_turns.pop() {
-            self.current_turn = next_turn;
+        while self.current_turn == 0 {
+          match self.turn_queue.pop() {
+            Some(next_turn) if self.can_act(party, next_turn) => self.current_turn = next_turn,
+            Some(_) => (),
+            None => break
+          }
+        }
+        let choosing_command = battle_menu.open && (1..=4).contains(&self.current_turn);
+        let atb_running = self.current_turn == 0 || (choosing_command && match party.settings.atb_mode {
+          AtbMode::Active => true,
+          AtbMode::Wait => !battle_menu.is_submenu()
+        });
+        if atb_running {
+          self.update_atb(party);
+        }
+        self.update_timeline(ctx, party);
+        party.update(ctx, battle_menu, &mut self.current_turn, &mut self.notification, transition)?;
+        if let Some((forced_action, action_parameters)) = party.take_forced_turn(ctx) {
+          let alive = self.get_alive_positions(party);
+          if let Some(target) = turn::get_forced_target(&mut party.rng, &alive, forced_action) {
+            party.battle_turn_action(ctx, self, target, &action_parameters)?;
           }
         }
-        party.update(ctx, battle_menu, &mut self.active_turns, &mut self.current_turn, &mut self.notification, transition)?;
-        battle_menu.update(ctx, mode, party, self, transition)?;
+        battle_menu.update(ctx, input, mode, party, self, map, transition)?;
       }
       let mut dead_enemies = Vec::new();
       for (i, enemy_column) in self.enemies.iter_mut().enumerate() {
         for (j, enemy) in enemy_column.iter_mut().enumerate() {
-          enemy.update(ctx, party, &mut self.active_turns, &mut self.current_turn, &mut self.notification, self.enemies_start_draw_height)?;
+          enemy.update(ctx, party, &mut self.current_turn, &mut self.notification, self.enemies_start_draw_height)?;
+          if enemy.animation.1 == 0 && self.dialogue.is_none() {
+            if let Some(phase) = enemy.update_phase(ctx)? {
+              if !phase.dialogue.is_empty() {
+                self.dialogue = Some(Dialogue::new(ctx, enemy.name.to_owned(), None, &phase.dialogue));
+              }
+            }
+          }
           if enemy.dead == true {
             dead_enemies.push((i, j));
-            self.experience_gained += enemy.state.experience;
+            self.rewards.experience += enemy.state.experience;
+            self.rewards.gold = self.rewards.gold.saturating_add(enemy.gold);
+            for (item, amount) in enemy.roll_drops(&mut party.rng) {
+              self.rewards.add_item(item, amount);
+            }
           } else if enemy.state.hp == 0 {
             enemy.selection_pos.0 = 0; // turns enemy untargetable, due to party occupying column 0
           }
         }
       }
-      for position in dead_enemies {
-        if self.enemies[position.0].len() > 1 {
-          self.enemies[position.0].remove(position.1);
-          for enemy in &mut self.enemies[position.0] {
-            if enemy.selection_pos.1 > position.1 {
-              enemy.selection_pos.1 -= 1;
-            }
-          }
-        } else {
-          self.enemies.remove(position.0);
-          for enemy in self.enemies.iter_mut().flatten() {
-            if enemy.selection_pos.0 > position.0 {
-              enemy.selection_pos.0 -= 1;
-            }
-          }
-        }
-      }
+      remove_positions(&mut self.enemies, &mut dead_enemies, |enemy| &mut enemy.selection_pos);
       if self.enemies.len() == 0 && !self.battle_over.0 {
         self.battle_over = (true, ticks(ctx));
+      } else if self.get_alive_positions(party).iter().all(|position| position.0 > 0) && !self.battle_over.0 {
+        self.battle_over = (true, ticks(ctx));
+        self.lost = true;
+        self.notification = Some(Notification::new(ctx, "The party has fallen".to_owned()));
       }
       if let Some(notification) = &mut self.notification {
         if notification.show_time > 0 {
@@ -119,9 +259,92 @@ impl Battle {
     Ok(())
   }
 
+  // Fills everyone's gauge except the one whose turn it is, their gauge stays full until the turn ends
+  fn update_atb(&mut self, party: &mut Party) {
+    let (battle_speed, current_turn) = (party.settings.battle_speed, self.current_turn);
+    let characters = vec![&mut party.first, &mut party.second, &mut party.third, &mut party.fourth];
+    let party_states = characters.into_iter().filter(|character| !character.name.is_empty()).map(|character| &mut character.state);
+    let enemy_states = self.enemies.iter_mut().flatten().map(|enemy| &mut enemy.state);
+    let ready = party_states.chain(enemy_states)
+      .filter(|state| state.id != current_turn)
+      .filter_map(|state| match state.update_atb(battle_speed) {
+        true  => Some((state.id, state.get_agility())),
+        false => None
+      })
+      .collect();
+    self.turn_queue.push_ready(ready);
+  }
+
+  // Ids of the next count turns after the current one, see TurnQueue::predict
+  pub fn predict_turns(&self, party: &Party, count: usize) -> Vec<u8> {
+    let characters = [&party.first, &party.second, &party.third, &party.fourth];
+    let states: Vec<&BattleState> = characters.iter()
+      .filter(|character| !character.name.is_empty())
+      .map(|character| &character.state)
+      .chain(self.enemies.iter().flatten().map(|enemy| &enemy.state))
+      .collect();
+    self.turn_queue.predict(&states, self.current_turn, party.settings.battle_speed, count)
+  }
+
+  fn update_timeline(&mut self, ctx: &mut Context, party: &Party) {
+    let predicted = self.predict_turns(party, TIMELINE_LENGTH);
+    if predicted == self.timeline.0 {
+      return;
+    }
+    let mut items = vec![MenuItem::new(ctx, "".to_owned(), "Next".to_owned(), (410., 124.), 24., OnClickEvent::None)];
+    for (index, id) in predicted.iter().enumerate() {
+      let name = match id {
+        1 => party.first .name.to_owned(),
+        2 => party.second.name.to_owned(),
+        3 => party.third .name.to_owned(),
+        4 => party.fourth.name.to_owned(),
+        _ => self.enemies.iter().flatten().find(|enemy| enemy.state.id == *id).map(|enemy| enemy.name.to_owned()).unwrap_or_default()
+      };
+      items.push(MenuItem::new(ctx, "".to_owned(), name, (410., 174. + index as f32 * 40.), 24., OnClickEvent::None));
+    }
+    self.timeline = (predicted, items);
+  }
+
+  // A queued turn is dropped if its combatant fell or left the battle while waiting
+  fn can_act(&self, party: &Party, id: u8) -> bool {
+    match id {
+      1 => party.first .state.hp > 0,
+      2 => party.second.state.hp > 0,
+      3 => party.third .state.hp > 0,
+      4 => party.fourth.state.hp > 0,
+      _ => self.enemies.iter().flatten().any(|enemy| enemy.state.id == id && enemy.state.hp > 0)
+    }
+  }
+
+  // Target selection positions of everyone still standing, enemies first
+  fn get_alive_positions(&self, party: &Party) -> Vec<(usize, usize)> {
+    let mut positions = Vec::new();
+    for (i, enemy_column) in self.enemies.iter().enumerate() {
+      for (j, enemy) in enemy_column.iter().enumerate() {
+        if enemy.state.hp > 0 {
+          positions.push((i + 1, j));
+        }
+      }
+    }
+    positions.extend(party.get_alive_positions());
+    positions
+  }
+
+  // Everyone hit by an action aimed at the target, see turn::get_targets
+  pub fn get_targets(&self, party: &mut Party, target: (usize, usize), action_parameters: &ActionParameters) -> Vec<(usize, usize)> {
+    let alive = self.get_alive_positions(party);
+    turn::get_targets(&mut party.rng, &alive, target, action_parameters)
+  }
+
   pub fn draw(&mut self, ctx: &mut Context, party: &mut Party, battle_menu: &mut MenuScreen) -> GameResult<()> {
     self.party_info_container.draw(ctx)?;
     party.draw(ctx)?;
+    if !self.battle_over.0 {
+      self.timeline_container.draw(ctx)?;
+      for item in self.timeline.1.iter_mut() {
+        item.draw(ctx)?;
+      }
+    }
     for enemy_column in self.enemies.iter_mut() {
       for enemy in enemy_column.iter_mut() {
         enemy.draw(ctx, self.enemies_start_draw_height)?;
@@ -131,6 +354,67 @@ impl Battle {
     if let Some(notification) = &mut self.notification {
       notification.draw(ctx)?;
     }
+    if let Some(dialogue) = &mut self.dialogue {
+      dialogue.draw(ctx)?;
+    }
     Ok(())
   }
-}
\ No newline at end of file
+}
+
+// Removes the given (column, row) positions, columns left empty go as well and the selection positions of the rest close the gap.
+// Highest positions are removed first, so several enemies can die in the same frame without shifting the ones still waiting.
+fn remove_positions<T>(columns: &mut Vec<Vec<T>>, positions: &mut [(usize, usize)], selection_pos: fn(&mut T) -> &mut (usize, usize)) {
+  positions.sort_unstable_by(|a, b| b.cmp(a));
+  for position in positions.iter() {
+    if columns[position.0].len() > 1 {
+      columns[position.0].remove(position.1);
+      for remaining in &mut columns[position.0] {
+        let selection_pos = selection_pos(remaining);
+        if selection_pos.1 > position.1 {
+          selection_pos.1 -= 1;
+        }
+      }
+    } else {
+      columns.remove(position.0);
+      for remaining in columns.iter_mut().flatten() {
+        let selection_pos = selection_pos(remaining);
+        if selection_pos.0 > position.0 {
+          selection_pos.0 -= 1;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::remove_positions;
+
+  // Named stand-ins for enemies with their selection positions, which index Battle::enemies
+  fn formation(columns: &[&[char]]) -> Vec<Vec<(char, (usize, usize))>> {
+    columns.iter().enumerate()
+      .map(|(i, column)| column.iter().enumerate().map(|(j, name)| (*name, (i, j))).collect())
+      .collect()
+  }
+
+  #[test]
+  fn removes_two_columns_in_one_action() {
+    let mut enemies = formation(&[&['a'], &['b'], &['c']]);
+    remove_positions(&mut enemies, &mut [(0, 0), (1, 0)], |enemy| &mut enemy.1);
+    assert_eq!(enemies, formation(&[&['c']]));
+  }
+
+  #[test]
+  fn removes_two_rows_of_a_column_in_one_action() {
+    let mut enemies = formation(&[&['a', 'b', 'c'], &['d']]);
+    remove_positions(&mut enemies, &mut [(0, 0), (0, 1)], |enemy| &mut enemy.1);
+    assert_eq!(enemies, formation(&[&['c'], &['d']]));
+  }
+
+  #[test]
+  fn removes_a_whole_column_and_a_row_behind_it() {
+    let mut enemies = formation(&[&['a', 'b'], &['c', 'd']]);
+    remove_positions(&mut enemies, &mut [(0, 1), (1, 0), (0, 0)], |enemy| &mut enemy.1);
+    assert_eq!(enemies, formation(&[&['d']]));
+  }
+}
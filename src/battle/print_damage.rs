@@ -1,11 +1,14 @@
 use ggez::graphics::{spritebatch, Color, Image, DrawParam, draw};
 use ggez::nalgebra::Point2;
 use ggez::{Context, GameResult};
-use crate::data::font::number_param;
+use ggez::nalgebra::Vector2;
+use crate::data::font::{font_param, number_param};
 
 pub struct PrintDamage {
   spritebatch: spritebatch::SpriteBatch,
   value: u16,
+  text: Option<String>, // Drawn with the font instead of the value
+  scale: f32,
   screen_pos: (f32, f32),
   color: Color,
   pub show_time: f32
@@ -18,12 +21,31 @@ impl PrintDamage {
     PrintDamage {
       spritebatch: batch,
       value,
+      text: None,
+      scale: 1.,
       screen_pos,
       color,
       show_time: 120.
     }
   }
 
+  // Larger and orange
+  pub fn critical(ctx: &mut Context, value: u16, screen_pos: (f32, f32)) -> PrintDamage {
+    PrintDamage {
+      scale: 1.5,
+      ..PrintDamage::new(ctx, value, screen_pos, Color::new(1., 0.6, 0., 1.))
+    }
+  }
+
+  pub fn miss(ctx: &mut Context, screen_pos: (f32, f32)) -> PrintDamage {
+    let image = Image::new(ctx, "/font.png").unwrap();
+    PrintDamage {
+      spritebatch: spritebatch::SpriteBatch::new(image),
+      text: Some("Miss".to_owned()),
+      ..PrintDamage::new(ctx, 0, screen_pos, Color::new(1., 1., 1., 1.))
+    }
+  }
+
   pub fn update(&mut self) -> GameResult<()> {
     self.show_time -= 1.;
     self.screen_pos.1 -= 0.000035 * self.show_time * self.show_time;
@@ -34,9 +56,13 @@ impl PrintDamage {
   }
 
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-    number_param(&mut self.spritebatch, format!("{}", self.value), self.color);
+    match &self.text {
+      Some(text) => font_param(&mut self.spritebatch, text),
+      None => number_param(&mut self.spritebatch, format!("{}", self.value), self.color)
+    }
     let param = DrawParam::new()
-      .dest(Point2::new(self.screen_pos.0, self.screen_pos.1));
+      .dest(Point2::new(self.screen_pos.0, self.screen_pos.1))
+      .scale(Vector2::new(self.scale, self.scale));
     draw(ctx, &self.spritebatch, param)?;
     self.spritebatch.clear();
     Ok(())
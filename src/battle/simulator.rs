@@ -0,0 +1,735 @@
+// Runs battles with the same turn resolution as Battle, see battle::turn, but without a window, animations or a ggez Context.
+// Both sides act instantly when their turn comes up, so a whole fight takes microseconds.
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::battle::action::{ActionParameters, DamageType};
+use crate::battle::behaviour::Behaviour;
+use crate::battle::damage::{self, DamageFormula};
+use crate::battle::encounter::Encounter;
+use crate::battle::enemy::SELF_TARGET;
+use crate::battle::state::BattleState;
+use crate::battle::turn::{self, ActionOutcome, FleeOutcome};
+use crate::battle::turn_queue::TurnQueue;
+use crate::battle::status_effect::StatusEffect;
+use crate::party::{self, InventoryElement};
+use crate::party::equipment::Equipped;
+use crate::party::item::InventoryItem;
+use crate::data::content::{self, CharacterDefinition, CombatDefinition, EnemyDefinition, EquipmentDefinition, FormationDefinition, SpellDefinition};
+use crate::data::{characters, enemies, enemy_formations, equipment};
+use crate::random::{self, GameRng};
+use crate::save::settings::Settings;
+
+const MAX_TURNS: u32 = 1000;
+const MAX_TICKS: u32 = 1_000_000; // Stops battles where nobody can act anymore, like everyone asleep
+
+#[derive(Clone, Copy)]
+pub enum Policy {
+  Attack,       // Attack the first enemy still standing
+  FocusWeakest, // Attack the enemy with the least HP
+  Careful,      // Heal anyone below a third of their HP first, with a healing ability, spell or a Potion, or step back when hurt with nothing to heal
+  Magic,        // Cast the strongest damaging spell or ability the member can pay for, on the whole scope when it has one
+  Flee          // Members who know Flee try it every turn unless a boss is there, the rest attack
+}
+
+impl Policy {
+  pub fn from_name(name: &str) -> Option<Policy> {
+    match name {
+      "attack"  => Some(Policy::Attack),
+      "weakest" => Some(Policy::FocusWeakest),
+      "careful" => Some(Policy::Careful),
+      "magic"   => Some(Policy::Magic),
+      "flee"    => Some(Policy::Flee),
+      _ => None
+    }
+  }
+}
+
+pub enum BattleResult {
+  Won, Lost, Fled, TimedOut
+}
+pub struct BattleReport {
+  pub result: BattleResult,
+  pub turns: u32,
+  pub damage_dealt: u32,
+  pub damage_taken: u32,
+  pub items_used: HashMap<String, u32>
+}
+
+#[derive(Default)]
+pub struct SimulationSummary {
+  pub seed: u64, // Running again with the same seed gives the same summary
+  pub battles: u32,
+  pub wins: u32,
+  pub losses: u32,
+  pub fled: u32,
+  pub timeouts: u32,
+  pub turns: u32,
+  pub damage_dealt: u32,
+  pub damage_taken: u32,
+  pub items_used: HashMap<String, u32>
+}
+
+impl SimulationSummary {
+  pub fn add(&mut self, report: BattleReport) {
+    self.battles += 1;
+    match report.result {
+      BattleResult::Won      => self.wins += 1,
+      BattleResult::Lost     => self.losses += 1,
+      BattleResult::Fled     => self.fled += 1,
+      BattleResult::TimedOut => self.timeouts += 1
+    }
+    self.turns += report.turns;
+    self.damage_dealt += report.damage_dealt;
+    self.damage_taken += report.damage_taken;
+    for (item, amount) in report.items_used {
+      *self.items_used.entry(item).or_insert(0) += amount;
+    }
+  }
+
+  pub fn win_rate(&self) -> f32 {
+    self.wins as f32 / self.battles.max(1) as f32
+  }
+
+  fn average(&self, total: u32) -> f32 {
+    total as f32 / self.battles.max(1) as f32
+  }
+}
+
+impl fmt::Display for SimulationSummary {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "Battles: {} (seed {})", self.battles, self.seed)?;
+    writeln!(f, "Won: {} ({:.1}%), lost: {}, fled: {}, timed out: {}", self.wins, self.win_rate() * 100., self.losses, self.fled, self.timeouts)?;
+    writeln!(f, "Average turns: {:.1}", self.average(self.turns))?;
+    writeln!(f, "Average damage dealt: {:.1}, taken: {:.1}", self.average(self.damage_dealt), self.average(self.damage_taken))?;
+    let mut items_used: Vec<String> = self.items_used.iter().map(|(item, amount)| format!("{} x{}", item, amount)).collect();
+    items_used.sort();
+    match items_used.is_empty() {
+      true  => write!(f, "Items used: none"),
+      false => write!(f, "Items used: {}", items_used.join(", "))
+    }
+  }
+}
+
+// Everything battles are built from besides the party
+pub struct SimulationContent {
+  pub enemies: Vec<EnemyDefinition>,
+  pub formations: Vec<FormationDefinition>,
+  pub combat: CombatDefinition,
+  pub equipment: Vec<EquipmentDefinition>,
+  pub spells: Vec<SpellDefinition>
+}
+
+impl SimulationContent {
+  // From the resources folder, see content::read_characters
+  pub fn read() -> Result<SimulationContent, String> {
+    let to_string = |error| format!("{}", error);
+    Ok(SimulationContent {
+      enemies: content::read_enemies().map_err(to_string)?,
+      formations: content::read_formations().map_err(to_string)?,
+      combat: content::read_combat().map_err(to_string)?,
+      equipment: content::read_equipment().map_err(to_string)?,
+      spells: content::read_spells().map_err(to_string)?
+    })
+  }
+}
+
+struct Combatant {
+  state: BattleState,
+  attack: Option<ActionParameters>,
+  abilities: Vec<ActionParameters>, // The other abilities that target someone
+  spells: Vec<ActionParameters>, // With their MP cost
+  flee: bool, // Knows Flee
+  behaviour: Option<Behaviour>,
+  boss: bool,
+  escapeable: bool
+}
+
+impl Combatant {
+  // Abilities, and spells while the member can cast, it can pay for. Scoped ones hit the whole scope.
+  fn usable(&self) -> impl Iterator<Item = ActionParameters> + '_ {
+    let spells = match self.state.can_cast() {
+      true  => &self.spells[..],
+      false => &[]
+    };
+    self.abilities.iter().chain(spells.iter())
+      .filter(move |action_parameters| action_parameters.mp_cost <= self.state.mp)
+      .map(|action_parameters| ActionParameters {group: action_parameters.scope.is_toggleable(), ..action_parameters.clone()})
+  }
+}
+
+fn party_member(
+  id: u8,
+  definition: &CharacterDefinition,
+  equipment: &[EquipmentDefinition],
+  spells: &[SpellDefinition],
+  encounter: Encounter
+) -> Combatant {
+  let mut state = BattleState::new(
+    id, definition.level, definition.hp, definition.mp, definition.attack, definition.defence, definition.magic,
+    definition.resistance, definition.agility, 0, Vec::new(), Vec::new(), definition.back_row, None, None, None
+  );
+  // The starting equipment, the simulated party never changes it
+  let mut equipped = Equipped::default();
+  for name in definition.equipment.iter() {
+    if let Some(piece) = equipment.iter().find(|piece| &piece.name == name).and_then(equipment::build_equipment) {
+      equipped.replace(piece.slot, Some(piece));
+    }
+  }
+  state.equipment = equipped.get_bonus();
+  if encounter.start_party_member(&mut state) {
+    state.back_row = !state.back_row;
+  }
+  let mut spell_names = definition.spells.clone();
+  spell_names.extend(state.equipment.spells.iter().filter(|name| !definition.spells.contains(name)).cloned());
+  let abilities = [&definition.primary_ability, &definition.secondary_ability];
+  Combatant {
+    attack: characters::get_action_parameters(&definition.attack_ability),
+    abilities: abilities.iter().filter_map(|ability| characters::get_action_parameters(ability)).collect(),
+    spells: spell_names.iter()
+      .filter_map(|name| spells.iter().find(|spell| &spell.ability.name == name))
+      .filter_map(characters::get_spell_parameters)
+      .collect(),
+    flee: abilities.iter().any(|ability| ability.kind == "Flee"),
+    behaviour: None,
+    boss: false,
+    escapeable: false,
+    state
+  }
+}
+
+fn enemy(id: u8, definition: &EnemyDefinition, column: usize) -> Combatant {
+  let mut state = BattleState::new(
+    id, definition.level, definition.hp, definition.mp, definition.attack, definition.defence, definition.magic,
+    definition.resistance, definition.agility, definition.experience,
+    definition.status_effects.iter().filter_map(|name| StatusEffect::from_name(name)).collect(),
+    definition.immunities.iter().filter_map(|name| StatusEffect::from_name(name)).collect(),
+    enemies::is_back_row(definition, column),
+    definition.common_steal.as_deref().and_then(InventoryItem::from_name),
+    definition.rare_steal.as_deref().and_then(InventoryItem::from_name),
+    None
+  );
+  state.affinities = enemies::get_affinities(definition);
+  Combatant {
+    state,
+    attack: None,
+    abilities: Vec::new(),
+    spells: Vec::new(),
+    flee: false,
+    behaviour: Some(enemies::get_behaviour(definition)),
+    boss: definition.boss,
+    escapeable: definition.escapeable
+  }
+}
+
+// The enemies of the formation arranged for the encounter, numbered from 5 like in a battle
+fn enemy_columns(definitions: &[EnemyDefinition], formation: &FormationDefinition, encounter: Encounter) -> Result<Vec<Vec<Combatant>>, String> {
+  let mut id = 5;
+  let mut columns = Vec::new();
+  for (column, side_column, _) in enemy_formations::arrange_columns(formation, encounter) {
+    let mut enemy_column = Vec::new();
+    for enemy_name in column.iter() {
+      match definitions.iter().find(|definition| &definition.name == enemy_name) {
+        Some(definition) => enemy_column.push(enemy(id, definition, side_column)),
+        None => return Err(format!("Formation uses unknown enemy {}", enemy_name))
+      }
+      id += 1;
+    }
+    columns.push(enemy_column);
+  }
+  Ok(columns)
+}
+
+// Both sides by target selection position, the party is column 0 and the enemy columns follow as in Battle::enemies
+struct Field {
+  party: Vec<Combatant>,
+  enemies: Vec<Vec<Combatant>>
+}
+
+impl Field {
+  fn get_mut(&mut self, position: (usize, usize)) -> &mut Combatant {
+    match position.0 {
+      0 => &mut self.party[position.1],
+      _ => &mut self.enemies[position.0 - 1][position.1]
+    }
+  }
+
+  fn position_of(&self, id: u8) -> Option<(usize, usize)> {
+    match id {
+      1..=4 => Some((0, id as usize - 1)),
+      _ => self.enemies.iter().enumerate()
+        .find_map(|(i, column)| column.iter().position(|enemy| enemy.state.id == id).map(|j| (i + 1, j)))
+    }
+  }
+
+  // Positions of everyone still standing, enemies first
+  fn get_alive_positions(&self) -> Vec<(usize, usize)> {
+    let mut positions: Vec<(usize, usize)> = self.enemies.iter().enumerate()
+      .flat_map(|(i, column)| column.iter().enumerate().filter(|(_, enemy)| enemy.state.hp > 0).map(move |(j, _)| (i + 1, j)))
+      .collect();
+    positions.extend(self.get_party_alive_positions());
+    positions
+  }
+
+  fn get_party_alive_positions(&self) -> Vec<(usize, usize)> {
+    self.party.iter().enumerate().filter(|(_, member)| member.state.hp > 0).map(|(index, _)| (0, index)).collect()
+  }
+
+  // Fallen enemies leave the battle, columns left empty go with them
+  fn remove_dead(&mut self) {
+    for column in self.enemies.iter_mut() {
+      column.retain(|enemy| enemy.state.hp > 0);
+    }
+    self.enemies.retain(|column| !column.is_empty());
+  }
+
+  fn everyone_mut(&mut self) -> impl Iterator<Item = &mut Combatant> {
+    self.party.iter_mut().chain(self.enemies.iter_mut().flatten())
+  }
+}
+
+enum Choice {
+  Act((usize, usize), ActionParameters),
+  Flee,
+  Row,
+  Nothing
+}
+
+fn find_item(inventory: &[InventoryElement], name: &str) -> Option<InventoryItem> {
+  inventory.iter().find_map(|inventory_element| match inventory_element {
+    InventoryElement::Item(item, amount) if item.get_name() == name && *amount > 0 => Some(item.clone()),
+    _ => None
+  })
+}
+
+// What the policy has the member do, berserk and confused members attack at random like in a battle
+fn choose_party_action(field: &Field, actor: usize, inventory: &[InventoryElement], rng: &mut GameRng, policy: Policy) -> Choice {
+  let member = &field.party[actor];
+  if let Some(forced_action) = member.state.get_forced_action() {
+    let alive = field.get_alive_positions();
+    return match (&member.attack, turn::get_forced_target(rng, &alive, forced_action)) {
+      (Some(attack), Some(target)) => Choice::Act(target, attack.clone()),
+      _ => Choice::Nothing
+    };
+  }
+  if let (Policy::Flee, true) = (policy, member.flee && !field.enemies.iter().flatten().any(|enemy| enemy.boss)) {
+    return Choice::Flee;
+  }
+  if let Policy::Careful = policy {
+    if let Some(hurt) = field.party.iter().position(|member| member.state.hp > 0 && (member.state.hp as u32) * 3 < member.state.max_hp as u32) {
+      if let Some(healing) = member.usable().find(|action_parameters| matches!(action_parameters.damage_type, DamageType::Healing)) {
+        return Choice::Act((0, hurt), healing);
+      }
+      if let Some(potion) = find_item(inventory, "Potion") {
+        return Choice::Act((0, hurt), potion.get_action_parameters());
+      }
+      if hurt == actor && !member.state.back_row {
+        return Choice::Row;
+      }
+    }
+  }
+  let enemies = field.get_alive_positions().into_iter().filter(|position| position.0 > 0);
+  let target = match policy {
+    Policy::FocusWeakest => enemies.min_by_key(|position| field.enemies[position.0 - 1][position.1].state.hp),
+    _ => enemies.min()
+  };
+  let target = match target {
+    Some(target) => target,
+    None => return Choice::Nothing
+  };
+  if let Policy::Magic = policy {
+    let damaging = member.usable()
+      .filter(|action_parameters| matches!(action_parameters.damage_type, DamageType::Physical | DamageType::Magical | DamageType::Pure))
+      .max_by_key(|action_parameters| action_parameters.power);
+    if let Some(action_parameters) = damaging {
+      return Choice::Act(target, action_parameters);
+    }
+  }
+  match &member.attack {
+    Some(attack) => Choice::Act(target, attack.clone()),
+    None => Choice::Nothing
+  }
+}
+
+// Carries out a prepared action on each target, counting the damage and the items used
+fn act_on_targets(
+  field: &mut Field,
+  inventory: &mut [InventoryElement],
+  rng: &mut GameRng,
+  targets: &[(usize, usize)],
+  action_parameters: &ActionParameters,
+  report: &mut BattleReport
+) {
+  for target in targets {
+    let state = &mut field.get_mut(*target).state;
+    let hp_before = state.hp;
+    let outcome = turn::apply_action(rng, inventory, state, action_parameters);
+    let damage = hp_before.saturating_sub(state.hp) as u32;
+    match target.0 {
+      0 => report.damage_taken += damage,
+      _ => report.damage_dealt += damage
+    }
+    if let ActionOutcome::Item(item, _) = outcome {
+      *report.items_used.entry(item.get_name()).or_insert(0) += 1;
+    }
+  }
+}
+
+// Returns true if the party got away
+fn party_turn(
+  actor: usize,
+  field: &mut Field,
+  inventory: &mut [InventoryElement],
+  rng: &mut GameRng,
+  policy: Policy,
+  formula: &Rc<dyn DamageFormula>,
+  report: &mut BattleReport
+) -> bool {
+  match choose_party_action(field, actor, inventory, rng, policy) {
+    Choice::Act(target, action_parameters) => {
+      if !field.party[actor].state.spend_mp(action_parameters.mp_cost) {
+        return false;
+      }
+      let targets = turn::get_targets(rng, &field.get_alive_positions(), target, &action_parameters);
+      let action_parameters = turn::prepare_action(&field.party[actor].state, formula, &action_parameters, targets.len());
+      act_on_targets(field, inventory, rng, &targets, &action_parameters, report);
+    },
+    Choice::Flee => {
+      let boss = field.enemies.iter().flatten().any(|enemy| enemy.boss);
+      let escapeable = field.enemies.iter().flatten().any(|enemy| enemy.escapeable);
+      if let FleeOutcome::Escaped = turn::roll_flee(rng, boss, escapeable) {
+        return true;
+      }
+    },
+    Choice::Row => field.party[actor].state.back_row = !field.party[actor].state.back_row,
+    Choice::Nothing => ()
+  }
+  false
+}
+
+fn enemy_turn(
+  position: (usize, usize),
+  field: &mut Field,
+  inventory: &mut [InventoryElement],
+  rng: &mut GameRng,
+  formula: &Rc<dyn DamageFormula>,
+  report: &mut BattleReport
+) {
+  let party_alive = field.get_party_alive_positions();
+  let party = &field.party;
+  let party_targets: Vec<(u8, &BattleState)> = party_alive.iter().map(|(_, index)| (*index as u8, &party[*index].state)).collect();
+  let enemy = &mut field.enemies[position.0 - 1][position.1];
+  let action = match &mut enemy.behaviour {
+    Some(behaviour) => turn::choose_enemy_action(rng, behaviour, &mut enemy.state, &party_targets),
+    None => None
+  };
+  let (target, _, action_parameters) = match action {
+    Some(action) => action,
+    None => return
+  };
+  let targets: Vec<(usize, usize)> = turn::get_enemy_targets(rng, &party_alive, target, &action_parameters).into_iter()
+    .map(|target| match target {
+      SELF_TARGET => position,
+      _ => (0, target as usize)
+    })
+    .collect();
+  let action_parameters = turn::prepare_action(&enemy.state, formula, &action_parameters, targets.len());
+  act_on_targets(field, inventory, rng, &targets, &action_parameters, report);
+}
+
+fn simulate_battle(
+  mut field: Field,
+  inventory: &mut [InventoryElement],
+  rng: &mut GameRng,
+  policy: Policy,
+  formula: &Rc<dyn DamageFormula>
+) -> BattleReport {
+  let mut report = BattleReport {result: BattleResult::TimedOut, turns: 0, damage_dealt: 0, damage_taken: 0, items_used: HashMap::new()};
+  let mut turn_queue = TurnQueue::default();
+  let mut current_turn = 0;
+  let mut ticks = 0;
+  while report.turns < MAX_TURNS && ticks < MAX_TICKS {
+    if field.enemies.is_empty() {
+      report.result = BattleResult::Won;
+      break;
+    }
+    if field.party.iter().all(|member| member.state.hp == 0) {
+      report.result = BattleResult::Lost;
+      break;
+    }
+    if current_turn == 0 {
+      if let Some(next_turn) = turn_queue.pop() {
+        current_turn = next_turn;
+      }
+    }
+    if current_turn == 0 {
+      ticks += 1;
+      let ready = field.everyone_mut()
+        .filter_map(|combatant| match combatant.state.update_atb(Settings::default().battle_speed) {
+          true  => Some((combatant.state.id, combatant.state.get_agility())),
+          false => None
+        })
+        .collect();
+      turn_queue.push_ready(ready);
+      continue;
+    }
+    let position = match field.position_of(current_turn) {
+      Some(position) if field.get_mut(position).state.hp > 0 => position,
+      _ => {
+        current_turn = 0;
+        continue;
+      }
+    };
+    current_turn = 0;
+    report.turns += 1;
+    match position.0 {
+      0 => if party_turn(position.1, &mut field, inventory, rng, policy, formula, &mut report) {
+        report.result = BattleResult::Fled;
+        break;
+      },
+      _ => enemy_turn(position, &mut field, inventory, rng, formula, &mut report)
+    }
+    let outcome = field.get_mut(position).state.apply_end_turn();
+    let tick_damage: i32 = outcome.hp_changes.iter().map(|(_, hp_change)| -hp_change).filter(|damage| *damage > 0).sum();
+    match position.0 {
+      0 => report.damage_taken += tick_damage as u32,
+      _ => report.damage_dealt += tick_damage as u32
+    }
+    for enemy in field.enemies.iter_mut().flatten() {
+      if let Some(behaviour) = &mut enemy.behaviour {
+        behaviour.update_phase(&enemy.state);
+      }
+    }
+    field.remove_dead();
+  }
+  report
+}
+
+// Battles against a named formation, or a random formation of a table each time
+pub fn simulate(
+  party_definitions: &[&CharacterDefinition],
+  content: &SimulationContent,
+  formation: &str,
+  policy: Policy,
+  battles: u32,
+  seed: u64
+) -> Result<SimulationSummary, String> {
+  let mut summary = SimulationSummary {seed, ..SimulationSummary::default()};
+  let mut rng = random::from_seed(seed);
+  let formula = damage::get_formula(&content.combat);
+  let (formations, equipment, spells) = (&content.formations, &content.equipment, &content.spells);
+  for _ in 0..battles {
+    // A named formation is a scripted battle and starts normally, a table rolls its formation and how it starts
+    let (chosen_formation, encounter) = match formations.iter().find(|definition| definition.name == formation) {
+      Some(definition) => (definition, Encounter::Normal),
+      None => match enemy_formations::pick_formation(&mut rng, formations, formation) {
+        Some(definition) => {
+          let party: Vec<Combatant> = party_definitions.iter().enumerate()
+            .map(|(index, definition)| party_member(index as u8 + 1, definition, equipment, spells, Encounter::Normal))
+            .collect();
+          let members: Vec<&BattleState> = party.iter().map(|member| &member.state).collect();
+          (definition, Encounter::roll_for_party(&mut rng, &content.combat, &members, definition.columns.iter().flatten().count()))
+        },
+        None => return Err(format!("No formation or formation table called {}", formation))
+      }
+    };
+    let field = Field {
+      party: party_definitions.iter().enumerate()
+        .map(|(index, definition)| party_member(index as u8 + 1, definition, equipment, spells, encounter))
+        .collect(),
+      enemies: enemy_columns(&content.enemies, chosen_formation, encounter)?
+    };
+    let mut inventory = party::starting_inventory();
+    summary.add(simulate_battle(field, &mut inventory, &mut rng, policy, &formula));
+  }
+  Ok(summary)
+}
+
+// simulate <formation name or table> [--battles N] [--policy attack|weakest|careful|magic|flee] [--party Name,Name] [--seed N]
+pub fn run_from_args(args: &[String]) -> Result<SimulationSummary, String> {
+  let mut formation = None;
+  let mut battles = 100;
+  let mut seed = random::new_seed();
+  let mut policy = Policy::Attack;
+  let mut party_names: Vec<String> = party::STARTING_PARTY.iter().map(|name| (*name).to_owned()).collect();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--battles" => battles = args.next().and_then(|value| value.parse().ok()).ok_or("--battles needs a number")?,
+      "--policy"  => policy = args.next().and_then(|value| Policy::from_name(value)).ok_or("--policy needs one of attack, weakest, careful, magic or flee")?,
+      "--seed"    => seed = args.next().and_then(|value| value.parse().ok()).ok_or("--seed needs a number")?,
+      "--party"   => party_names = args.next().ok_or("--party needs a comma separated list of names")?.split(',').map(|name| name.to_owned()).collect(),
+      _ => formation = Some(arg.to_owned())
+    }
+  }
+  let formation = formation.ok_or("Usage: simulate <formation name or table> [--battles N] [--policy attack|weakest|careful|magic|flee] [--party Name,Name] [--seed N]")?;
+  let character_definitions = content::read_characters().map_err(|error| format!("{}", error))?;
+  let simulation_content = SimulationContent::read()?;
+  let mut party_definitions = Vec::new();
+  for name in party_names.iter().take(4) {
+    match character_definitions.iter().find(|definition| &definition.name == name) {
+      Some(definition) => party_definitions.push(definition),
+      None => return Err(format!("Unknown character {}", name))
+    }
+  }
+  simulate(&party_definitions, &simulation_content, &formation, policy, battles, seed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{simulate, Policy, SimulationContent, SimulationSummary};
+  use crate::data::content::{self, CombatDefinition};
+
+  const CHARACTERS: &str = r#"
+    [[character]]
+    name = "Hero"
+    spritefile = ""
+    avatar_spritefile = ""
+    level = 50
+    hp = 9999
+    mp = 0
+    attack = 99
+    defence = 99
+    magic = 1
+    resistance = 99
+    agility = 99
+    attack_ability = { name = "Attack", kind = "Physical", power = 50 }
+    primary_ability = { name = "Flee", kind = "Flee" }
+    secondary_ability = { name = "Wait", kind = "None" }
+
+    [[character]]
+    name = "Weakling"
+    spritefile = ""
+    avatar_spritefile = ""
+    level = 1
+    hp = 10
+    mp = 0
+    attack = 1
+    defence = 1
+    magic = 1
+    resistance = 1
+    agility = 1
+    attack_ability = { name = "Attack", kind = "Physical", power = 1 }
+    primary_ability = { name = "Wait", kind = "None" }
+    secondary_ability = { name = "Wait", kind = "None" }
+  "#;
+
+  const ENEMIES: &str = r#"
+    [[enemy]]
+    name = "Slime"
+    spritefile = ""
+    size = 1.0
+    level = 1
+    hp = 10
+    mp = 0
+    attack = 1
+    defence = 1
+    magic = 1
+    resistance = 1
+    agility = 1
+    experience = 1
+    escapeable = true
+
+    [[enemy]]
+    name = "Brute"
+    spritefile = ""
+    size = 1.0
+    level = 50
+    hp = 9999
+    mp = 0
+    attack = 99
+    defence = 99
+    magic = 1
+    resistance = 99
+    agility = 99
+    experience = 1
+    behaviour = [{ ability = "Attack" }]
+
+    [[enemy]]
+    name = "Wall"
+    spritefile = ""
+    size = 1.0
+    level = 99
+    hp = 65535
+    mp = 0
+    attack = 1
+    defence = 255
+    magic = 1
+    resistance = 255
+    agility = 1
+    experience = 1
+    escapeable = true
+    behaviour = [{ ability = "Nothing" }]
+  "#;
+
+  const FORMATIONS: &str = r#"
+    [[formation]]
+    table = "field"
+    weight = 2.0
+    columns = [["Slime", "Slime"]]
+
+    [[formation]]
+    table = "field"
+    weight = 1.0
+    columns = [["Slime"], ["Slime"]]
+
+    [[formation]]
+    name = "brute"
+    table = ""
+    weight = 0.0
+    columns = [["Brute"]]
+
+    [[formation]]
+    name = "wall"
+    table = ""
+    weight = 0.0
+    columns = [["Wall"]]
+  "#;
+
+  fn run(party_names: &[&str], formation: &str, policy: Policy, seed: u64) -> SimulationSummary {
+    let characters = content::parse_characters(CHARACTERS).unwrap();
+    let party: Vec<&content::CharacterDefinition> = party_names.iter()
+      .map(|name| characters.iter().find(|definition| &definition.name == name).unwrap())
+      .collect();
+    let simulation_content = SimulationContent {
+      enemies: content::parse_enemies(ENEMIES).unwrap(),
+      formations: content::parse_formations(FORMATIONS).unwrap(),
+      combat: CombatDefinition::default(),
+      equipment: Vec::new(),
+      spells: Vec::new()
+    };
+    simulate(&party, &simulation_content, formation, policy, 20, seed).unwrap()
+  }
+
+  fn outcomes(summary: &SimulationSummary) -> (u32, u32, u32, u32) {
+    (summary.wins, summary.losses, summary.fled, summary.timeouts)
+  }
+
+  #[test]
+  fn same_seed_gives_the_same_summary() {
+    let first = run(&["Hero", "Weakling"], "field", Policy::Attack, 7);
+    let second = run(&["Hero", "Weakling"], "field", Policy::Attack, 7);
+    assert_eq!(outcomes(&first), outcomes(&second));
+    assert_eq!((first.turns, first.damage_dealt, first.damage_taken), (second.turns, second.damage_dealt, second.damage_taken));
+  }
+
+  #[test]
+  fn much_stronger_party_always_wins() {
+    assert_eq!(outcomes(&run(&["Hero"], "field", Policy::Attack, 1)), (20, 0, 0, 0));
+  }
+
+  #[test]
+  fn much_weaker_party_always_loses() {
+    assert_eq!(outcomes(&run(&["Weakling"], "brute", Policy::Attack, 1)), (0, 20, 0, 0));
+  }
+
+  #[test]
+  fn fleeing_only_escapes() {
+    assert_eq!(outcomes(&run(&["Hero"], "field", Policy::Flee, 1)), (0, 0, 20, 0));
+  }
+
+  #[test]
+  fn party_that_cannot_win_times_out() {
+    assert_eq!(outcomes(&run(&["Weakling"], "wall", Policy::Attack, 1)), (0, 0, 0, 20));
+  }
+}
@@ -1,13 +1,47 @@
 use ggez::{Context, GameResult};
 use ggez::graphics::{Color, DrawParam, DrawMode, FillOptions, Mesh, Rect, draw};
-use rand::{Rng, thread_rng};
+use rand::Rng;
 use crate::battle::action::{ActionParameters, DamageType};
+use crate::battle::damage::{Attacker, CombatStats, DamageFormula, DamageRoll, ClassicFormula};
+use crate::battle::element::{self, Element, Affinity};
 use crate::battle::print_damage::PrintDamage;
-use crate::party::InventoryElement;
+use crate::battle::status_effect::{StatusEffect, Stat, ForcedAction};
+use crate::battle::turn::ActionOutcome;
 use crate::party::character_info::CharacterInfo;
+use crate::party::equipment::EquipmentBonus;
 use crate::party::item::InventoryItem;
 use crate::menu::notification::Notification;
+use crate::random::GameRng;
 use std::cmp::{min, max};
+use std::rc::Rc;
+
+// Frames between gauge steps at each battle speed, the slowest first
+const ATB_STEP_FRAMES: [u8; 5] = [9, 7, 5, 4, 3];
+
+fn get_step_frames(battle_speed: u8) -> u8 {
+  ATB_STEP_FRAMES[(battle_speed.clamp(1, ATB_STEP_FRAMES.len() as u8) - 1) as usize]
+}
+
+const BACK_ROW_MULTIPLIER: f32 = 0.5;
+
+pub struct DamageOutcome {
+  pub damage: u16, // Healed instead when the element was absorbed
+  pub missed: bool,
+  pub critical: bool,
+  pub affinity: f32,
+  pub killed: bool,
+  pub inflicted: Vec<StatusEffect>
+}
+
+pub struct EndTurnOutcome {
+  pub hp_changes: Vec<(StatusEffect, i32)>, // Ticks of the effects, negative is damage
+  pub ended: Vec<StatusEffect>
+}
+
+pub struct ActiveEffect {
+  pub effect: StatusEffect,
+  pub turns: Option<u8> // None lasts until cured
+}
 
 pub struct BattleState {
   pub id: u8,
@@ -18,15 +52,17 @@ pub struct BattleState {
   pub hp: u16,
   pub mp: u16,
   pub attack: u16,
-  defence: u16,
-  magic: u16,
-  resistance: u16,
-  agility: u8,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8,
   atb: u8,
   atb_subtick: u8,
   pub turn_active: bool,
-  poisoned: i8, // effected for x turns, negative means immunity, 128 and -127 means effect for eternity
-  sleeping: i8, // effected for x turns, negative means immunity, 128 and -127 means effect for eternity
+  status_effects: Vec<ActiveEffect>,
+  immunities: Vec<(StatusEffect, Option<u8>)>, // Turns left, None is permanent
+  pub affinities: Vec<Affinity>, // Own affinities, status effects add theirs on top
+  pub equipment: EquipmentBonus, // Set by the party member's equipment, enemies have none
   pub back_row: bool,
   pub common_steal: Option<InventoryItem>,
   pub rare_steal: Option<InventoryItem>,
@@ -46,8 +82,8 @@ impl BattleState {
     resistance: u16,
     agility: u8,
     experience: u32,
-    poisoned: i8,
-    sleeping: i8,
+    status_effects: Vec<StatusEffect>, // Last until cured
+    immunities: Vec<StatusEffect>, // Permanent
     back_row: bool,
     common_steal: Option<InventoryItem>,
     rare_steal: Option<InventoryItem>,
@@ -69,8 +105,10 @@ impl BattleState {
       atb: 0,
       atb_subtick: 0,
       turn_active: false,
-      poisoned,
-      sleeping,
+      status_effects: status_effects.into_iter().map(|effect| ActiveEffect {effect, turns: None}).collect(),
+      immunities: immunities.into_iter().map(|effect| (effect, None)).collect(),
+      affinities: Vec::new(),
+      equipment: EquipmentBonus::default(),
       back_row,
       common_steal,
       rare_steal,
@@ -79,21 +117,54 @@ impl BattleState {
     }
   }
 
-  pub fn update(&mut self, current_turn: &mut u8, active_turns: &mut Vec<u8>) -> GameResult<()> {
-    if self.hp == 0 || self.sleeping > 0 {
+  fn is_atb_filling(&self) -> bool {
+    self.hp > 0 && !self.status_effects.iter().any(|active| active.effect.definition().stops_atb)
+  }
+
+  // Fills the gauge by agility every few frames at the battle speed, returns true while it is full and the turn is due
+  pub fn update_atb(&mut self, battle_speed: u8) -> bool {
+    if !self.is_atb_filling() {
       self.atb = 0;
       self.atb_subtick = 0;
-    } else if *current_turn == 0 {
-      self.atb_subtick += 1;
-      if self.atb_subtick % 5 == 0 {
-        self.atb_subtick = 0;
-        if let Some(sum) = self.atb.checked_add(self.agility) {
-          self.atb = sum;
-        } else {
-          active_turns.push(self.id);
-        }
+      return false;
+    }
+    self.atb_subtick += 1;
+    if self.atb_subtick >= get_step_frames(battle_speed) {
+      self.atb_subtick = 0;
+      match self.atb.checked_add(self.get_agility()) {
+        Some(sum) => self.atb = sum,
+        None => return true
       }
     }
+    false
+  }
+
+  // Starts the gauge at the amount, u8::MAX makes the turn due on the next step
+  pub fn set_atb(&mut self, atb: u8) {
+    self.atb = atb;
+    self.atb_subtick = 0;
+  }
+
+  // Frames of running time until the gauge overflows, None if it isn't filling
+  pub fn frames_until_turn(&self, battle_speed: u8) -> Option<u32> {
+    self.frames_to_fill(self.atb, self.atb_subtick, battle_speed)
+  }
+
+  // Frames from an empty gauge to a turn
+  pub fn frames_per_turn(&self, battle_speed: u8) -> Option<u32> {
+    self.frames_to_fill(0, 0, battle_speed)
+  }
+
+  fn frames_to_fill(&self, atb: u8, atb_subtick: u8, battle_speed: u8) -> Option<u32> {
+    let agility = self.get_agility() as u32;
+    if !self.is_atb_filling() || agility == 0 {
+      return None;
+    }
+    let steps = (u8::MAX - atb) as u32 / agility + 1;
+    Some((steps * get_step_frames(battle_speed) as u32).saturating_sub(atb_subtick as u32))
+  }
+
+  pub fn update(&mut self) -> GameResult<()> {
     if let Some(print_damage) = &mut self.print_damage {
       if print_damage.show_time > 0. {
         print_damage.update()?;
@@ -104,145 +175,391 @@ impl BattleState {
     Ok(())
   }
 
-  pub fn end_turn(
-    &mut self,
-    ctx: &mut Context,
-    notification: &mut Option<Notification>,
-    name: &String,
-    position: (f32, f32)
-  ) -> GameResult<()> {
-    if self.poisoned > 0 && self.poisoned != 126 {
-      self.poisoned -= 1;
-      let poison_damage = max(self.hp / 20, 1);
-      *notification = Some(Notification::new(ctx, format!("{} took {} poison damage", name, poison_damage)));
-      if let Some(hp) = self.hp.checked_sub(poison_damage) {
-        self.hp = hp;
-      } else {
-        self.hp = 0;
-      }
-      self.print_damage = Some(PrintDamage::new(ctx, poison_damage, self.get_damage_position(position), Color::new(1., 1., 1., 1.)));
-      if let Some(info) = &mut self.character_info {
-        if self.poisoned == 0 {info.remove_effect("poison".to_owned())?;}
-        info.hp.text = format!("{}/", self.hp);
+  pub fn has_effect(&self, effect: StatusEffect) -> bool {
+    self.status_effects.iter().any(|active| active.effect == effect)
+  }
+
+  pub fn is_immune(&self, effect: StatusEffect) -> bool {
+    self.immunities.iter().any(|(immunity, _)| *immunity == effect) || self.equipment.immunities.contains(&effect)
+  }
+
+  // Returns false if immune or already affected, an effect that was already there lasts its full duration again
+  pub fn inflict(&mut self, effect: StatusEffect) -> bool {
+    if self.is_immune(effect) {
+      return false;
+    }
+    let definition = effect.definition();
+    let turns = match definition.duration {
+      0 => None,
+      duration => Some(duration)
+    };
+    if let Some(active) = self.status_effects.iter_mut().find(|active| active.effect == effect) {
+      if active.turns.is_some() {
+        active.turns = turns;
       }
-    } else if self.poisoned < 0 && self.poisoned != -125 {
-      self.poisoned += 1;
-      if let Some(info) = &mut self.character_info {
-        if self.poisoned == 0 {info.remove_effect("poison".to_owned())?;}
+      return false;
+    }
+    self.status_effects.retain(|active| !definition.cancels.contains(&active.effect));
+    self.status_effects.push(ActiveEffect {effect, turns});
+    true
+  }
+
+  // Removes the effect and starts its immunity, returns false if it wasn't there
+  pub fn cure(&mut self, effect: StatusEffect) -> bool {
+    if !self.has_effect(effect) {
+      return false;
+    }
+    self.status_effects.retain(|active| active.effect != effect);
+    let immunity = effect.definition().immunity;
+    if immunity > 0 && !self.is_immune(effect) {
+      self.immunities.push((effect, Some(immunity)));
+    }
+    true
+  }
+
+  // Effects that only last a battle wear off
+  pub fn end_battle(&mut self, ctx: &mut Context) -> GameResult<()> {
+    self.status_effects.retain(|active| active.effect.definition().lasts_after_battle);
+    self.immunities.retain(|(_, turns)| turns.is_none());
+    self.update_effect_icons(ctx)
+  }
+
+  fn get_multiplier(&self, stat: Stat) -> f32 {
+    self.status_effects.iter().map(|active| (active.effect.definition().modifier)(stat)).product()
+  }
+
+  // A stat after status effects, which never bring it from above 0 to 0
+  pub fn get_modified(&self, stat: Stat, value: u16) -> u16 {
+    let modified = (value as f32 * self.get_multiplier(stat)).min(u16::MAX as f32) as u16;
+    match value {
+      0 => 0,
+      _ => max(modified, 1)
+    }
+  }
+
+  pub fn get_base(&self, stat: Stat) -> u16 {
+    match stat {
+      Stat::Attack     => self.attack,
+      Stat::Defence    => self.defence,
+      Stat::Magic      => self.magic,
+      Stat::Resistance => self.resistance,
+      Stat::Agility    => self.agility as u16,
+      Stat::Accuracy   => 0
+    }
+  }
+
+  // A stat with the equipment, before status effects
+  pub fn get_equipped(&self, stat: Stat) -> u16 {
+    self.equipment.apply(stat, self.get_base(stat))
+  }
+
+  pub fn get_agility(&self) -> u8 {
+    min(self.get_modified(Stat::Agility, self.get_equipped(Stat::Agility)), u8::MAX as u16) as u8
+  }
+
+  // Damage multiplier against an element, see element::combine_affinities
+  pub fn get_affinity(&self, element: Element) -> f32 {
+    let multipliers: Vec<f32> = self.affinities.iter()
+      .chain(self.equipment.affinities.iter())
+      .chain(self.status_effects.iter().flat_map(|active| active.effect.definition().affinities.iter()))
+      .filter(|(affinity_element, _)| *affinity_element == element)
+      .map(|(_, multiplier)| *multiplier)
+      .collect();
+    element::combine_affinities(&multipliers)
+  }
+
+  pub fn can_cast(&self) -> bool {
+    !self.status_effects.iter().any(|active| active.effect.definition().blocks_magic)
+  }
+
+  // Set when a status effect decides the turn instead of the player or the enemy's own choice
+  pub fn get_forced_action(&self) -> Option<ForcedAction> {
+    self.status_effects.iter().find_map(|active| active.effect.definition().forced_action)
+  }
+
+  pub fn get_combat_stats(&self) -> CombatStats {
+    CombatStats {
+      attack: self.get_modified(Stat::Attack, self.get_equipped(Stat::Attack)),
+      defence: self.get_modified(Stat::Defence, self.get_equipped(Stat::Defence)),
+      magic: self.get_modified(Stat::Magic, self.get_equipped(Stat::Magic)),
+      resistance: self.get_modified(Stat::Resistance, self.get_equipped(Stat::Resistance)),
+      agility: self.get_agility(),
+      accuracy: self.get_multiplier(Stat::Accuracy)
+    }
+  }
+
+  // The action as this combatant performs it, so the target's damage roll knows who attacked
+  pub fn prepare_action(&self, formula: &Rc<dyn DamageFormula>, action_parameters: &ActionParameters) -> ActionParameters {
+    ActionParameters {
+      attacker: Some(Attacker {stats: self.get_combat_stats(), formula: formula.clone(), back_row: self.back_row}),
+      ..action_parameters.clone()
+    }
+  }
+
+  // Ticks and counts down status effects at the end of this combatant's turn, without any presentation
+  pub fn apply_end_turn(&mut self) -> EndTurnOutcome {
+    let mut outcome = EndTurnOutcome {hp_changes: Vec::new(), ended: Vec::new()};
+    let effects: Vec<StatusEffect> = self.status_effects.iter().map(|active| active.effect).collect();
+    for effect in effects {
+      let hp_change = match self.hp {
+        0 => 0,
+        _ => (effect.definition().tick)(self)
+      };
+      if hp_change != 0 {
+        self.hp = min(max(self.hp as i32 + hp_change, 0), self.max_hp as i32) as u16;
+        outcome.hp_changes.push((effect, hp_change));
       }
     }
-    if self.sleeping > 0 && self.sleeping != 126 {
-      self.sleeping -= 1;
-      if let Some(info) = &mut self.character_info {
-        if self.sleeping == 0 {info.remove_effect("poison".to_owned())?;}
+    for active in self.status_effects.iter_mut() {
+      if let Some(turns) = &mut active.turns {
+        *turns = turns.saturating_sub(1);
+        if *turns == 0 {
+          outcome.ended.push(active.effect);
+        }
       }
-    } else if self.sleeping < 0 && self.sleeping != -125 {
-      self.sleeping += 1;
-      if let Some(info) = &mut self.character_info {
-        if self.sleeping == 0 {info.remove_effect("poison".to_owned())?;}
+    }
+    for (_, turns) in self.immunities.iter_mut() {
+      if let Some(turns) = turns {
+        *turns = turns.saturating_sub(1);
       }
     }
+    self.immunities.retain(|(_, turns)| *turns != Some(0));
+    for effect in outcome.ended.iter() {
+      self.cure(*effect);
+    }
     self.atb = 0;
-    Ok(())
+    outcome
   }
 
-  pub fn receive_damage(
+  pub fn end_turn(
     &mut self,
     ctx: &mut Context,
     notification: &mut Option<Notification>,
     name: &String,
-    action_parameters: &ActionParameters,
     position: (f32, f32)
   ) -> GameResult<()> {
-    let damage = match action_parameters.damage_type {
-      DamageType::Physical => action_parameters.power * 3 / self.defence,
-      DamageType::Magical  => action_parameters.power * 3 / self.resistance,
-      DamageType::Pure     => action_parameters.power * 3,
-      _ => 0
-    };
-    if let Some(hp) = self.hp.checked_sub(damage) {
-      self.hp = hp;
-    } else {
-      self.hp = 0;
+    let outcome = self.apply_end_turn();
+    for (effect, hp_change) in outcome.hp_changes.iter() {
+      let (text, color) = match *hp_change < 0 {
+        true  => (format!("{} took {} {} damage", name, -hp_change, effect.get_name()), Color::new(1., 1., 1., 1.)),
+        false => (format!("{} regained {} HP", name, hp_change), Color::new(0., 1., 0., 1.))
+      };
+      *notification = Some(Notification::new(ctx, text));
+      self.print_damage = Some(PrintDamage::new(ctx, hp_change.unsigned_abs() as u16, self.get_damage_position(position), color));
     }
+    self.update_effect_icons(ctx)?;
     if let Some(info) = &mut self.character_info {
       info.hp.text = format!("{}/", self.hp);
     }
-    let mut rng = thread_rng();
-    if rng.gen::<f32>() < action_parameters.dead_change   {
-      self.hp = 0;
-      if let Some(info) = &mut self.character_info {
-        info.hp.text = format!("{}", 0);
-      }
+    Ok(())
+  }
+
+  pub fn full_heal(&mut self) -> GameResult<()> {
+    self.hp = self.max_hp;
+    self.mp = self.max_mp;
+    self.status_effects.clear();
+    self.immunities.retain(|(_, turns)| turns.is_none());
+    if let Some(info) = &mut self.character_info {
+      info.status_effects.clear();
+      info.hp.text = format!("{}/", self.hp);
+      info.mp.text = format!("{}/", self.mp);
     }
-    let mut new_effects = Vec::<String>::new();
-    if self.poisoned >= 0 && rng.gen::<f32>() < action_parameters.poison_change {
-      if let Some(info) = &mut self.character_info {
-        if self.poisoned == 0 {
-          info.set_effect(ctx, self.id, "poison".to_owned())?;
+    Ok(())
+  }
+
+  // Makes the status icons match the active effects
+  fn update_effect_icons(&mut self, ctx: &mut Context) -> GameResult<()> {
+    if let Some(info) = &mut self.character_info {
+      let names: Vec<&str> = self.status_effects.iter().map(|active| active.effect.get_name()).collect();
+      let worn_off: Vec<String> = info.status_effects.iter()
+        .map(|(name, _)| name.to_owned())
+        .filter(|name| !names.contains(&name.as_str()))
+        .collect();
+      for name in worn_off {
+        info.remove_effect(name)?;
+      }
+      for name in names {
+        if !info.status_effects.iter().any(|(icon_name, _)| icon_name == name) {
+          info.set_effect(ctx, self.id, name.to_owned())?;
         }
       }
-      self.poisoned = 5;
-      new_effects.push("poisoned".to_owned());
     }
-    if self.sleeping >= 0 && rng.gen::<f32>() < action_parameters.sleep_change {
-      if let Some(info) = &mut self.character_info {
-        if self.sleeping == 0 {
-          info.set_effect(ctx, self.id, "sleep".to_owned())?;
-        }
+    Ok(())
+  }
+
+  // Returns false without spending anything if there isn't enough MP
+  pub fn spend_mp(&mut self, mp_cost: u16) -> bool {
+    if self.mp < mp_cost {
+      return false;
+    }
+    self.mp -= mp_cost;
+    if let Some(info) = &mut self.character_info {info.mp.text = format!("{}/", self.mp);}
+    true
+  }
+
+  // Rolls the status effects of an action, returns the ones that took hold
+  pub fn apply_status_effects(&mut self, rng: &mut GameRng, action_parameters: &ActionParameters) -> Vec<StatusEffect> {
+    let mut inflicted = Vec::new();
+    for (effect, chance) in action_parameters.status_effects.iter() {
+      if !self.is_immune(*effect) && rng.gen::<f32>() < *chance && self.inflict(*effect) {
+        inflicted.push(*effect);
       }
-      self.sleeping = 3;
-      new_effects.push("fell asleep".to_owned());
     }
-    if new_effects.len() > 0 {
-      let mut notification_text = format!("{} is", name);
-      if new_effects.len() == 1 {
-        notification_text = format!("{} {}", notification_text, new_effects[0]);
-      } else {
-        for (index, element) in new_effects.iter().enumerate() {
-          if index == 0 {
-            notification_text = format!("{} {}", notification_text, element);
-          } else if index < new_effects.len() - 2 {
-            notification_text = format!("{}, {}", notification_text, element);
-          } else {
-            notification_text = format!("{} and {}", notification_text, element);
-          }
+    inflicted
+  }
+
+  // Damage, instant death and status effects of an action, without any presentation.
+  // Immune or absorbing targets take none of the action's other effects.
+  pub fn apply_damage(&mut self, rng: &mut GameRng, action_parameters: &ActionParameters) -> DamageOutcome {
+    let defender = self.get_combat_stats();
+    let roll = match &action_parameters.attacker {
+      Some(attacker) => attacker.formula.roll(rng, &attacker.stats, &defender, action_parameters),
+      None => ClassicFormula.roll(rng, &CombatStats::default(), &defender, action_parameters)
+    };
+    let (damage, critical) = match roll {
+      DamageRoll::Miss => return DamageOutcome {damage: 0, missed: true, critical: false, affinity: 1., killed: false, inflicted: Vec::new()},
+      DamageRoll::Hit(damage) => (damage, false),
+      DamageRoll::Critical(damage) => (damage, true)
+    };
+    let affinity = match action_parameters.element {
+      Some(element) => self.get_affinity(element),
+      None => 1.
+    };
+    let damage = (damage as f32 * affinity.abs() * self.get_row_multiplier(action_parameters)).min(u16::MAX as f32) as u16;
+    let mut outcome = DamageOutcome {damage, missed: false, critical, affinity, killed: false, inflicted: Vec::new()};
+    if affinity < 0. {
+      self.hp = min(self.hp.saturating_add(damage), self.max_hp);
+      return outcome;
+    }
+    self.hp = self.hp.saturating_sub(damage);
+    if affinity == 0. {
+      return outcome;
+    }
+    if damage > 0 {
+      let ended: Vec<StatusEffect> = self.status_effects.iter()
+        .map(|active| active.effect)
+        .filter(|effect| effect.definition().ends_on_damage)
+        .collect();
+      for effect in ended {
+        self.cure(effect);
+      }
+    }
+    if rng.gen::<f32>() < action_parameters.dead_change {
+      self.hp = 0;
+      outcome.killed = true;
+    }
+    outcome.inflicted = self.apply_status_effects(rng, action_parameters);
+    outcome
+  }
+
+  // Physical damage is cut for an attacker in the back row and again for a target in it, unless the action is long range
+  fn get_row_multiplier(&self, action_parameters: &ActionParameters) -> f32 {
+    if !matches!(action_parameters.damage_type, DamageType::Physical) || action_parameters.long_range {
+      return 1.;
+    }
+    let mut multiplier = 1.;
+    if matches!(&action_parameters.attacker, Some(attacker) if attacker.back_row) {
+      multiplier *= BACK_ROW_MULTIPLIER;
+    }
+    if self.back_row {
+      multiplier *= BACK_ROW_MULTIPLIER;
+    }
+    multiplier
+  }
+
+  // Shows what an action did to this combatant, see turn::apply_action
+  pub fn show_outcome(
+    &mut self,
+    ctx: &mut Context,
+    notification: &mut Option<Notification>,
+    name: &String,
+    action_parameters: &ActionParameters,
+    outcome: &ActionOutcome,
+    position: (f32, f32)
+  ) -> GameResult<()> {
+    match outcome {
+      ActionOutcome::Damage(damage) => self.show_damage(ctx, notification, name, action_parameters, damage, position),
+      ActionOutcome::Healing(heal_amount, granted) => self.show_healing(ctx, notification, name, *heal_amount, granted, position),
+      ActionOutcome::Item(item, restored) => {
+        *notification = Some(Notification::new(ctx, item.get_name()));
+        let position = self.get_damage_position(position);
+        item.show_item_effect(ctx, self, *restored, position)
+      },
+      ActionOutcome::Special(message) => {
+        if let Some(message) = message {
+          *notification = Some(Notification::new(ctx, message.to_owned()));
         }
+        Ok(())
       }
-      *notification = Some(Notification::new(ctx, notification_text));
     }
-    self.print_damage = Some(PrintDamage::new(ctx, damage, self.get_damage_position(position), Color::new(1., 1., 1., 1.)));
-    Ok(())
   }
 
-  pub fn receive_healing(
+  fn show_damage(
     &mut self,
     ctx: &mut Context,
+    notification: &mut Option<Notification>,
+    name: &String,
     action_parameters: &ActionParameters,
+    outcome: &DamageOutcome,
     position: (f32, f32)
   ) -> GameResult<()> {
-    let heal_amount = action_parameters.power * self.magic;
-    self.hp = min(self.hp + heal_amount, self.max_hp);
-    if let Some(info) = &mut self.character_info {info.hp.text = format!("{}/", self.hp);}
-    self.print_damage = Some(PrintDamage::new(ctx, heal_amount, self.get_damage_position(position), Color::new(0., 1., 0., 1.)));
+    self.update_effect_icons(ctx)?;
+    if let Some(info) = &mut self.character_info {
+      info.hp.text = format!("{}/", self.hp);
+      if outcome.killed {
+        info.hp.text = format!("{}", 0);
+      }
+    }
+    if outcome.critical {
+      *notification = Some(Notification::new(ctx, "Critical hit".to_owned()));
+    }
+    let mut print_color = Color::new(1., 1., 1., 1.);
+    if let Some(element) = action_parameters.element {
+      if outcome.affinity < 0. {
+        *notification = Some(Notification::new(ctx, format!("{} absorbs {}", name, element.get_name())));
+        print_color = Color::new(0., 1., 0., 1.);
+      } else if outcome.affinity == 0. {
+        *notification = Some(Notification::new(ctx, format!("{} is immune to {}", name, element.get_name())));
+      } else if outcome.affinity > 1. {
+        *notification = Some(Notification::new(ctx, format!("{} is weak to {}", name, element.get_name())));
+      }
+    }
+    notify_effects(ctx, notification, name, &outcome.inflicted);
+    let damage_position = self.get_damage_position(position);
+    self.print_damage = Some(match (outcome.missed, outcome.critical) {
+      (true, _) => PrintDamage::miss(ctx, damage_position),
+      (_, true) => PrintDamage::critical(ctx, outcome.damage, damage_position),
+      _ => PrintDamage::new(ctx, outcome.damage, damage_position, print_color)
+    });
     Ok(())
   }
 
-  pub fn receive_none_type_action(
+  // Heals and removes the status effects the action cures
+  pub fn apply_healing(&mut self, action_parameters: &ActionParameters) -> u16 {
+    let heal_amount = action_parameters.power.saturating_mul(self.magic);
+    self.hp = min(self.hp.saturating_add(heal_amount), self.max_hp);
+    for effect in action_parameters.cures.iter() {
+      self.cure(*effect);
+    }
+    heal_amount
+  }
+
+  fn show_healing(
     &mut self,
     ctx: &mut Context,
-    inventory: &mut Vec<InventoryElement>,
-    action_parameters: &ActionParameters,
-    action: for<'r, 's, 't1, 't2, 't3> fn(
-      &'r mut Context,
-      &'s mut Vec<InventoryElement>,
-      &'t1 ActionParameters,
-      &'t2 mut BattleState,
-      &'t3 mut Option<Notification>
-    ) -> GameResult<()>,
-    notification: &mut Option<Notification>
+    notification: &mut Option<Notification>,
+    name: &String,
+    heal_amount: u16,
+    granted: &[StatusEffect],
+    position: (f32, f32)
   ) -> GameResult<()> {
-    action(ctx, inventory, action_parameters, self, notification)
+    self.update_effect_icons(ctx)?;
+    if let Some(info) = &mut self.character_info {info.hp.text = format!("{}/", self.hp);}
+    notify_effects(ctx, notification, name, granted);
+    if heal_amount > 0 || granted.is_empty() {
+      self.print_damage = Some(PrintDamage::new(ctx, heal_amount, self.get_damage_position(position), Color::new(0., 1., 0., 1.)));
+    }
+    Ok(())
   }
 
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
@@ -280,4 +597,21 @@ impl BattleState {
     }
     damage_position
   }
+}
+
+// Names the status effects that took hold, "Darrel is poisoned and asleep"
+fn notify_effects(ctx: &mut Context, notification: &mut Option<Notification>, name: &String, effects: &[StatusEffect]) {
+  if effects.is_empty() {
+    return;
+  }
+  let mut notification_text = format!("{} is", name);
+  for (index, effect) in effects.iter().enumerate() {
+    let separator = match index {
+      0 => "",
+      _ if index < effects.len() - 1 => ",",
+      _ => " and"
+    };
+    notification_text = format!("{}{} {}", notification_text, separator, effect.definition().inflicted);
+  }
+  *notification = Some(Notification::new(ctx, notification_text));
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use crate::battle::element::{Element, Affinity};
+use crate::battle::state::BattleState;
+use std::cmp::max;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatusEffect {
+  Poison,
+  Sleep,
+  Haste,
+  Slow,
+  Silence,
+  Blind,
+  Regen,
+  Protect,
+  Shell,
+  Berserk,
+  Confuse,
+  Barfire,
+  Barice,
+  Barthunder
+}
+
+pub const STATUS_EFFECTS: [StatusEffect; 14] = [
+  StatusEffect::Poison, StatusEffect::Sleep, StatusEffect::Haste, StatusEffect::Slow, StatusEffect::Silence, StatusEffect::Blind,
+  StatusEffect::Regen, StatusEffect::Protect, StatusEffect::Shell, StatusEffect::Berserk, StatusEffect::Confuse,
+  StatusEffect::Barfire, StatusEffect::Barice, StatusEffect::Barthunder
+];
+
+// Stats an effect can scale, accuracy is the chance of physical attacks to hit
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stat {
+  Attack,
+  Defence,
+  Magic,
+  Resistance,
+  Agility,
+  Accuracy
+}
+
+// Turns taken away from the player while the effect lasts
+#[derive(Clone, Copy, PartialEq)]
+pub enum ForcedAction {
+  AttackEnemy, // Attacks a random enemy
+  AttackAnyone // Attacks a random combatant of either side
+}
+
+pub struct StatusEffectDefinition {
+  pub name: &'static str, // Icon is /status_effects/<name>.png
+  pub inflicted: &'static str, // Notification text after the name, "Darrel is poisoned"
+  pub duration: u8, // Turns of the affected combatant, 0 lasts until cured
+  pub immunity: u8, // Turns the combatant can't get the effect again after it wears off
+  pub lasts_after_battle: bool,
+  pub stops_atb: bool,
+  pub ends_on_damage: bool,
+  pub blocks_magic: bool,
+  pub forced_action: Option<ForcedAction>,
+  pub cancels: &'static [StatusEffect], // Removed when this effect is inflicted
+  pub affinities: &'static [Affinity],
+  pub tick: fn(&BattleState) -> i32, // HP change at the end of each turn
+  pub modifier: fn(Stat) -> f32 // Multiplier of each stat
+}
+
+fn no_tick(_state: &BattleState) -> i32 {
+  0
+}
+
+fn poison_tick(state: &BattleState) -> i32 {
+  -(max(state.hp / 20, 1) as i32)
+}
+
+fn regen_tick(state: &BattleState) -> i32 {
+  max(state.max_hp / 20, 1) as i32
+}
+
+fn no_modifier(_stat: Stat) -> f32 {
+  1.
+}
+
+fn haste_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Agility => 2., _ => 1.}
+}
+
+fn slow_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Agility => 0.5, _ => 1.}
+}
+
+fn blind_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Accuracy => 0.5, _ => 1.}
+}
+
+fn protect_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Defence => 1.5, _ => 1.}
+}
+
+fn shell_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Resistance => 1.5, _ => 1.}
+}
+
+fn berserk_modifier(stat: Stat) -> f32 {
+  match stat {Stat::Attack => 1.5, _ => 1.}
+}
+
+impl StatusEffect {
+  pub fn from_name(name: &str) -> Option<StatusEffect> {
+    STATUS_EFFECTS.iter().find(|effect| effect.get_name() == name).copied()
+  }
+
+  pub fn get_name(self) -> &'static str {
+    self.definition().name
+  }
+
+  pub fn definition(self) -> StatusEffectDefinition {
+    let effect = StatusEffectDefinition {
+      name: "",
+      inflicted: "",
+      duration: 0,
+      immunity: 0,
+      lasts_after_battle: false,
+      stops_atb: false,
+      ends_on_damage: false,
+      blocks_magic: false,
+      forced_action: None,
+      cancels: &[],
+      affinities: &[],
+      tick: no_tick,
+      modifier: no_modifier
+    };
+    match self {
+      StatusEffect::Poison  => StatusEffectDefinition {name: "poison", inflicted: "poisoned", duration: 5, lasts_after_battle: true, tick: poison_tick, ..effect},
+      StatusEffect::Sleep   => StatusEffectDefinition {name: "sleep", inflicted: "asleep", duration: 3, immunity: 2, stops_atb: true, ends_on_damage: true, ..effect},
+      StatusEffect::Haste   => StatusEffectDefinition {name: "haste", inflicted: "hasted", duration: 8, cancels: &[StatusEffect::Slow], modifier: haste_modifier, ..effect},
+      StatusEffect::Slow    => StatusEffectDefinition {name: "slow", inflicted: "slowed", duration: 8, cancels: &[StatusEffect::Haste], modifier: slow_modifier, ..effect},
+      StatusEffect::Silence => StatusEffectDefinition {name: "silence", inflicted: "silenced", duration: 5, blocks_magic: true, ..effect},
+      StatusEffect::Blind   => StatusEffectDefinition {name: "blind", inflicted: "blinded", duration: 5, modifier: blind_modifier, ..effect},
+      StatusEffect::Regen   => StatusEffectDefinition {name: "regen", inflicted: "regenerating", duration: 6, tick: regen_tick, ..effect},
+      StatusEffect::Protect => StatusEffectDefinition {name: "protect", inflicted: "protected", duration: 10, modifier: protect_modifier, ..effect},
+      StatusEffect::Shell   => StatusEffectDefinition {name: "shell", inflicted: "shelled", duration: 10, modifier: shell_modifier, ..effect},
+      StatusEffect::Berserk => StatusEffectDefinition {
+        name: "berserk", inflicted: "berserk", duration: 6, forced_action: Some(ForcedAction::AttackEnemy), modifier: berserk_modifier, ..effect
+      },
+      StatusEffect::Confuse => StatusEffectDefinition {name: "confuse", inflicted: "confused", duration: 3, forced_action: Some(ForcedAction::AttackAnyone), ..effect},
+      StatusEffect::Barfire    => StatusEffectDefinition {name: "barfire", inflicted: "shielded from fire", duration: 10, affinities: &[(Element::Fire, 0.5)], ..effect},
+      StatusEffect::Barice     => StatusEffectDefinition {name: "barice", inflicted: "shielded from ice", duration: 10, affinities: &[(Element::Ice, 0.5)], ..effect},
+      StatusEffect::Barthunder => StatusEffectDefinition {
+        name: "barthunder", inflicted: "shielded from thunder", duration: 10, affinities: &[(Element::Thunder, 0.5)], ..effect
+      }
+    }
+  }
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+// Turn resolution shared by the battle screen and the simulator. Positions are (column, row) with the party as column 0,
+// the callers look up who stands there and add the animations and notifications around the result.
+use std::rc::Rc;
+use rand::Rng;
+use crate::battle::action::{ActionParameters, DamageType, TargetScope};
+use crate::battle::behaviour::Behaviour;
+use crate::battle::damage::DamageFormula;
+use crate::battle::enemy::SELF_TARGET;
+use crate::battle::state::{BattleState, DamageOutcome};
+use crate::battle::status_effect::{StatusEffect, ForcedAction};
+use crate::party::InventoryElement;
+use crate::party::item::InventoryItem;
+use crate::random::GameRng;
+
+// What an action did to one target
+pub enum ActionOutcome {
+  Damage(DamageOutcome),
+  Healing(u16, Vec<StatusEffect>), // HP healed and the effects granted
+  Item(InventoryItem, u16), // HP or MP restored
+  Special(Option<String>) // What an ability without damage, like Steal, has to say
+}
+
+pub enum FleeOutcome {
+  Blocked, // A boss is in the battle
+  Escaped,
+  Failed
+}
+
+// Everyone hit by an action aimed at the target, among the positions of everyone still standing.
+// A random scope can hit the same target more than once.
+pub fn get_targets(rng: &mut GameRng, alive: &[(usize, usize)], target: (usize, usize), action_parameters: &ActionParameters) -> Vec<(usize, usize)> {
+  let scope = match action_parameters.scope.is_toggleable() && !action_parameters.group {
+    true  => TargetScope::Single,
+    false => action_parameters.scope
+  };
+  expand_scope(rng, alive, target, scope)
+}
+
+// Party members hit by an enemy's action, always the whole scope. One aimed at the enemy itself only hits it.
+pub fn get_enemy_targets(rng: &mut GameRng, party_alive: &[(usize, usize)], target: u8, action_parameters: &ActionParameters) -> Vec<u8> {
+  match target {
+    SELF_TARGET => vec![SELF_TARGET],
+    _ => expand_scope(rng, party_alive, (0, target as usize), action_parameters.scope).into_iter().map(|position| position.1 as u8).collect()
+  }
+}
+
+fn expand_scope(rng: &mut GameRng, alive: &[(usize, usize)], target: (usize, usize), scope: TargetScope) -> Vec<(usize, usize)> {
+  match scope {
+    TargetScope::Single => vec![target],
+    TargetScope::Random(hits) => {
+      let side: Vec<(usize, usize)> = alive.iter().copied().filter(|position| (position.0 == 0) == (target.0 == 0)).collect();
+      match side.is_empty() {
+        true  => Vec::new(),
+        false => (0..hits).map(|_| side[rng.gen_range(0, side.len())]).collect()
+      }
+    },
+    _ => alive.iter().copied().filter(|position| scope.includes(target, *position)).collect()
+  }
+}
+
+// Random target of a berserk or confused party member, a confused one may hit the party as well
+pub fn get_forced_target(rng: &mut GameRng, alive: &[(usize, usize)], forced_action: ForcedAction) -> Option<(usize, usize)> {
+  let targets: Vec<(usize, usize)> = alive.iter().copied()
+    .filter(|position| position.0 > 0 || forced_action == ForcedAction::AttackAnyone)
+    .collect();
+  match targets.is_empty() {
+    true  => None,
+    false => Some(targets[rng.gen_range(0, targets.len())])
+  }
+}
+
+// The action as the attacker performs it on this many targets, split power is divided between them
+pub fn prepare_action(attacker: &BattleState, formula: &Rc<dyn DamageFormula>, action_parameters: &ActionParameters, target_count: usize) -> ActionParameters {
+  let mut prepared = attacker.prepare_action(formula, action_parameters);
+  if prepared.split && target_count > 1 {
+    prepared.power = prepared.power.div_ceil(target_count as u16);
+  }
+  prepared
+}
+
+// The enemy's target, rule name and action for its turn with the MP already paid, see Behaviour::choose
+pub fn choose_enemy_action(
+  rng: &mut GameRng,
+  behaviour: &mut Behaviour,
+  state: &mut BattleState,
+  party: &[(u8, &BattleState)]
+) -> Option<(u8, String, ActionParameters)> {
+  let action = behaviour.choose(rng, state, party)?;
+  state.spend_mp(action.2.mp_cost);
+  Some(action)
+}
+
+// Carries out a prepared action on one target. Items come out of the inventory, Steal puts them in.
+pub fn apply_action(
+  rng: &mut GameRng,
+  inventory: &mut [InventoryElement],
+  target: &mut BattleState,
+  action_parameters: &ActionParameters
+) -> ActionOutcome {
+  match &action_parameters.damage_type {
+    DamageType::None(action) => ActionOutcome::Special(action(inventory, rng, action_parameters, target)),
+    DamageType::Item(used_item) => {
+      for inventory_element in inventory.iter_mut() {
+        if let InventoryElement::Item(item, amount) = inventory_element {
+          if used_item.get_name() == item.get_name() && *amount > 0 {
+            *amount -= 1;
+          }
+        }
+      }
+      ActionOutcome::Item(used_item.clone(), used_item.apply_to_state(target))
+    },
+    DamageType::Healing => {
+      let heal_amount = target.apply_healing(action_parameters);
+      ActionOutcome::Healing(heal_amount, target.apply_status_effects(rng, action_parameters))
+    },
+    _ => ActionOutcome::Damage(target.apply_damage(rng, action_parameters))
+  }
+}
+
+// Bosses never let the party go, otherwise it needs an escapeable enemy and gets away four times in five
+pub fn roll_flee(rng: &mut GameRng, boss: bool, escapeable: bool) -> FleeOutcome {
+  if boss {
+    return FleeOutcome::Blocked;
+  }
+  match escapeable && rng.gen::<f32>() < 0.8 {
+    true  => FleeOutcome::Escaped,
+    false => FleeOutcome::Failed
+  }
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+use crate::battle::state::BattleState;
+
+// Turns waiting to start, served in the order the gauges filled
+#[derive(Default)]
+pub struct TurnQueue {
+  queued: VecDeque<u8>
+}
+
+impl TurnQueue {
+  // Gauges that filled on the same frame queue the most agile first, then the lowest id
+  pub fn push_ready(&mut self, mut ready: Vec<(u8, u8)>) {
+    ready.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (id, _) in ready {
+      if !self.contains(id) {
+        self.queued.push_back(id);
+      }
+    }
+  }
+
+  pub fn pop(&mut self) -> Option<u8> {
+    self.queued.pop_front()
+  }
+
+  pub fn contains(&self, id: u8) -> bool {
+    self.queued.contains(&id)
+  }
+
+  // Ids of the next turns after the current one: the queue, then the gauges played forward at the battle speed.
+  // Turns take no time in the forecast, anyone who is queued or acting starts again from an empty gauge.
+  pub fn predict(&self, states: &[&BattleState], current_turn: u8, battle_speed: u8, count: usize) -> Vec<u8> {
+    let mut turns: Vec<u8> = self.queued.iter().copied().take(count).collect();
+    let mut upcoming = Vec::new(); // (frames from now, agility, id)
+    for state in states.iter() {
+      let period = match state.frames_per_turn(battle_speed) {
+        Some(period) => period,
+        None => continue
+      };
+      let first = match self.contains(state.id) || state.id == current_turn {
+        true  => Some(period),
+        false => state.frames_until_turn(battle_speed)
+      };
+      if let Some(first) = first {
+        upcoming.extend((0..count as u32).map(|turn| (first + turn * period, state.get_agility(), state.id)));
+      }
+    }
+    upcoming.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    let remaining = count - turns.len();
+    turns.extend(upcoming.into_iter().map(|(_, _, id)| id).take(remaining));
+    turns
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::TurnQueue;
+  use crate::battle::state::BattleState;
+
+  fn combatant(id: u8, agility: u8) -> BattleState {
+    BattleState::new(id, 1, 10, 0, 1, 1, 1, 1, agility, 0, Vec::new(), Vec::new(), false, None, None, None)
+  }
+
+  fn drain(queue: &mut TurnQueue) -> Vec<u8> {
+    std::iter::from_fn(|| queue.pop()).collect()
+  }
+
+  #[test]
+  fn gauges_filling_together_queue_by_agility_then_id() {
+    let mut queue = TurnQueue::default();
+    queue.push_ready(vec![(3, 1)]);
+    queue.push_ready(vec![(6, 5), (5, 9), (1, 5), (2, 9)]);
+    assert_eq!(drain(&mut queue), vec![3, 2, 5, 1, 6]);
+  }
+
+  #[test]
+  fn queued_combatants_keep_their_place() {
+    let mut queue = TurnQueue::default();
+    queue.push_ready(vec![(1, 5)]);
+    queue.push_ready(vec![(2, 3), (1, 5)]);
+    assert_eq!(drain(&mut queue), vec![1, 2]);
+  }
+
+  #[test]
+  fn prediction_restarts_the_acting_combatant_after_the_queue() {
+    let (acting, waiting, queued) = (combatant(1, 10), combatant(2, 5), combatant(3, 5));
+    let mut queue = TurnQueue::default();
+    queue.push_ready(vec![(3, 5)]);
+    // The acting and queued gauges restart from empty. With twice the agility the acting one comes round twice
+    // before the others, then agility and id break the tie at the second of them.
+    assert_eq!(queue.predict(&[&acting, &waiting, &queued], 1, 3, 6), vec![3, 1, 1, 2, 3, 1]);
+  }
+}
@@ -1,14 +1,26 @@
-use ggez::{Context, GameResult};
+use ggez::{Context, GameResult, GameError};
 use ggez::timer::ticks;
-use rand::{Rng, thread_rng};
+use rand::Rng;
 use crate::battle::Battle;
-use crate::battle::action::{ActionParameters, DamageType};
+use crate::battle::action::{ActionParameters, DamageType, TargetScope};
+use crate::battle::element::Element;
 use crate::battle::state::BattleState;
-use crate::party::{Party, InventoryElement};
+use crate::battle::status_effect::StatusEffect;
+use crate::battle::turn::{self, FleeOutcome};
+use crate::party::{Party, InventoryElement, ITEM_STACK_LIMIT};
 use crate::party::character::{Character, Animation, Sprite};
 use crate::menu::item::OnClickEvent;
 use crate::menu::notification::Notification;
-use crate::data::menus;
+use crate::data::{equipment, menus};
+use crate::data::content::{self, AbilityDefinition, CharacterDefinition, GameContent, SpellDefinition};
+use crate::random::GameRng;
+
+pub fn get_character(ctx: &mut Context, content: &GameContent, id: u8, name: &str) -> GameResult<Character> {
+  match content.characters.iter().find(|definition| definition.name == name) {
+    Some(definition) => Ok(build_character(ctx, content, id, definition)),
+    None => Err(GameError::ResourceLoadError(format!("{}: no character called {}", content::CHARACTERS_FILE, name)))
+  }
+}
 
 pub fn none_character(ctx: &mut Context, id: u8) -> Character {
   let attack            = (" ".to_owned(), OnClickEvent::None);
@@ -17,134 +29,146 @@ pub fn none_character(ctx: &mut Context, id: u8) -> Character {
   Character::new(ctx, id, "/empty.png".to_owned(), "/empty.png".to_owned(), "".to_owned(), 0, 0, 0, 0, 0, 0, 0, 0, false, attack, primary_ability, secondary_ability)
 }
 
-pub fn darrel_deen(ctx: &mut Context, id: u8) -> Character {
-  let attack = ("Attack".to_owned(), OnClickEvent::ToTargetSelection(
-    menus::to_target_selection, ActionParameters::new(DamageType::Physical, 4, 0., false, 1., false, 1., false), (0, 0)
-  ));
-  fn steal_action(
-    ctx: &mut Context,
-    inventory: &mut Vec<InventoryElement>,
-    _action_parameters: &ActionParameters,
-    target_state: &mut BattleState,
-    notification: &mut Option<Notification>
-  ) -> GameResult<()> {
-    if target_state.common_steal.is_none() && target_state.rare_steal.is_none() {
-      *notification = Some(Notification::new(ctx, "Nothing to steal".to_owned()));
-    } else {
-      let rng = thread_rng().gen::<f32>();
-      if rng < 0.5 {
-        if let Some(common_steal) = &target_state.common_steal {
-          if let Some(inventory_stack) = inventory.into_iter().find(|inventory_element| match inventory_element {
-              InventoryElement::Item(item, _) => item.get_name() == common_steal.get_name()
-            }
-          ) {
-            match inventory_stack {
-              InventoryElement::Item(_, amount) => if *amount < 99 {*amount += 1}
-            }
-            *notification = Some(Notification::new(ctx, format!("Stole {}", common_steal.get_name())));
-          }
-        }
-      } else if rng < 0.6 {
-        if let Some(rare_steal) = &target_state.rare_steal {
-          if let Some(inventory_stack) = inventory.into_iter().find(|inventory_element| match inventory_element {
-              InventoryElement::Item(item, _) => item.get_name() == rare_steal.get_name()
-            }
-          ) {
-            match inventory_stack {
-              InventoryElement::Item(_, amount) => *amount += 1
-            }
-            *notification = Some(Notification::new(ctx, format!("Stole {}", rare_steal.get_name())));
-          }
-        }
-      } else {
-        *notification = Some(Notification::new(ctx, "Could not steal".to_owned()));
-      }
+pub fn build_character(ctx: &mut Context, content: &GameContent, id: u8, definition: &CharacterDefinition) -> Character {
+  let mut character = Character::new(
+    ctx,
+    id,
+    definition.spritefile.to_owned(),
+    definition.avatar_spritefile.to_owned(),
+    definition.name.to_owned(),
+    definition.level,
+    definition.hp,
+    definition.mp,
+    definition.attack,
+    definition.defence,
+    definition.magic,
+    definition.resistance,
+    definition.agility,
+    definition.back_row,
+    build_ability(&definition.attack_ability, (0, 0)),
+    build_ability(&definition.primary_ability, (0, 1)),
+    build_ability(&definition.secondary_ability, (0, 2))
+  );
+  character.growth = definition.growth.clone();
+  character.spells = definition.spells.clone();
+  for equipment_name in definition.equipment.iter() {
+    if let Some(equipment) = equipment::get_equipment(content, equipment_name) {
+      character.equip(equipment.slot, Some(equipment));
     }
-    Ok(())
   }
-  fn flee_action(ctx: &mut Context, party: &mut Party, battle: &mut Battle) -> GameResult<()> {
-    let mut escapeable = false;
-    for enemy_column in &battle.enemies {
-      for enemy in enemy_column {
-        if enemy.escapeable {
-          escapeable = true;
+  character.state.experience = definition.growth.experience_for_level(definition.level).unwrap_or(0); // Starts at the beginning of its level
+  character
+}
+
+pub enum AbilityKind {
+  Target(DamageType),
+  Battle(for<'r, 's, 't> fn(&'r mut Context, &'s mut Party, &'t mut Battle) -> GameResult<()>),
+  None
+}
+
+pub fn get_ability_kind(kind: &str) -> Option<AbilityKind> {
+  match kind {
+    "Physical" => Some(AbilityKind::Target(DamageType::Physical)),
+    "Magical"  => Some(AbilityKind::Target(DamageType::Magical)),
+    "Pure"     => Some(AbilityKind::Target(DamageType::Pure)),
+    "Healing"  => Some(AbilityKind::Target(DamageType::Healing)),
+    "Steal"    => Some(AbilityKind::Target(DamageType::None(steal_action))),
+    "Flee"     => Some(AbilityKind::Battle(flee_action)),
+    "None"     => Some(AbilityKind::None),
+    _ => None
+  }
+}
+
+// Parameters of an ability that targets someone, None for battle-wide actions like Flee
+pub fn get_action_parameters(definition: &AbilityDefinition) -> Option<ActionParameters> {
+  match get_ability_kind(&definition.kind) {
+    Some(AbilityKind::Target(damage_type)) => Some(ActionParameters {
+      element: definition.element.as_ref().and_then(|name| Element::from_name(name)),
+      scope: definition.scope.as_ref().and_then(|name| TargetScope::from_name(name, definition.hits)).unwrap_or(TargetScope::Single),
+      split: definition.split,
+      long_range: definition.long_range,
+      ..ActionParameters::new(
+      damage_type,
+      definition.power,
+      definition.death_chance,
+      definition.revive,
+      definition.status_effects.iter()
+        .filter_map(|status_chance| StatusEffect::from_name(&status_chance.effect).map(|effect| (effect, status_chance.chance)))
+        .collect(),
+      definition.cures.iter().filter_map(|name| StatusEffect::from_name(name)).collect()
+    )}),
+    _ => None
+  }
+}
+
+// A spell's parameters with the MP it costs
+pub fn get_spell_parameters(spell: &SpellDefinition) -> Option<ActionParameters> {
+  let mut action_parameters = get_action_parameters(&spell.ability)?;
+  action_parameters.mp_cost = spell.mp_cost;
+  Some(action_parameters)
+}
+
+fn build_ability(definition: &AbilityDefinition, cursor_memory: (usize, usize)) -> (String, OnClickEvent) {
+  let on_click = match (get_action_parameters(definition), get_ability_kind(&definition.kind)) {
+    (Some(action_parameters), _) => OnClickEvent::ToTargetSelection(menus::to_target_selection, action_parameters, cursor_memory),
+    (None, Some(AbilityKind::Battle(action))) => OnClickEvent::BattleAction(action),
+    _ => OnClickEvent::None
+  };
+  (definition.name.to_owned(), on_click)
+}
+
+fn steal_action(
+  inventory: &mut [InventoryElement],
+  rng: &mut GameRng,
+  _action_parameters: &ActionParameters,
+  target_state: &mut BattleState
+) -> Option<String> {
+  if target_state.common_steal.is_none() && target_state.rare_steal.is_none() {
+    return Some("Nothing to steal".to_owned());
+  }
+  let roll = rng.gen::<f32>();
+  let stolen = match roll {
+    _ if roll < 0.5 => &target_state.common_steal,
+    _ if roll < 0.6 => &target_state.rare_steal,
+    _ => return Some("Could not steal".to_owned())
+  };
+  let stolen = stolen.as_ref()?;
+  let inventory_stack = inventory.iter_mut().find(|inventory_element| inventory_element.get_name() == stolen.get_name())?;
+  let amount = inventory_stack.get_amount_mut();
+  if *amount < ITEM_STACK_LIMIT {*amount += 1}
+  Some(format!("Stole {}", stolen.get_name()))
+}
+
+// Moving between rows takes the character's turn
+pub fn row_action(ctx: &mut Context, party: &mut Party, battle: &mut Battle) -> GameResult<()> {
+  let character = party.get_active();
+  character.change_row();
+  character.animation = (Animation::EndTurn, 12, ticks(ctx));
+  character.sprite = Sprite::WalkLeft;
+  let row = match character.state.back_row {
+    true  => "back",
+    false => "front"
+  };
+  battle.notification = Some(Notification::new(ctx, format!("{} moved to the {} row", character.name, row)));
+  Ok(())
+}
+
+fn flee_action(ctx: &mut Context, party: &mut Party, battle: &mut Battle) -> GameResult<()> {
+  let boss = battle.enemies.iter().flatten().any(|enemy| enemy.boss);
+  let escapeable = battle.enemies.iter().flatten().any(|enemy| enemy.escapeable);
+  match turn::roll_flee(&mut party.rng, boss, escapeable) {
+    FleeOutcome::Blocked => battle.notification = Some(Notification::new(ctx, "Can't escape from this battle".to_owned())),
+    FleeOutcome::Failed  => battle.notification = Some(Notification::new(ctx, "Could not manage to flee".to_owned())),
+    FleeOutcome::Escaped => {
+      for character in [&mut party.first, &mut party.second, &mut party.third, &mut party.fourth] {
+        if character.state.hp > 0 {
+          character.sprite = Sprite::WalkLeft;
+          character.animation = (Animation::Flee, 80, ticks(ctx));
         }
       }
-    }
-    let rng = thread_rng().gen::<f32>();
-    if escapeable && rng < 0.8 {
-      if party.first.state.hp > 0 {
-        party.first.sprite = Sprite::WalkLeft;
-        party.first.animation = (Animation::Flee, 80, ticks(ctx))
-      }
-      if party.second.state.hp > 0 {
-        party.second.sprite = Sprite::WalkLeft;
-        party.second.animation = (Animation::Flee, 80, ticks(ctx))
-      }
-      if party.third.state.hp > 0 {
-        party.third.sprite = Sprite::WalkLeft;
-        party.third.animation = (Animation::Flee, 80, ticks(ctx))
-      }
-      if party.fourth.state.hp > 0 {
-        party.fourth.sprite = Sprite::WalkLeft;
-        party.fourth.animation = (Animation::Flee, 80, ticks(ctx))
-      }
+      party.end_battle(ctx)?;
       battle.notification = Some(Notification::new(ctx, "Party escaped the battle".to_owned()));
-    } else {
-      battle.notification = Some(Notification::new(ctx, "Could not manage to flee".to_owned()));
     }
-    Ok(())
   }
-  let primary_ability = ("Steal".to_owned(), OnClickEvent::ToTargetSelection(
-    menus::to_target_selection, ActionParameters::new(DamageType::None(steal_action), 0, 0., false, 0., false, 0., false), (0, 1)
-  ));
-  let secondary_ability = ("Flee".to_owned(), OnClickEvent::BattleAction(flee_action));
-  Character::new(
-    ctx,
-    id,
-    "/characters/Darrel_Deen.png".to_owned(),
-    "/characters/Darrel_Deen_avatar.png".to_owned(),
-    "Darrel".to_owned(),
-    3, // LVL
-    9935, // HP
-    910, // MP
-    6, // ATK
-    5, // DEF
-    3, // MAG
-    4, // RES
-    5, // AGI
-    false,
-    attack,
-    primary_ability,
-    secondary_ability
-  )
+  Ok(())
 }
-
-pub fn nurse_seraphine(ctx: &mut Context, id: u8) -> Character {
-  let attack = ("Attack".to_owned(), OnClickEvent::ToTargetSelection(
-    menus::to_target_selection, ActionParameters::new(DamageType::Physical, 4, 0., false, 0., false, 0., false), (0, 0)
-  ));
-  let primary_ability = ("Medicine".to_owned(), OnClickEvent::ToTargetSelection(
-    menus::to_target_selection, ActionParameters::new(DamageType::Healing, 4, 0., false, 0., false, 0., false), (0, 1)
-  ));
-  let secondary_ability = ("asdf".to_owned(), OnClickEvent::None);
-  Character::new(
-    ctx,
-    id,
-    "/characters/Nurse_Seraphine.png".to_owned(),
-    "/characters/Nurse_Seraphine_avatar.png".to_owned(),
-    "Seraphine".to_owned(),
-    3, // LVL
-    20, // HP
-    15, // MP
-    5, // ATK
-    3, // DEF
-    5, // MAG
-    3, // RES
-    4, // AGI
-    true,
-    attack,
-    primary_ability,
-    secondary_ability
-  )
-}
\ No newline at end of file
@@ -0,0 +1,765 @@
+use ggez::{Context, GameResult, GameError};
+use ggez::filesystem;
+use serde_derive::Deserialize;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use std::collections::HashMap;
+use std::fs;
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::globals::RESOURCES_DIR;
+use crate::battle::action::TargetScope;
+use crate::battle::behaviour::{Condition, TargetPreference};
+use crate::battle::element::Element;
+use crate::battle::status_effect::{StatusEffect, STATUS_EFFECTS};
+use crate::party::item::InventoryItem;
+use crate::party::equipment::Slot;
+use crate::data::{characters, tilemaps};
+use crate::tilemap::Tilemap;
+use crate::tilemap::tile::Warp;
+use crate::script::{self, Command};
+
+#[derive(Deserialize, Clone)]
+pub struct AbilityDefinition {
+  pub name: String,
+  pub kind: String, // Physical, Magical, Pure, Healing or the name of a special action like Steal and Flee
+  #[serde(default)] pub power: u16,
+  #[serde(default)] pub death_chance: f32,
+  #[serde(default)] pub revive: bool,
+  #[serde(default)] pub element: Option<String>,
+  #[serde(default)] pub scope: Option<String>, // single, all, column, row or random, see TargetScope
+  #[serde(default)] pub hits: u8, // Targets picked by a random scope
+  #[serde(default)] pub split: bool, // Divides the power between everyone hit
+  #[serde(default)] pub long_range: bool, // Physical damage isn't reduced by the back row
+  #[serde(default)] pub status_effects: Vec<StatusChance>,
+  #[serde(default)] pub cures: Vec<String> // Status effects removed by healing
+}
+
+// A status effect an ability inflicts, like { effect = "poison", chance = 0.5 }
+#[derive(Deserialize, Clone)]
+pub struct StatusChance {
+  pub effect: String,
+  pub chance: f32
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CharacterDefinition {
+  pub name: String,
+  pub spritefile: String,
+  pub avatar_spritefile: String,
+  pub level: u8,
+  pub hp: u16,
+  pub mp: u16,
+  pub attack: u16,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8,
+  #[serde(default)] pub back_row: bool,
+  pub attack_ability: AbilityDefinition,
+  pub primary_ability: AbilityDefinition,
+  pub secondary_ability: AbilityDefinition,
+  #[serde(default)] pub growth: GrowthDefinition,
+  #[serde(default)] pub spells: Vec<String>, // Names from the spell catalogue, listed in the Magic menu
+  #[serde(default)] pub equipment: Vec<String> // Worn from the start, at most one per slot
+}
+
+pub const MAX_LEVEL: u8 = 99;
+
+// Experience curve and stat gains of a character. Gain tables hold the gain of each level up in turn and
+// repeat from the start once used up, so [1, 0] raises a stat every other level. Without a curve the character never levels.
+#[derive(Deserialize, Clone, Default)]
+pub struct GrowthDefinition {
+  #[serde(default)] pub experience_base: u32, // Total experience for a level is base * (level - 1)^exponent
+  #[serde(default)] pub experience_exponent: f32,
+  #[serde(default)] pub hp: Vec<u16>,
+  #[serde(default)] pub mp: Vec<u16>,
+  #[serde(default)] pub attack: Vec<u16>,
+  #[serde(default)] pub defence: Vec<u16>,
+  #[serde(default)] pub magic: Vec<u16>,
+  #[serde(default)] pub resistance: Vec<u16>,
+  #[serde(default)] pub agility: Vec<u8>
+}
+
+impl GrowthDefinition {
+  // None when the level can't be reached
+  pub fn experience_for_level(&self, level: u8) -> Option<u32> {
+    if self.experience_base == 0 || level > MAX_LEVEL {
+      return None;
+    }
+    let experience = self.experience_base as f64 * ((level.max(1) - 1) as f64).powf(self.experience_exponent as f64);
+    Some(experience.min(u32::MAX as f64).round() as u32)
+  }
+
+  // Gain of the level up that reaches the given level
+  pub fn gain<T: Copy + Default>(table: &[T], level: u8) -> T {
+    match table.is_empty() {
+      true  => T::default(),
+      false => table[(level.max(2) - 2) as usize % table.len()]
+    }
+  }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EnemyDefinition {
+  pub name: String,
+  pub spritefile: String,
+  pub size: f32,
+  pub level: u8,
+  pub hp: u16,
+  pub mp: u16,
+  pub attack: u16,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8,
+  pub experience: u32,
+  #[serde(default)] pub status_effects: Vec<String>, // Present from the start of battle until cured
+  #[serde(default)] pub immunities: Vec<String>,
+  #[serde(default)] pub affinities: HashMap<String, f32>, // Element to damage multiplier, like { fire = 2.0, ice = -1.0 }
+  #[serde(default)] pub back_row: bool,
+  #[serde(default)] pub abilities: Vec<EnemyAbilityDefinition>, // Used by name in the behaviour rules
+  #[serde(default)] pub behaviour: Vec<BehaviourRule>, // Attacks a random party member three turns in four without any rules
+  #[serde(default)] pub boss: bool, // The party can't flee, whatever the other enemies allow
+  #[serde(default)] pub phases: Vec<PhaseDefinition>,
+  pub common_steal: Option<String>,
+  pub rare_steal: Option<String>,
+  #[serde(default)] pub drops: Vec<DropDefinition>, // Each is rolled on its own when the enemy dies
+  #[serde(default)] pub gold: u32,
+  #[serde(default)] pub escapeable: bool
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DropDefinition {
+  pub item: String,
+  pub chance: f32, // 0-1
+  #[serde(default = "default_drop_amount")] pub amount: u8
+}
+
+fn default_drop_amount() -> u8 {
+  1
+}
+
+// An enemy's ability, one with an MP cost is a spell and is passed over while the enemy is silenced or short of MP
+#[derive(Deserialize, Clone)]
+pub struct EnemyAbilityDefinition {
+  #[serde(default)] pub mp_cost: u16,
+  #[serde(flatten)] pub ability: AbilityDefinition
+}
+
+// A line of an enemy's behaviour, checked in order. See battle::behaviour
+#[derive(Deserialize, Clone)]
+pub struct BehaviourRule {
+  pub ability: String, // Attack, Nothing or one of the enemy's abilities
+  #[serde(default)] pub condition: Option<String>, // hp_below, party_has_effect or every_turns, rules without one are fallbacks
+  #[serde(default)] pub value: f32, // Fraction of max HP for hp_below, turns for every_turns
+  #[serde(default)] pub effect: Option<String>, // Status effect looked for by party_has_effect
+  #[serde(default = "default_rule_weight")] pub weight: f32, // Chance of a fallback against the other fallbacks
+  #[serde(default)] pub target: Option<String> // random, lowest_hp, back_row, caster, self or affected
+}
+
+// Entered once the enemy's HP drops below hp_below, a fraction of max HP
+#[derive(Deserialize, Clone)]
+pub struct PhaseDefinition {
+  pub hp_below: f32,
+  #[serde(default)] pub spritefile: Option<String>,
+  #[serde(default)] pub dialogue: Vec<String>, // Each line is a page spoken by the enemy
+  #[serde(default)] pub behaviour: Vec<BehaviourRule> // Replaces the rules before it, empty keeps them
+}
+
+fn default_rule_weight() -> f32 {
+  1.
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FormationDefinition {
+  #[serde(default)] pub name: String, // Lets scripts start this exact formation
+  pub table: String,
+  pub weight: f32,
+  pub columns: Vec<Vec<String>>
+}
+
+// Worn in one of a character's slots, everything but the slot and price is added to the wearer in battle
+#[derive(Deserialize, Clone)]
+pub struct EquipmentDefinition {
+  pub name: String,
+  pub slot: String, // weapon, armour or accessory
+  #[serde(default)] pub price: u32, // Paid in shops, 0 can't be sold
+  #[serde(default)] pub attack: i16,
+  #[serde(default)] pub defence: i16,
+  #[serde(default)] pub magic: i16,
+  #[serde(default)] pub resistance: i16,
+  #[serde(default)] pub agility: i16,
+  #[serde(default)] pub affinities: HashMap<String, f32>, // Like the enemies' affinities
+  #[serde(default)] pub immunities: Vec<String>,
+  #[serde(default)] pub spells: Vec<String>,
+  #[serde(default)] pub preemptive: f32, // Added to the chance of a preemptive strike
+  #[serde(default)] pub alert: bool // The party can't be caught in a back attack or pincer
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ShopDefinition {
+  pub name: String, // Opened by scripts with shop name
+  pub items: Vec<ShopItemDefinition>
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ShopItemDefinition {
+  pub item: String, // An item or a piece of equipment
+  #[serde(default)] pub price: Option<u32> // The item's own price without it
+}
+
+#[derive(Deserialize)]
+struct CharacterFile {
+  character: Vec<CharacterDefinition>
+}
+
+#[derive(Deserialize)]
+struct EnemyFile {
+  enemy: Vec<EnemyDefinition>
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SpellDefinition {
+  pub mp_cost: u16,
+  #[serde(default = "default_spell_target")] pub target: String, // Enemy, Ally or Any
+  #[serde(flatten)] pub ability: AbilityDefinition
+}
+
+fn default_spell_target() -> String {
+  "Any".to_owned()
+}
+
+#[derive(Deserialize)]
+struct SpellFile {
+  spell: Vec<SpellDefinition>
+}
+
+// Tuning of the damage formula, see battle::damage
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CombatDefinition {
+  pub formula: String, // standard, or classic for the original power * 3 / defence
+  pub stat_scaling: f32, // Each point of attack or magic adds this much damage, 0.1 is 10%
+  pub hit_chance: f32,
+  pub evasion_per_agility: f32, // Taken off the hit chance for each point of agility the defender has over the attacker
+  pub critical_chance: f32,
+  pub critical_multiplier: f32,
+  pub variance: f32, // Damage is randomly up to this fraction lower or higher
+  pub preemptive_chance: f32, // Of a random encounter, see battle::encounter
+  pub back_attack_chance: f32,
+  pub pincer_chance: f32,
+  pub encounter_agility_scaling: f32 // Each point of the party's average agility makes preemptive strikes this fraction likelier and ambushes rarer
+}
+
+impl Default for CombatDefinition {
+  fn default() -> CombatDefinition {
+    CombatDefinition {
+      formula: "standard".to_owned(),
+      stat_scaling: 0.05,
+      hit_chance: 0.95,
+      evasion_per_agility: 0.02,
+      critical_chance: 0.05,
+      critical_multiplier: 2.,
+      variance: 0.1,
+      preemptive_chance: 0.08,
+      back_attack_chance: 0.06,
+      pincer_chance: 0.04,
+      encounter_agility_scaling: 0.05
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct FormationFile {
+  formation: Vec<FormationDefinition>
+}
+
+#[derive(Deserialize)]
+struct EquipmentFile {
+  equipment: Vec<EquipmentDefinition>
+}
+
+#[derive(Deserialize)]
+struct ShopFile {
+  shop: Vec<ShopDefinition>
+}
+
+pub const CHARACTERS_FILE: &str = "/data/characters.toml";
+pub const ENEMIES_FILE: &str = "/data/enemies.toml";
+pub const FORMATIONS_FILE: &str = "/data/formations.toml";
+pub const SPELLS_FILE: &str = "/data/spells.toml";
+pub const COMBAT_FILE: &str = "/data/combat.toml";
+pub const SHOPS_FILE: &str = "/data/shops.toml";
+pub const EQUIPMENT_FILE: &str = "/data/equipment.toml";
+
+fn parse_file<T: DeserializeOwned>(path: &str, contents: &str) -> GameResult<T> {
+  toml::from_str(contents).map_err(|error| GameError::ResourceLoadError(format!("{}: {}", path, error)))
+}
+
+fn read_file(ctx: &mut Context, path: &str) -> GameResult<String> {
+  let mut contents = String::new();
+  filesystem::open(ctx, path)?.read_to_string(&mut contents)?;
+  Ok(contents)
+}
+
+// Reads straight from the resources folder, for tools that run without a window and so without a ggez Context.
+// Looks where the game's ggez filesystem does, next to the executable and then in the working directory,
+// and last in the crate's own folder so cargo run works from anywhere.
+fn read_resource_file(path: &str) -> GameResult<String> {
+  let mut resource_dirs = Vec::new();
+  if let Some(exe_dir) = env::current_exe().ok().as_ref().and_then(|exe| exe.parent()) {
+    resource_dirs.push(exe_dir.join("resources"));
+  }
+  resource_dirs.push(PathBuf::from(RESOURCES_DIR));
+  resource_dirs.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("resources"));
+  let relative_path = path.trim_start_matches('/');
+  match resource_dirs.iter().map(|dir| dir.join(relative_path)).find(|file| file.is_file()) {
+    Some(file) => Ok(fs::read_to_string(file)?),
+    None => {
+      let searched: Vec<String> = resource_dirs.iter().map(|dir| dir.display().to_string()).collect();
+      Err(GameError::ResourceLoadError(format!("{}: not found in {}", path, searched.join(", "))))
+    }
+  }
+}
+
+pub fn parse_characters(contents: &str) -> GameResult<Vec<CharacterDefinition>> {
+  Ok(parse_file::<CharacterFile>(CHARACTERS_FILE, contents)?.character)
+}
+
+pub fn parse_enemies(contents: &str) -> GameResult<Vec<EnemyDefinition>> {
+  Ok(parse_file::<EnemyFile>(ENEMIES_FILE, contents)?.enemy)
+}
+
+pub fn parse_formations(contents: &str) -> GameResult<Vec<FormationDefinition>> {
+  Ok(parse_file::<FormationFile>(FORMATIONS_FILE, contents)?.formation)
+}
+
+pub fn parse_spells(contents: &str) -> GameResult<Vec<SpellDefinition>> {
+  Ok(parse_file::<SpellFile>(SPELLS_FILE, contents)?.spell)
+}
+
+pub fn parse_combat(contents: &str) -> GameResult<CombatDefinition> {
+  parse_file(COMBAT_FILE, contents)
+}
+
+pub fn parse_shops(contents: &str) -> GameResult<Vec<ShopDefinition>> {
+  Ok(parse_file::<ShopFile>(SHOPS_FILE, contents)?.shop)
+}
+
+pub fn parse_equipment(contents: &str) -> GameResult<Vec<EquipmentDefinition>> {
+  Ok(parse_file::<EquipmentFile>(EQUIPMENT_FILE, contents)?.equipment)
+}
+
+pub fn load_characters(ctx: &mut Context) -> GameResult<Vec<CharacterDefinition>> {
+  parse_characters(&read_file(ctx, CHARACTERS_FILE)?)
+}
+
+pub fn load_enemies(ctx: &mut Context) -> GameResult<Vec<EnemyDefinition>> {
+  parse_enemies(&read_file(ctx, ENEMIES_FILE)?)
+}
+
+pub fn load_formations(ctx: &mut Context) -> GameResult<Vec<FormationDefinition>> {
+  parse_formations(&read_file(ctx, FORMATIONS_FILE)?)
+}
+
+pub fn load_spells(ctx: &mut Context) -> GameResult<Vec<SpellDefinition>> {
+  parse_spells(&read_file(ctx, SPELLS_FILE)?)
+}
+
+pub fn load_combat(ctx: &mut Context) -> GameResult<CombatDefinition> {
+  parse_combat(&read_file(ctx, COMBAT_FILE)?)
+}
+
+pub fn load_shops(ctx: &mut Context) -> GameResult<Vec<ShopDefinition>> {
+  parse_shops(&read_file(ctx, SHOPS_FILE)?)
+}
+
+pub fn load_equipment(ctx: &mut Context) -> GameResult<Vec<EquipmentDefinition>> {
+  parse_equipment(&read_file(ctx, EQUIPMENT_FILE)?)
+}
+
+// What battles are built from, parsed once at startup and shared by every battle after
+pub struct BattleContent {
+  pub enemies: Vec<EnemyDefinition>,
+  pub formations: Vec<FormationDefinition>,
+  pub combat: CombatDefinition
+}
+
+pub fn load_battle_content(ctx: &mut Context) -> GameResult<BattleContent> {
+  Ok(BattleContent {enemies: load_enemies(ctx)?, formations: load_formations(ctx)?, combat: load_combat(ctx)?})
+}
+
+// What the party and its menus look things up in by name, parsed once at startup like BattleContent
+pub struct GameContent {
+  pub characters: Vec<CharacterDefinition>,
+  pub equipment: Vec<EquipmentDefinition>,
+  pub shops: Vec<ShopDefinition>,
+  pub spells: Vec<SpellDefinition>
+}
+
+pub fn load_game_content(ctx: &mut Context) -> GameResult<GameContent> {
+  Ok(GameContent {characters: load_characters(ctx)?, equipment: load_equipment(ctx)?, shops: load_shops(ctx)?, spells: load_spells(ctx)?})
+}
+
+pub fn read_characters() -> GameResult<Vec<CharacterDefinition>> {
+  parse_characters(&read_resource_file(CHARACTERS_FILE)?)
+}
+
+pub fn read_enemies() -> GameResult<Vec<EnemyDefinition>> {
+  parse_enemies(&read_resource_file(ENEMIES_FILE)?)
+}
+
+pub fn read_combat() -> GameResult<CombatDefinition> {
+  parse_combat(&read_resource_file(COMBAT_FILE)?)
+}
+
+pub fn read_formations() -> GameResult<Vec<FormationDefinition>> {
+  parse_formations(&read_resource_file(FORMATIONS_FILE)?)
+}
+
+pub fn read_equipment() -> GameResult<Vec<EquipmentDefinition>> {
+  parse_equipment(&read_resource_file(EQUIPMENT_FILE)?)
+}
+
+pub fn read_spells() -> GameResult<Vec<SpellDefinition>> {
+  parse_spells(&read_resource_file(SPELLS_FILE)?)
+}
+
+fn validate_sprite(ctx: &mut Context, errors: &mut Vec<String>, file: &str, owner: &str, spritefile: &str) {
+  if !filesystem::is_file(ctx, spritefile) {
+    errors.push(format!("{}: {} uses sprite {} which does not exist", file, owner, spritefile));
+  }
+}
+
+fn validate_ability(errors: &mut Vec<String>, file: &str, owner: &str, ability: &AbilityDefinition) {
+  if characters::get_ability_kind(&ability.kind).is_none() {
+    errors.push(format!("{}: {} has ability {} of unknown kind {}", file, owner, ability.name, ability.kind));
+  }
+  for status_chance in ability.status_effects.iter() {
+    validate_status_effect(errors, file, owner, &status_chance.effect);
+    if status_chance.chance < 0. || status_chance.chance > 1. {
+      errors.push(format!("{}: {} has ability {} with {} chance outside 0-1", file, owner, ability.name, status_chance.effect));
+    }
+  }
+  for effect_name in ability.cures.iter() {
+    validate_status_effect(errors, file, owner, effect_name);
+  }
+  if let Some(element_name) = &ability.element {
+    validate_element(errors, file, owner, element_name);
+  }
+  if let Some(scope) = &ability.scope {
+    if TargetScope::from_name(scope, ability.hits).is_none() {
+      errors.push(format!(
+        "{}: {} has ability {} with unknown scope {}, use single, all, column, row or random with hits above 0", file, owner, ability.name, scope
+      ));
+    }
+  }
+}
+
+fn validate_element(errors: &mut Vec<String>, file: &str, owner: &str, element_name: &str) {
+  if Element::from_name(element_name).is_none() {
+    errors.push(format!("{}: {} uses unknown element {}", file, owner, element_name));
+  }
+}
+
+fn validate_status_effect(errors: &mut Vec<String>, file: &str, owner: &str, effect_name: &str) {
+  if StatusEffect::from_name(effect_name).is_none() {
+    errors.push(format!("{}: {} uses unknown status effect {}", file, owner, effect_name));
+  }
+}
+
+fn validate_item(errors: &mut Vec<String>, owner: &str, item: &Option<String>) {
+  if let Some(item_name) = item {
+    if InventoryItem::from_name(item_name).is_none() {
+      errors.push(format!("{}: {} can drop unknown item {}", ENEMIES_FILE, owner, item_name));
+    }
+  }
+}
+
+fn validate_rules(errors: &mut Vec<String>, enemy: &EnemyDefinition, rules: &[BehaviourRule]) {
+  for rule in rules.iter() {
+    if rule.ability != "Attack" && rule.ability != "Nothing" && !enemy.abilities.iter().any(|enemy_ability| enemy_ability.ability.name == rule.ability) {
+      errors.push(format!("{}: enemy {} has a rule using unknown ability {}", ENEMIES_FILE, enemy.name, rule.ability));
+    }
+    if let Some(condition) = &rule.condition {
+      if Condition::from_name(condition, rule.value, rule.effect.as_deref()).is_none() {
+        errors.push(format!(
+          "{}: enemy {} has a rule with condition {} which is unknown or misses its value or effect", ENEMIES_FILE, enemy.name, condition
+        ));
+      }
+    }
+    if let Some(target) = &rule.target {
+      if TargetPreference::from_name(target).is_none() {
+        errors.push(format!("{}: enemy {} has a rule with unknown target {}", ENEMIES_FILE, enemy.name, target));
+      }
+    }
+    if rule.weight < 0. {
+      errors.push(format!("{}: enemy {} has a rule with a weight below 0", ENEMIES_FILE, enemy.name));
+    }
+  }
+}
+
+pub fn validate(ctx: &mut Context) -> Vec<String> {
+  let mut errors = Vec::new();
+  let character_definitions = load_characters(ctx).unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let enemy_definitions     = load_enemies(ctx)   .unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let formation_definitions = load_formations(ctx).unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let spell_definitions     = load_spells(ctx)    .unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let shop_definitions      = load_shops(ctx)     .unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let equipment_definitions = load_equipment(ctx) .unwrap_or_else(|error| {errors.push(format!("{}", error)); Vec::new()});
+  let is_known = |name: &str| InventoryItem::from_name(name).is_some() || equipment_definitions.iter().any(|equipment| equipment.name == name);
+  for (index, equipment) in equipment_definitions.iter().enumerate() {
+    if equipment_definitions[..index].iter().any(|other| other.name == equipment.name) {
+      errors.push(format!("{}: equipment {} is defined more than once", EQUIPMENT_FILE, equipment.name));
+    }
+    if InventoryItem::from_name(&equipment.name).is_some() {
+      errors.push(format!("{}: equipment {} has the name of an item", EQUIPMENT_FILE, equipment.name));
+    }
+    if Slot::from_name(&equipment.slot).is_none() {
+      errors.push(format!("{}: equipment {} has unknown slot {}, use weapon, armour or accessory", EQUIPMENT_FILE, equipment.name, equipment.slot));
+    }
+    for (element_name, multiplier) in equipment.affinities.iter() {
+      validate_element(&mut errors, EQUIPMENT_FILE, &equipment.name, element_name);
+      if !multiplier.is_finite() {
+        errors.push(format!("{}: equipment {} has {} affinity {} which is not a number", EQUIPMENT_FILE, equipment.name, element_name, multiplier));
+      }
+    }
+    for effect_name in equipment.immunities.iter() {
+      validate_status_effect(&mut errors, EQUIPMENT_FILE, &equipment.name, effect_name);
+    }
+    for spell_name in equipment.spells.iter() {
+      if !spell_definitions.iter().any(|spell| &spell.ability.name == spell_name) {
+        errors.push(format!("{}: equipment {} teaches unknown spell {}", EQUIPMENT_FILE, equipment.name, spell_name));
+      }
+    }
+    if equipment.preemptive < 0. || equipment.preemptive > 1. {
+      errors.push(format!("{}: equipment {} has preemptive outside 0-1", EQUIPMENT_FILE, equipment.name));
+    }
+  }
+  match load_combat(ctx) {
+    Ok(combat) => {
+      if combat.formula != "standard" && combat.formula != "classic" {
+        errors.push(format!("{}: unknown formula {}, use standard or classic", COMBAT_FILE, combat.formula));
+      }
+      let chances = [
+        ("hit_chance", combat.hit_chance), ("critical_chance", combat.critical_chance), ("variance", combat.variance),
+        ("preemptive_chance", combat.preemptive_chance), ("back_attack_chance", combat.back_attack_chance), ("pincer_chance", combat.pincer_chance)
+      ];
+      for (name, chance) in chances.iter() {
+        if *chance < 0. || *chance > 1. {
+          errors.push(format!("{}: {} must be between 0 and 1", COMBAT_FILE, name));
+        }
+      }
+      if combat.critical_multiplier < 1. || combat.stat_scaling < 0. || combat.evasion_per_agility < 0. || combat.encounter_agility_scaling < 0. {
+        errors.push(format!(
+          "{}: critical_multiplier must be at least 1, stat_scaling, evasion_per_agility and encounter_agility_scaling at least 0", COMBAT_FILE
+        ));
+      }
+    },
+    Err(error) => errors.push(format!("{}", error))
+  }
+  for (index, shop) in shop_definitions.iter().enumerate() {
+    if shop_definitions[..index].iter().any(|other| other.name == shop.name) {
+      errors.push(format!("{}: shop {} is defined more than once", SHOPS_FILE, shop.name));
+    }
+    if shop.items.is_empty() {
+      errors.push(format!("{}: shop {} has nothing to sell", SHOPS_FILE, shop.name));
+    }
+    for shop_item in shop.items.iter() {
+      if !is_known(&shop_item.item) {
+        errors.push(format!("{}: shop {} sells unknown item {}", SHOPS_FILE, shop.name, shop_item.item));
+      }
+      if shop_item.price == Some(0) {
+        errors.push(format!("{}: shop {} sells {} for 0", SHOPS_FILE, shop.name, shop_item.item));
+      }
+    }
+  }
+  for effect in STATUS_EFFECTS.iter() {
+    validate_sprite(ctx, &mut errors, "status effects", effect.get_name(), &format!("/status_effects/{}.png", effect.get_name()));
+  }
+  for (index, spell) in spell_definitions.iter().enumerate() {
+    if spell_definitions[..index].iter().any(|other| other.ability.name == spell.ability.name) {
+      errors.push(format!("{}: spell {} is defined more than once", SPELLS_FILE, spell.ability.name));
+    }
+    validate_ability(&mut errors, SPELLS_FILE, &spell.ability.name, &spell.ability);
+    if characters::get_ability_kind(&spell.ability.kind).is_some() && characters::get_action_parameters(&spell.ability).is_none() {
+      errors.push(format!("{}: spell {} must target someone, {} spells can't be cast", SPELLS_FILE, spell.ability.name, spell.ability.kind));
+    }
+    if !["Enemy", "Ally", "Any"].contains(&spell.target.as_str()) {
+      errors.push(format!("{}: spell {} has unknown target {}, use Enemy, Ally or Any", SPELLS_FILE, spell.ability.name, spell.target));
+    }
+  }
+  for (index, character) in character_definitions.iter().enumerate() {
+    if character_definitions[..index].iter().any(|other| other.name == character.name) {
+      errors.push(format!("{}: character {} is defined more than once", CHARACTERS_FILE, character.name));
+    }
+    validate_sprite(ctx, &mut errors, CHARACTERS_FILE, &character.name, &character.spritefile);
+    validate_sprite(ctx, &mut errors, CHARACTERS_FILE, &character.name, &character.avatar_spritefile);
+    validate_ability(&mut errors, CHARACTERS_FILE, &character.name, &character.attack_ability);
+    validate_ability(&mut errors, CHARACTERS_FILE, &character.name, &character.primary_ability);
+    validate_ability(&mut errors, CHARACTERS_FILE, &character.name, &character.secondary_ability);
+    for spell_name in character.spells.iter() {
+      if !spell_definitions.iter().any(|spell| &spell.ability.name == spell_name) {
+        errors.push(format!("{}: character {} knows unknown spell {}", CHARACTERS_FILE, character.name, spell_name));
+      }
+    }
+    let mut worn_slots = Vec::new();
+    for equipment_name in character.equipment.iter() {
+      match equipment_definitions.iter().find(|equipment| &equipment.name == equipment_name) {
+        Some(equipment) if worn_slots.contains(&equipment.slot) => {
+          errors.push(format!("{}: character {} wears more than one {}", CHARACTERS_FILE, character.name, equipment.slot));
+        },
+        Some(equipment) => worn_slots.push(equipment.slot.to_owned()),
+        None => errors.push(format!("{}: character {} wears unknown equipment {}", CHARACTERS_FILE, character.name, equipment_name))
+      }
+    }
+    if character.growth.experience_base > 0 && character.growth.experience_exponent <= 0. {
+      errors.push(format!("{}: character {} needs an experience_exponent above 0", CHARACTERS_FILE, character.name));
+    }
+  }
+  for (index, enemy) in enemy_definitions.iter().enumerate() {
+    if enemy_definitions[..index].iter().any(|other| other.name == enemy.name) {
+      errors.push(format!("{}: enemy {} is defined more than once", ENEMIES_FILE, enemy.name));
+    }
+    if enemy.defence == 0 || enemy.resistance == 0 {
+      errors.push(format!("{}: enemy {} must have defence and resistance above 0", ENEMIES_FILE, enemy.name));
+    }
+    validate_sprite(ctx, &mut errors, ENEMIES_FILE, &enemy.name, &enemy.spritefile);
+    validate_item(&mut errors, &enemy.name, &enemy.common_steal);
+    validate_item(&mut errors, &enemy.name, &enemy.rare_steal);
+    for drop in enemy.drops.iter() {
+      validate_item(&mut errors, &enemy.name, &Some(drop.item.to_owned()));
+      if drop.chance <= 0. || drop.chance > 1. {
+        errors.push(format!("{}: enemy {} drops {} with a chance outside 0-1", ENEMIES_FILE, enemy.name, drop.item));
+      }
+      if drop.amount == 0 {
+        errors.push(format!("{}: enemy {} drops {} with amount 0", ENEMIES_FILE, enemy.name, drop.item));
+      }
+    }
+    for effect_name in enemy.status_effects.iter().chain(enemy.immunities.iter()) {
+      validate_status_effect(&mut errors, ENEMIES_FILE, &enemy.name, effect_name);
+    }
+    for (element_name, multiplier) in enemy.affinities.iter() {
+      validate_element(&mut errors, ENEMIES_FILE, &enemy.name, element_name);
+      if !multiplier.is_finite() {
+        errors.push(format!("{}: enemy {} has {} affinity {} which is not a number", ENEMIES_FILE, enemy.name, element_name, multiplier));
+      }
+    }
+    for EnemyAbilityDefinition {mp_cost, ability} in enemy.abilities.iter() {
+      validate_ability(&mut errors, ENEMIES_FILE, &enemy.name, ability);
+      if *mp_cost > enemy.mp {
+        errors.push(format!("{}: enemy {} can never pay the {} MP of ability {}", ENEMIES_FILE, enemy.name, mp_cost, ability.name));
+      }
+      if ability.name == "Attack" || ability.name == "Nothing" {
+        errors.push(format!("{}: enemy {} can't name an ability {}, the name is taken by the built in action", ENEMIES_FILE, enemy.name, ability.name));
+      } else if characters::get_ability_kind(&ability.kind).is_some() && characters::get_action_parameters(ability).is_none() {
+        errors.push(format!("{}: enemy {} has ability {} which doesn't target anyone", ENEMIES_FILE, enemy.name, ability.name));
+      }
+    }
+    validate_rules(&mut errors, enemy, &enemy.behaviour);
+    for (index, phase) in enemy.phases.iter().enumerate() {
+      if phase.hp_below <= 0. || phase.hp_below > 1. {
+        errors.push(format!("{}: enemy {} has a phase with hp_below outside 0-1", ENEMIES_FILE, enemy.name));
+      }
+      if index > 0 && phase.hp_below >= enemy.phases[index - 1].hp_below {
+        errors.push(format!("{}: enemy {} must list its phases from the highest hp_below to the lowest", ENEMIES_FILE, enemy.name));
+      }
+      if let Some(spritefile) = &phase.spritefile {
+        validate_sprite(ctx, &mut errors, ENEMIES_FILE, &enemy.name, spritefile);
+      }
+      validate_rules(&mut errors, enemy, &phase.behaviour);
+    }
+  }
+  for (index, formation) in formation_definitions.iter().enumerate() {
+    if !formation.name.is_empty() && formation_definitions[..index].iter().any(|other| other.name == formation.name) {
+      errors.push(format!("{}: formation {} is defined more than once", FORMATIONS_FILE, formation.name));
+    }
+    if formation.weight <= 0. {
+      errors.push(format!("{}: formation in table {} must have a weight above 0", FORMATIONS_FILE, formation.table));
+    }
+    if formation.columns.iter().all(|column| column.is_empty()) {
+      errors.push(format!("{}: formation in table {} has no enemies", FORMATIONS_FILE, formation.table));
+    }
+    for enemy_name in formation.columns.iter().flatten() {
+      if !enemy_definitions.iter().any(|enemy| &enemy.name == enemy_name) {
+        errors.push(format!("{}: formation in table {} references unknown enemy {}", FORMATIONS_FILE, formation.table, enemy_name));
+      }
+    }
+  }
+  let map_paths: Vec<String> = match filesystem::read_dir(ctx, "/maps") {
+    Ok(paths) => paths.map(|path| path.to_string_lossy().replace('\\', "/")).filter(|path| path.ends_with(".json")).collect(),
+    Err(_) => Vec::new()
+  };
+  let mut maps = Vec::new();
+  for map_path in map_paths {
+    let map_name = map_path.trim_start_matches("/maps/").trim_end_matches(".json").to_owned();
+    match tilemaps::get_tilemap(ctx, &map_name) {
+      Ok(map) => {
+        if map.encounter_rate > 0. && !formation_definitions.iter().any(|formation| formation.table == map.enemy_formations) {
+          errors.push(format!("{}: map encounters use formation table {} which has no formations", map_path, map.enemy_formations));
+        }
+        maps.push((map_path, map));
+      },
+      Err(error) => errors.push(format!("{}", error))
+    }
+  }
+  for (map_path, map) in maps.iter() {
+    for warp in map.tiles.iter().flatten().filter_map(|tile| tile.warp.as_ref()) {
+      validate_warp(&mut errors, map_path, warp, &maps);
+    }
+    for script_name in map.tiles.iter().flatten().filter_map(|tile| tile.event.as_ref()) {
+      if !filesystem::is_file(ctx, script::get_script_path(script_name)) {
+        errors.push(format!("{}: event uses script {} which does not exist", map_path, script_name));
+      }
+    }
+    for npc in map.npcs.iter() {
+      if let Some(script_name) = &npc.script {
+        if !filesystem::is_file(ctx, script::get_script_path(script_name)) {
+          errors.push(format!("{}: npc {} uses script {} which does not exist", map_path, npc.name, script_name));
+        }
+      }
+    }
+  }
+  let script_paths: Vec<String> = match filesystem::read_dir(ctx, "/scripts") {
+    Ok(paths) => paths.map(|path| path.to_string_lossy().replace('\\', "/")).filter(|path| path.ends_with(".txt")).collect(),
+    Err(_) => Vec::new()
+  };
+  for script_path in script_paths {
+    let script_name = script_path.trim_start_matches("/scripts/").trim_end_matches(".txt").to_owned();
+    let loaded_script = match script::load_script(ctx, &script_name) {
+      Ok(loaded_script) => loaded_script,
+      Err(error) => {
+        errors.push(format!("{}", error));
+        continue;
+      }
+    };
+    for command in loaded_script.commands.iter() {
+      match command {
+        Command::Give(item_name, _) if !is_known(item_name) => {
+          errors.push(format!("{}: gives unknown item {}", script_path, item_name));
+        },
+        Command::Battle(formation_name, _) if !formation_definitions.iter().any(|formation| &formation.name == formation_name) => {
+          errors.push(format!("{}: starts a battle with unknown formation {}", script_path, formation_name));
+        },
+        Command::Shop(shop_name) if !shop_definitions.iter().any(|shop| &shop.name == shop_name) => {
+          errors.push(format!("{}: opens unknown shop {}", script_path, shop_name));
+        },
+        Command::Warp(warp) => validate_warp(&mut errors, &script_path, warp, &maps),
+        _ => ()
+      }
+    }
+  }
+  errors
+}
+
+// Tile and script warps alike must lead to a free tile of a known map
+fn validate_warp(errors: &mut Vec<String>, source: &str, warp: &Warp, maps: &[(String, Tilemap)]) {
+  match maps.iter().find(|(_, map)| map.name == warp.map) {
+    Some((_, destination)) if !destination.is_free(warp.position) => {
+      errors.push(format!("{}: warp leads to {:?} on map {} which is not walkable", source, warp.position, warp.map));
+    },
+    Some(_) => (),
+    None => errors.push(format!("{}: warp leads to unknown map {}", source, warp.map))
+  }
+}
@@ -1,124 +1,123 @@
 use ggez::{Context, GameResult};
 use ggez::timer::ticks;
-use rand::{Rng, thread_rng};
 use crate::battle::action::{ActionParameters, DamageType};
-use crate::battle::enemy::{Enemy, Animation};
+use crate::battle::behaviour::{Behaviour, Rule, Phase, Condition, TargetPreference};
+use crate::battle::enemy::{Enemy, Animation, Drop, SELF_TARGET};
+use crate::battle::element::{Element, Affinity};
+use crate::battle::state::BattleState;
+use crate::battle::status_effect::StatusEffect;
+use crate::battle::turn;
 use crate::party::Party;
-use crate::party::item::{InventoryItem, ItemVariant};
+use crate::party::item::InventoryItem;
 use crate::menu::notification::Notification;
+use crate::data::characters;
+use crate::data::content::{EnemyDefinition, BehaviourRule};
 
-fn get_random_target(party: &Party) -> u8 {
-  let mut party_targets = Vec::new();
-  if party.first .name.len() > 0 && party.first .state.hp > 0 {party_targets.push(0)}
-  if party.second.name.len() > 0 && party.second.state.hp > 0 {party_targets.push(1)}
-  if party.third .name.len() > 0 && party.third .state.hp > 0 {party_targets.push(2)}
-  if party.fourth.name.len() > 0 && party.fourth.state.hp > 0 {party_targets.push(3)}
-  let mut rng = thread_rng();
-  let target_rng = rng.gen::<f32>();
-  if target_rng <= 1. / party_targets.len() as f32 {
-    party_targets[0]
-  } else if target_rng <= 2. / party_targets.len() as f32 {
-    party_targets[1]
-  } else if target_rng <= 3. / party_targets.len() as f32 {
-    party_targets[2]
-  } else {
-    party_targets[3]
+pub fn turn_action(ctx: &mut Context, enemy: &mut Enemy, party: &mut Party, notification: &mut Option<Notification>) -> GameResult<()> {
+  let party_targets: Vec<(u8, &BattleState)> = vec![&party.first, &party.second, &party.third, &party.fourth].into_iter()
+    .enumerate()
+    .filter(|(_, character)| !character.name.is_empty() && character.state.hp > 0)
+    .map(|(index, character)| (index as u8, &character.state))
+    .collect();
+  match turn::choose_enemy_action(&mut party.rng, &mut enemy.behaviour, &mut enemy.state, &party_targets) {
+    Some((target, name, action_parameters)) => {
+      *notification = Some(Notification::new(ctx, match (target, name.as_str()) {
+        (SELF_TARGET, "Attack") => format!("{} attacks itself", enemy.name),
+        (_, "Attack") => format!("{} attacks", enemy.name),
+        _ => format!("{} uses {}", enemy.name, name)
+      }));
+      enemy.animation = (Animation::StartTurn(target, action_parameters), 60, ticks(ctx));
+    },
+    None => {
+      *notification = Some(Notification::new(ctx, format!("{} does nothing", enemy.name)));
+      enemy.animation = (Animation::EndTurn, 30, ticks(ctx));
+    }
   }
+  Ok(())
 }
 
-fn turn_action(ctx: &mut Context, enemy: &mut Enemy, party: &mut Party, notification: &mut Option<Notification>) -> GameResult<()> {
-  let mut rng = thread_rng();
-  if rng.gen::<f32>() < 0.75 {
-    *notification = Some(Notification::new(ctx, format!("{} attacks", enemy.name)));
-    let action_parameters = ActionParameters::new(DamageType::Physical, enemy.state.attack, 0., false, 0., false, 0., false);
-    enemy.animation = (Animation::StartTurn(get_random_target(party), action_parameters), 60, ticks(ctx));
-  } else {
-    *notification = Some(Notification::new(ctx, format!("{} does nothing", enemy.name)));
-    enemy.animation = (Animation::EndTurn, 30, ticks(ctx));
-  }
-  Ok(())
+// Only the column nearest the party on its side stands in the front row, an enemy defined in the back row stays there in any column
+pub fn is_back_row(definition: &EnemyDefinition, column: usize) -> bool {
+  definition.back_row || column > 0
 }
 
-pub fn test_triangle(ctx: &mut Context, id: u8, screen_pos: (f32, f32), selection_pos: (usize, usize)) -> Enemy {
-  Enemy::new(
+pub fn build_enemy(ctx: &mut Context, id: u8, definition: &EnemyDefinition, screen_pos: (f32, f32), selection_pos: (usize, usize)) -> Enemy {
+  let steal_item = |item_name: &Option<String>| match item_name {
+    Some(item_name) => InventoryItem::from_name(item_name),
+    None => None
+  };
+  let mut enemy = Enemy::new(
     ctx,
     id,
-    "/enemies/test-triangle.png".to_owned(),
+    definition.spritefile.to_owned(),
     screen_pos,
     selection_pos,
-    2.,
-    "Triangle".to_owned(),
-    3,
-    16,
-    5,
-    6,
-    2,
-    2,
-    2,
-    2,
-    15,
-    0,
-    0,
-    false,
-    Some(InventoryItem::new(ItemVariant::Potion)),
-    Some(InventoryItem::new(ItemVariant::Ether)),
-    true,
-    turn_action
-  )
+    definition.size,
+    definition.name.to_owned(),
+    definition.level,
+    definition.hp,
+    definition.mp,
+    definition.attack,
+    definition.defence,
+    definition.magic,
+    definition.resistance,
+    definition.agility,
+    definition.experience,
+    definition.status_effects.iter().filter_map(|name| StatusEffect::from_name(name)).collect(),
+    definition.immunities.iter().filter_map(|name| StatusEffect::from_name(name)).collect(),
+    definition.back_row,
+    steal_item(&definition.common_steal),
+    steal_item(&definition.rare_steal),
+    definition.escapeable,
+    get_behaviour(definition)
+  );
+  enemy.boss = definition.boss;
+  enemy.drops = definition.drops.iter()
+    .filter_map(|drop| InventoryItem::from_name(&drop.item).map(|item| Drop {item, chance: drop.chance, amount: drop.amount}))
+    .collect();
+  enemy.gold = definition.gold;
+  enemy.state.affinities = get_affinities(definition);
+  enemy
 }
 
-pub fn test_circle(ctx: &mut Context, id: u8, screen_pos: (f32, f32), selection_pos: (usize, usize)) -> Enemy {
-  Enemy::new(
-    ctx,
-    id,
-    "/enemies/test-circle.png".to_owned(),
-    screen_pos,
-    selection_pos,
-    1.,
-    "Circle".to_owned(),
-    2,
-    12,
-    6,
-    4,
-    1,
-    1,
-    2,
-    3,
-    7,
-    0,
-    0,
-    false,
-    Some(InventoryItem::new(ItemVariant::Potion)),
-    Some(InventoryItem::new(ItemVariant::Ether)),
-    true,
-    turn_action
-  )
+// The enemy's rules and phases with their abilities looked up, attacking three turns in four when it has no rules
+pub fn get_behaviour(definition: &EnemyDefinition) -> Behaviour {
+  let phases = definition.phases.iter().map(|phase| Phase {
+    hp_below: phase.hp_below,
+    rules: get_rules(definition, &phase.behaviour),
+    spritefile: phase.spritefile.to_owned(),
+    dialogue: phase.dialogue.to_owned()
+  }).collect();
+  if definition.behaviour.is_empty() {
+    let attack = ActionParameters::new(DamageType::Physical, definition.attack, 0., false, Vec::new(), Vec::new());
+    return Behaviour::new(vec![
+      Rule {name: "Attack".to_owned(), action: Some(attack), condition: None, weight: 3., target: TargetPreference::Random},
+      Rule {name: "Nothing".to_owned(), action: None, condition: None, weight: 1., target: TargetPreference::Random}
+    ], phases);
+  }
+  Behaviour::new(get_rules(definition, &definition.behaviour), phases)
 }
 
-pub fn test_square(ctx: &mut Context, id: u8, screen_pos: (f32, f32), selection_pos: (usize, usize)) -> Enemy {
-  Enemy::new(
-    ctx,
-    id,
-    "/enemies/test-square.png".to_owned(),
-    screen_pos,
-    selection_pos,
-    1.,
-    "Square".to_owned(),
-    2,
-    12,
-    6,
-    4,
-    2,
-    2,
-    2,
-    3,
-    8,
-    0,
-    0,
-    false,
-    Some(InventoryItem::new(ItemVariant::Potion)),
-    Some(InventoryItem::new(ItemVariant::Ether)),
-    true,
-    turn_action
-  )
+fn get_rules(definition: &EnemyDefinition, rules: &[BehaviourRule]) -> Vec<Rule> {
+  let attack = ActionParameters::new(DamageType::Physical, definition.attack, 0., false, Vec::new(), Vec::new());
+  rules.iter().map(|rule| Rule {
+    name: rule.ability.to_owned(),
+    action: match rule.ability.as_str() {
+      "Attack" => Some(attack.clone()),
+      _ => definition.abilities.iter().find(|enemy_ability| enemy_ability.ability.name == rule.ability).and_then(|enemy_ability| {
+        let mut action_parameters = characters::get_action_parameters(&enemy_ability.ability)?;
+        action_parameters.mp_cost = enemy_ability.mp_cost;
+        Some(action_parameters)
+      })
+    },
+    condition: rule.condition.as_ref().and_then(|name| Condition::from_name(name, rule.value, rule.effect.as_deref())),
+    weight: rule.weight,
+    target: rule.target.as_ref().and_then(|name| TargetPreference::from_name(name)).unwrap_or(TargetPreference::Random)
+  }).collect()
+}
+
+pub fn get_affinities(definition: &EnemyDefinition) -> Vec<Affinity> {
+  definition.affinities.iter()
+    .filter_map(|(name, multiplier)| Element::from_name(name).map(|element| (element, *multiplier)))
+    .collect()
 }
\ No newline at end of file
@@ -1,25 +1,84 @@
-use ggez::Context;
-use rand::{Rng, thread_rng};
+use ggez::{Context, GameResult, GameError};
+use rand::Rng;
+use crate::battle::encounter::Encounter;
 use crate::battle::enemy::Enemy;
-use crate::data::enemies;
+use crate::battle::state::BattleState;
+use crate::party::Party;
+use crate::data::{content, enemies};
+use crate::random::GameRng;
 
-pub fn test_room(ctx: &mut Context) -> Vec<Vec<Enemy>> {
-  let rng = thread_rng().gen::<f32>();
-  if rng < 0.9 {
-    vec![
-      vec![enemies::test_triangle(ctx, 5, (0., 0.), (0, 0)), enemies::test_triangle(ctx, 6, (0., 2.), (0, 1))]
-      // vec![enemies::test_circle(ctx, 6, (2., 0.), (1, 0)), enemies::test_square(ctx, 7, (2., 1.), (1, 1))]
-    ]
-  } else if rng < 0.666666666666667 {
-    vec![
-      vec![enemies::test_circle(ctx, 5, (0., 0.), (0, 0)), enemies::test_square(ctx, 6, (0., 1.), (0, 1))],
-      vec![enemies::test_triangle(ctx, 7, (1., 0.), (1, 0))]
-    ]
-  } else {
-    vec![
-      vec![enemies::test_circle(ctx, 5 , (0., 0.), (0, 0)), enemies::test_square(ctx, 6 , (0., 1.), (0, 1)), enemies::test_circle(ctx, 7 , (0., 2.), (0, 2))],
-      vec![enemies::test_square(ctx, 8 , (1., 0.), (1, 0)), enemies::test_circle(ctx, 9 , (1., 1.), (1, 1)), enemies::test_square(ctx, 10, (1., 2.), (1, 2))],
-      vec![enemies::test_circle(ctx, 11, (2., 0.), (2, 0)), enemies::test_square(ctx, 12, (2., 1.), (2, 1)), enemies::test_circle(ctx, 13, (2., 2.), (2, 2))]
-    ]
+// Weighted random pick among the formations of a table
+pub fn pick_formation<'a>(rng: &mut GameRng, formations: &'a [content::FormationDefinition], table: &str) -> Option<&'a content::FormationDefinition> {
+  let table_formations: Vec<&content::FormationDefinition> = formations.iter().filter(|formation| formation.table == table).collect();
+  let total_weight: f32 = table_formations.iter().map(|formation| formation.weight).sum();
+  let mut roll = rng.gen::<f32>() * total_weight;
+  match table_formations.iter().find(|formation| {roll -= formation.weight; roll < 0.}) {
+    Some(formation) => Some(formation),
+    None => table_formations.last().copied()
   }
-}
\ No newline at end of file
+}
+
+pub fn get_formation(content: &content::BattleContent, rng: &mut GameRng, table: &str) -> GameResult<content::FormationDefinition> {
+  match pick_formation(rng, &content.formations, table) {
+    Some(formation) => Ok(formation.clone()),
+    None => Err(GameError::ResourceLoadError(format!("{}: formation table {} has no formations", content::FORMATIONS_FILE, table)))
+  }
+}
+
+// How a random encounter starts for the party members still standing
+pub fn roll_encounter(content: &content::BattleContent, party: &mut Party, formation: &content::FormationDefinition) -> Encounter {
+  let members: Vec<&BattleState> = [&party.first, &party.second, &party.third, &party.fourth].iter()
+    .filter(|character| !character.name.is_empty() && character.state.hp > 0)
+    .map(|character| &character.state)
+    .collect();
+  Encounter::roll_for_party(&mut party.rng, &content.combat, &members, formation.columns.iter().flatten().count())
+}
+
+pub fn get_named_formation(content: &content::BattleContent, name: &str) -> GameResult<content::FormationDefinition> {
+  match content.formations.iter().find(|formation| formation.name == name) {
+    Some(formation) => Ok(formation.clone()),
+    None => Err(GameError::ResourceLoadError(format!("{}: there is no formation named {}", content::FORMATIONS_FILE, name)))
+  }
+}
+
+// The formation's columns with their index counted from the party on their side, and whether that side is the left.
+// A pincer puts every other column on the left, or the back half of a formation with a single column.
+pub fn arrange_columns(formation: &content::FormationDefinition, encounter: Encounter) -> Vec<(Vec<String>, usize, bool)> {
+  let mut columns: Vec<Vec<String>> = formation.columns.iter().filter(|column| !column.is_empty()).cloned().collect();
+  match encounter {
+    Encounter::Pincer if columns.len() == 1 => {
+      let mut right = columns.remove(0);
+      let left = right.split_off(right.len().div_ceil(2));
+      vec![(right, 0, false), (left, 0, true)]
+    },
+    Encounter::Pincer => columns.into_iter().enumerate().map(|(i, column)| (column, i / 2, i % 2 == 1)).collect(),
+    _ => columns.into_iter().enumerate().map(|(i, column)| (column, i, encounter == Encounter::BackAttack)).collect()
+  }
+}
+
+// Fresh enemies of the formation, each battle and retry builds its own
+pub fn build_formation(ctx: &mut Context, content: &content::BattleContent, formation: &content::FormationDefinition, encounter: Encounter) -> GameResult<Vec<Vec<Enemy>>> {
+  let mut id = 5;
+  let mut enemy_columns = Vec::new();
+  for (column, side_column, mirrored) in arrange_columns(formation, encounter) {
+    let mut enemy_column = Vec::new();
+    let mut column_height = 0.;
+    for enemy_name in column.iter() {
+      let definition = match content.enemies.iter().find(|definition| &definition.name == enemy_name) {
+        Some(definition) => definition,
+        None => return Err(GameError::ResourceLoadError(format!("{}: formation uses unknown enemy {}", content::FORMATIONS_FILE, enemy_name)))
+      };
+      let mut enemy = enemies::build_enemy(ctx, id, definition, (side_column as f32, column_height), (enemy_columns.len(), enemy_column.len()));
+      enemy.state.back_row = enemies::is_back_row(definition, side_column);
+      enemy.mirrored = mirrored;
+      enemy_column.push(enemy);
+      column_height += definition.size;
+      id += 1;
+    }
+    enemy_columns.push(enemy_column);
+  }
+  if enemy_columns.is_empty() {
+    return Err(GameError::ResourceLoadError(format!("{}: formation {} has no enemies", content::FORMATIONS_FILE, formation.name)));
+  }
+  Ok(enemy_columns)
+}
@@ -0,0 +1,56 @@
+use crate::battle::element::Element;
+use crate::battle::status_effect::StatusEffect;
+use crate::data::content::{EquipmentDefinition, GameContent};
+use crate::party::{Party, InventoryElement};
+use crate::party::equipment::{Equipment, EquipmentBonus, Slot};
+
+// Which page of the Equip menu a menu shows, by party member id
+#[derive(Clone, Copy)]
+pub enum EquipScreen {
+  Characters,
+  Slots(u8),
+  Candidates(u8, Slot),
+  Compare(u8, Slot, Option<usize>) // Inventory index of the candidate, None takes the slot's equipment off
+}
+
+// Inventory indices of the spare equipment that fits the slot, the rows of the candidate list
+pub fn get_candidates(party: &Party, slot: Slot) -> Vec<usize> {
+  party.inventory.iter().enumerate()
+    .filter(|(_, inventory_element)| match inventory_element {
+      InventoryElement::Equipment(equipment, amount) => equipment.slot == slot && *amount > 0,
+      _ => false
+    })
+    .map(|(index, _)| index)
+    .collect()
+}
+
+// Party members with an empty name are free spots and get no row
+pub fn get_member_row(party: &Party, id: u8) -> usize {
+  (1..id).filter(|member_id| !party.get_member(*member_id).name.is_empty()).count()
+}
+
+pub fn build_equipment(definition: &EquipmentDefinition) -> Option<Equipment> {
+  Some(Equipment {
+    name: definition.name.to_owned(),
+    slot: Slot::from_name(&definition.slot)?,
+    price: definition.price,
+    bonus: EquipmentBonus {
+      attack: definition.attack,
+      defence: definition.defence,
+      magic: definition.magic,
+      resistance: definition.resistance,
+      agility: definition.agility,
+      affinities: definition.affinities.iter()
+        .filter_map(|(name, multiplier)| Element::from_name(name).map(|element| (element, *multiplier)))
+        .collect(),
+      immunities: definition.immunities.iter().filter_map(|name| StatusEffect::from_name(name)).collect(),
+      spells: definition.spells.clone(),
+      preemptive: definition.preemptive,
+      alert: definition.alert
+    }
+  })
+}
+
+pub fn get_equipment(content: &GameContent, name: &str) -> Option<Equipment> {
+  content.equipment.iter().find(|definition| definition.name == name).and_then(build_equipment)
+}
\ No newline at end of file
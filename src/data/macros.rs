@@ -2,10 +2,6 @@ macro_rules! text {($ctx:expr, $text:expr, $position_x:expr, $position_y:expr, $
   MenuItem::new($ctx, String::new(), $text.to_owned(), ($position_x, $position_y), 24., $onclick)
 };}
 
-macro_rules! tiles {($([$(($spritesheet_x:expr, $spritesheet_y:expr, $entity:expr)$(,)?)*])*) => {
-  vec![$(vec![$(Tile::new(($spritesheet_x, $spritesheet_y), $entity)),*]),*]
-}}
-
 macro_rules! battle_target_positions {($ctx:expr, $party:expr, $enemies:expr, $action_parameters:expr) => {{
   fn push_character(
     ctx: &mut Context,
@@ -15,11 +11,12 @@ macro_rules! battle_target_positions {($ctx:expr, $party:expr, $enemies:expr, $a
     action_parameters: &ActionParameters
   ) -> () {
     if character.name.len() > 0 && character.state.hp != 0 {
+      let (x, y) = character.get_screen_pos();
       party_column.push(MenuItem::new(
           ctx,
           String::new(),
           " ".to_owned(),
-          (210. + character.x_offset, 65. + character.state.id as f32 * 66.),
+          (x + 10., y + 15.),
           32.,
           OnClickEvent::ActOnTarget(position, action_parameters.clone())
       ));
@@ -46,11 +43,12 @@ macro_rules! battle_target_positions {($ctx:expr, $party:expr, $enemies:expr, $a
     let mut column_vec = Vec::new();
     for enemy in column {
       if enemy.state.hp != 0 {
+        let (x, y) = enemy.get_screen_pos(184. - max_enemy_column_length * 16.);
         column_vec.push(MenuItem::new(
           $ctx,
           String::new(),
           " ".to_owned(),
-          (710. + enemy.x_offset + enemy.screen_pos.0 * 70., 184. - max_enemy_column_length * 16. + enemy.screen_pos.1 * 66.),
+          (x + 10., y),
           enemy.size * 32.,
           OnClickEvent::ActOnTarget((enemy.selection_pos.0 + 1, enemy.selection_pos.1), $action_parameters.clone())
         ));
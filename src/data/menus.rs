@@ -3,18 +3,28 @@ use crate::GameMode;
 use crate::menu::{MenuScreen, MenuMovement, MenuMutation};
 use crate::menu::item::{MenuItem, OnClickEvent};
 use crate::menu::container::MenuContainer;
-use crate::party::{Party, InventoryElement};
+use crate::party::{Party, InventoryElement, ITEM_STACK_LIMIT};
 use crate::party::character::Character;
+use crate::party::equipment::{EquipmentBonus, SLOTS};
 use crate::battle::enemy::Enemy;
+use crate::battle::Rewards;
+use crate::battle::status_effect::Stat;
 use crate::battle::action::ActionParameters;
+use crate::save;
+use crate::save::settings::{self, Setting};
+use crate::data::{characters, equipment};
+use crate::data::equipment::EquipScreen;
+use crate::data::shops::{self, Shop, ShopScreen};
+use std::rc::Rc;
 
 pub fn none_menu(ctx: &mut Context) -> MenuScreen {
   MenuScreen::new(ctx, false, Vec::new(), vec![Vec::new()], Vec::new(), (0, 0), MenuMovement::Grid, OnClickEvent::None)
 }
 
 pub fn main_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
-  let submenu_selection = MenuContainer::new(ctx, 10. , 10., 250., 300.);
+  let submenu_selection = MenuContainer::new(ctx, 10. , 10., 250., 380.);
   let character_info    = MenuContainer::new(ctx, 275., 10., 795., 700.);
+  let gold_container    = MenuContainer::new(ctx, 10. , 400., 250., 100.);
   fn to_item_menu(ctx: &mut Context, mode: &mut GameMode, party: &mut Party, enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
     item_menu(ctx, mode, party, enemies, cursor_start)
   }
@@ -23,9 +33,11 @@ pub fn main_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _en
   }
   let item    = text!(ctx, "Item"   , 55., 60. , OnClickEvent::MenuTransition(to_item_menu, (0, 0)));
   let ability = text!(ctx, "Ability", 55., 100., OnClickEvent::None);
-  let equip   = text!(ctx, "Equip"  , 55., 140., OnClickEvent::None);
+  let equip   = text!(ctx, "Equip"  , 55., 140., OnClickEvent::ToEquipScreen(EquipScreen::Characters, (0, 0)));
   let row     = text!(ctx, "Row"    , 55., 180., OnClickEvent::ToMenuScreen(to_row_menu, (0, 0)));
-  let config  = text!(ctx, "Config" , 55., 220., OnClickEvent::None);
+  let config  = text!(ctx, "Config" , 55., 220., OnClickEvent::ToMenuScreen(config_menu, (0, 0)));
+  let save    = text!(ctx, "Save"   , 55., 260., OnClickEvent::ToMenuScreen(save_menu, (0, 0)));
+  let load    = text!(ctx, "Load"   , 55., 300., OnClickEvent::ToMenuScreen(load_menu, (0, 0)));
   let mut unselectable_items = Vec::new();
   fn push_party_memeber_to_unselectables(ctx: &mut Context, vector: &mut Vec<MenuItem>, character: &Character) {
     if character.name.len() > 0 {
@@ -72,12 +84,13 @@ pub fn main_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _en
   push_party_memeber_to_unselectables(ctx, &mut unselectable_items, &party.second);
   push_party_memeber_to_unselectables(ctx, &mut unselectable_items, &party.third);
   push_party_memeber_to_unselectables(ctx, &mut unselectable_items, &party.fourth);
+  unselectable_items.push(text!(ctx, format!("{}G", party.gold), 55., 440., OnClickEvent::None));
   MenuScreen::new(
     ctx,
     true,
-    vec![submenu_selection, character_info],
+    vec![submenu_selection, character_info, gold_container],
     vec![
-      vec![item, ability, equip, row, config]
+      vec![item, ability, equip, row, config, save, load]
     ],
     unselectable_items,
     cursor_start,
@@ -88,12 +101,8 @@ pub fn main_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _en
 
 pub fn item_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, mut cursor_start: (usize, usize)) -> MenuScreen {
   party.inventory.sort_by(|a, b| { // Sort items with amount 0 to the end of array.
-    let a_amount = match a {
-      InventoryElement::Item(_, amount) => match *amount {0 => 0, _ => 1}
-    };
-    let b_amount = match b {
-      InventoryElement::Item(_, amount) => match *amount {0 => 0, _ => 1}
-    };
+    let a_amount = match a.get_amount() {0 => 0, _ => 1};
+    let b_amount = match b.get_amount() {0 => 0, _ => 1};
     return b_amount.cmp(&a_amount);
   });
   let container = MenuContainer::new(ctx, 10. , 10., 1060., 700.);
@@ -102,7 +111,8 @@ pub fn item_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _en
   let mut unselectable_items = Vec::new();
   for (index, element) in party.inventory.iter_mut().enumerate() {
     let (element_name, element_amount, click_event) = match element {
-      InventoryElement::Item(item, amount) => (item.get_name(), amount, item.get_to_menu_click_event((index % 2, index / 2)))
+      InventoryElement::Item(item, amount) => (item.get_name(), amount, item.get_to_menu_click_event((index % 2, index / 2))),
+      InventoryElement::Equipment(equipment, amount) => (equipment.name.to_owned(), amount, OnClickEvent::None) // Worn from the Equip menu
     };
     let item_height = (index / 2) as f32 * 24. + 50.;
     if *element_amount > 0 {
@@ -140,7 +150,7 @@ pub fn item_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _en
 }
 
 pub fn row_change_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
-  let submenu_selection = MenuContainer::new(ctx, 10. , 10., 250., 300.);
+  let submenu_selection = MenuContainer::new(ctx, 10. , 10., 250., 380.);
   let character_info    = MenuContainer::new(ctx, 275., 10., 795., 700.);
   fn to_main_menu(ctx: &mut Context, mode: &mut GameMode, party: &mut Party, enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
     main_menu(ctx, mode, party, enemies, cursor_start)
@@ -152,6 +162,8 @@ pub fn row_change_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Part
   unselectable_items.push(text!(ctx, "Equip"  , 55., 140., OnClickEvent::None));
   unselectable_items.push(text!(ctx, "Row"    , 55., 180., OnClickEvent::None));
   unselectable_items.push(text!(ctx, "Config" , 55., 220., OnClickEvent::None));
+  unselectable_items.push(text!(ctx, "Save"   , 55., 260., OnClickEvent::None));
+  unselectable_items.push(text!(ctx, "Load"   , 55., 300., OnClickEvent::None));
   fn push_party_memeber_to_menu_items(ctx: &mut Context, selectables: &mut Vec<MenuItem>, unselectables: &mut Vec<MenuItem>, character: &Character) {
     fn toggle_row(menu: &mut MenuScreen, party: &mut Party) -> GameResult<()> {
       let mut characters_in_party = Vec::new();
@@ -171,14 +183,10 @@ pub fn row_change_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Part
         2 => &mut party.third,
         _ => &mut party.fourth
       };
-      if selected_character.state.back_row {
-        selected_character.state.back_row = false;
-        selected_character.x_offset += 50.;
-        menu.selectable_items[0][selected_index].screen_pos.0 += 50.;
-      } else {
-        selected_character.state.back_row = true;
-        selected_character.x_offset -= 50.;
-        menu.selectable_items[0][selected_index].screen_pos.0 -= 50.;
+      selected_character.change_row();
+      match selected_character.state.back_row {
+        true  => menu.selectable_items[0][selected_index].screen_pos.0 -= 50.,
+        false => menu.selectable_items[0][selected_index].screen_pos.0 += 50.
       }
       menu.mutation = MenuMutation::None;
       Ok(())
@@ -246,6 +254,313 @@ pub fn row_change_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Part
   )
 }
 
+pub fn config_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  let title_container   = MenuContainer::new(ctx, 10. , 10., 250., 100.);
+  let setting_container = MenuContainer::new(ctx, 275., 10., 795., 180.);
+  let selectable_items = vec![
+    text!(ctx, "ATB mode"    , 360., 60. , OnClickEvent::ChangeSetting(Setting::AtbMode)),
+    text!(ctx, "Battle speed", 360., 120., OnClickEvent::ChangeSetting(Setting::BattleSpeed))
+  ];
+  let unselectable_items = vec![
+    text!(ctx, "Config", 55., 50., OnClickEvent::None),
+    text!(ctx, party.settings.atb_mode.get_name(), 750., 60., OnClickEvent::None),
+    text!(ctx, format!("{}/{}", party.settings.battle_speed, settings::BATTLE_SPEEDS), 750., 120., OnClickEvent::None)
+  ];
+  MenuScreen::new(
+    ctx,
+    true,
+    vec![title_container, setting_container],
+    vec![selectable_items],
+    unselectable_items,
+    cursor_start,
+    MenuMovement::Grid,
+    OnClickEvent::ToMenuScreen(main_menu, (0, 4))
+  )
+}
+
+fn save_slot_menu(ctx: &mut Context, saving: bool, cursor_start: (usize, usize), return_action: OnClickEvent) -> MenuScreen {
+  let title_container = MenuContainer::new(ctx, 10. , 10., 250., 100.);
+  let slot_container  = MenuContainer::new(ctx, 275., 10., 795., 100. + save::SAVE_SLOTS as f32 * 80.);
+  let mut selectable_items = Vec::new();
+  let mut unselectable_items = vec![text!(ctx, match saving {true => "Save", false => "Load"}, 55., 50., OnClickEvent::None)];
+  for slot in 1..=save::SAVE_SLOTS {
+    let slot_height = 60. + (slot - 1) as f32 * 80.;
+    let (description, loadable) = match save::read_save(ctx, slot) {
+      Ok(Some(save_file)) => (save_file.get_description(), true),
+      Ok(None) => ("Empty".to_owned(), false),
+      Err(_) => ("Unreadable".to_owned(), false)
+    };
+    let click_event = match (saving, loadable) {
+      (true, _)     => OnClickEvent::SaveGame(slot),
+      (false, true) => OnClickEvent::LoadGame(slot),
+      _ => OnClickEvent::None
+    };
+    selectable_items.push(text!(ctx, format!("Slot {}", slot), 360., slot_height, click_event));
+    unselectable_items.push(text!(ctx, description, 600., slot_height, OnClickEvent::None));
+  }
+  MenuScreen::new(
+    ctx,
+    true,
+    vec![title_container, slot_container],
+    vec![selectable_items],
+    unselectable_items,
+    cursor_start,
+    MenuMovement::Grid,
+    return_action
+  )
+}
+
+pub fn save_menu(ctx: &mut Context, _mode: &mut GameMode, _party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  save_slot_menu(ctx, true, cursor_start, OnClickEvent::ToMenuScreen(main_menu, (0, 5)))
+}
+
+pub fn load_menu(ctx: &mut Context, _mode: &mut GameMode, _party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  save_slot_menu(ctx, false, cursor_start, OnClickEvent::ToMenuScreen(main_menu, (0, 6)))
+}
+
+fn title_load_menu(ctx: &mut Context, _mode: &mut GameMode, _party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  save_slot_menu(ctx, false, cursor_start, OnClickEvent::ToMenuScreen(title_menu, (0, 1)))
+}
+
+pub fn title_menu(ctx: &mut Context, _mode: &mut GameMode, _party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  let container = MenuContainer::new(ctx, 140., 200., 800., 260.);
+  let selectable_items = vec![
+    text!(ctx, "New game" , 440., 320., OnClickEvent::NewGame),
+    text!(ctx, "Load game", 440., 370., OnClickEvent::ToMenuScreen(title_load_menu, (0, 0)))
+  ];
+  let title = text!(ctx, "THE ULTIMATE GAMING EXPERIENCE", 180., 240., OnClickEvent::None);
+  MenuScreen::new(ctx, true, vec![container], vec![selectable_items], vec![title], cursor_start, MenuMovement::Grid, OnClickEvent::None)
+}
+
+const SHOP_VISIBLE_ROWS: usize = 14;
+
+fn shop_row(name: &str, price: u32, owned: u8) -> String {
+  format!("{:<16}{:>6}{:>6}", name, price, owned)
+}
+
+// Buy, Sell and Exit on the left, the list of the page and the party's gold on the right
+pub fn shop_menu(ctx: &mut Context, party: &Party, shop: Rc<Shop>, screen: ShopScreen, cursor_start: (usize, usize)) -> MenuScreen {
+  let page_container = MenuContainer::new(ctx, 10. , 10. , 250., 180.);
+  let gold_container = MenuContainer::new(ctx, 10. , 610., 250., 100.);
+  let list_container = MenuContainer::new(ctx, 275., 10. , 795., 700.);
+  let header = format!("{:<16}{:>6}{:>6}", "Item", "Price", "Owned");
+  let mut unselectable_items = vec![text!(ctx, format!("{}G", party.gold), 55., 650., OnClickEvent::None)];
+  let mut list = Vec::new();
+  let mut visible_rows = SHOP_VISIBLE_ROWS;
+  let return_action = match screen {
+    ShopScreen::Main => {
+      unselectable_items.push(text!(ctx, header, 320., 50., OnClickEvent::None));
+      for (index, (item, price)) in shop.stock.iter().enumerate().take(SHOP_VISIBLE_ROWS) {
+        let row = shop_row(&item.get_name(), *price, party.get_item_amount(&item.get_name()));
+        unselectable_items.push(text!(ctx, row, 320., 100. + index as f32 * 40., OnClickEvent::None));
+      }
+      list = vec![
+        text!(ctx, "Buy" , 55., 60. , OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Buy, (0, 0))),
+        text!(ctx, "Sell", 55., 100., OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Sell, (0, 0))),
+        text!(ctx, "Exit", 55., 140., OnClickEvent::Transition(GameMode::Map))
+      ];
+      OnClickEvent::Transition(GameMode::Map)
+    },
+    ShopScreen::Buy => {
+      unselectable_items.push(text!(ctx, "Buy", 55., 60., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, header, 320., 50., OnClickEvent::None));
+      for (index, (item, price)) in shop.stock.iter().enumerate() {
+        let owned = party.get_item_amount(&item.get_name());
+        let click_event = match get_max_purchase(party.gold, *price, owned) {
+          0 => OnClickEvent::None,
+          _ => OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::BuyAmount(index), (0, 0))
+        };
+        list.push(text!(ctx, shop_row(&item.get_name(), *price, owned), 320., 100. + index as f32 * 40., click_event));
+      }
+      OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Main, (0, 0))
+    },
+    ShopScreen::Sell => {
+      unselectable_items.push(text!(ctx, "Sell", 55., 60., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, header, 320., 50., OnClickEvent::None));
+      for (row, index) in shops::get_sellable(party).into_iter().enumerate() {
+        let inventory_element = &party.inventory[index];
+        let click_event = match inventory_element.get_sell_price() {
+          0 => OnClickEvent::None,
+          _ => OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::SellAmount(index), (0, 0))
+        };
+        let row_text = shop_row(&inventory_element.get_name(), inventory_element.get_sell_price(), inventory_element.get_amount());
+        list.push(text!(ctx, row_text, 320., 100. + row as f32 * 40., click_event));
+      }
+      if list.is_empty() {
+        list.push(text!(ctx, "Nothing to sell", 320., 100., OnClickEvent::None));
+      }
+      OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Main, (0, 1))
+    },
+    ShopScreen::BuyAmount(stock_index) => {
+      let (item, price) = &shop.stock[stock_index];
+      let owned = party.get_item_amount(&item.get_name());
+      unselectable_items.push(text!(ctx, "Buy", 55., 60., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, item.get_name(), 320., 50., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, format!("Owned {}", owned), 800., 50., OnClickEvent::None));
+      for amount in 1..=get_max_purchase(party.gold, *price, owned) {
+        let row = format!("x{:<3}{:>10}G", amount, *price * amount as u32);
+        list.push(text!(ctx, row, 320., 110. + (amount - 1) as f32 * 40., OnClickEvent::BuyItem(shop.clone(), stock_index, amount)));
+      }
+      visible_rows = SHOP_VISIBLE_ROWS - 1;
+      OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Buy, (0, stock_index))
+    },
+    ShopScreen::SellAmount(inventory_index) => {
+      let inventory_element = &party.inventory[inventory_index];
+      let owned = inventory_element.get_amount();
+      unselectable_items.push(text!(ctx, "Sell", 55., 60., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, inventory_element.get_name(), 320., 50., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, format!("Owned {}", owned), 800., 50., OnClickEvent::None));
+      for amount in 1..=owned {
+        let row = format!("x{:<3}{:>10}G", amount, inventory_element.get_sell_price() * amount as u32);
+        list.push(text!(ctx, row, 320., 110. + (amount - 1) as f32 * 40., OnClickEvent::SellItem(shop.clone(), inventory_index, amount)));
+      }
+      visible_rows = SHOP_VISIBLE_ROWS - 1;
+      let row = shops::get_sellable(party).iter().position(|index| *index == inventory_index).unwrap_or(0);
+      OnClickEvent::ToShopScreen(shop.clone(), ShopScreen::Sell, (0, row))
+    }
+  };
+  let cursor_start = (0, cursor_start.1.min(list.len().saturating_sub(1)));
+  let mut menu = MenuScreen::new(
+    ctx,
+    true,
+    vec![page_container, gold_container, list_container],
+    vec![list],
+    unselectable_items,
+    cursor_start,
+    MenuMovement::Grid,
+    return_action
+  );
+  menu.set_visible_rows(visible_rows);
+  menu
+}
+
+// As many as the party can pay for and carry
+fn get_max_purchase(gold: u32, price: u32, owned: u8) -> u8 {
+  let affordable = match price {
+    0 => u32::MAX,
+    _ => gold / price
+  };
+  affordable.min((ITEM_STACK_LIMIT - owned.min(ITEM_STACK_LIMIT)) as u32) as u8
+}
+
+const EQUIP_STATS: [(Stat, &str); 5] = [
+  (Stat::Attack, "Attack"), (Stat::Defence, "Defence"), (Stat::Magic, "Magic"), (Stat::Resistance, "Resistance"), (Stat::Agility, "Agility")
+];
+
+// The party with what everyone wears, then one member's stats next to their slots, the spare equipment that fits a slot
+// and the stats before and after putting a piece on
+pub fn equip_menu(ctx: &mut Context, party: &Party, screen: EquipScreen, cursor_start: (usize, usize)) -> MenuScreen {
+  let title_container = MenuContainer::new(ctx, 10. , 10. , 1060., 100.);
+  let mut containers = vec![title_container];
+  let mut unselectable_items = Vec::new();
+  let mut list = Vec::new();
+  let return_action = match screen {
+    EquipScreen::Characters => {
+      containers.push(MenuContainer::new(ctx, 10., 120., 1060., 590.));
+      unselectable_items.push(text!(ctx, "Equip", 55., 50., OnClickEvent::None));
+      let members = (1..=4).map(|id| party.get_member(id)).filter(|character| !character.name.is_empty());
+      for (row, character) in members.enumerate() {
+        let y = 140. + row as f32 * 142.;
+        let click_event = OnClickEvent::ToEquipScreen(EquipScreen::Slots(character.state.id), (0, 0));
+        list.push(MenuItem::new(ctx, character.avatar_spritefile.to_owned(), "".to_owned(), (55., y), 128., click_event));
+        unselectable_items.push(text!(ctx, character.name, 215., y + 10., OnClickEvent::None));
+        for slot in SLOTS.iter() {
+          let worn = character.equipped.get(*slot).map_or("-", |equipment| &equipment.name);
+          let row_text = format!("{:<10}{}", slot.get_name(), worn);
+          unselectable_items.push(text!(ctx, row_text, 480., y + 10. + *slot as usize as f32 * 40., OnClickEvent::None));
+        }
+      }
+      OnClickEvent::ToMenuScreen(main_menu, (0, 2))
+    },
+    EquipScreen::Slots(id) => {
+      let character = party.get_member(id);
+      containers.push(MenuContainer::new(ctx, 10. , 120., 520., 590.));
+      containers.push(MenuContainer::new(ctx, 545., 120., 525., 590.));
+      unselectable_items.push(text!(ctx, character.name, 55., 50., OnClickEvent::None));
+      push_equip_stats(ctx, &mut unselectable_items, character, None);
+      for slot in SLOTS.iter() {
+        let y = 160. + *slot as usize as f32 * 80.;
+        let worn = character.equipped.get(*slot).map_or("-", |equipment| &equipment.name);
+        unselectable_items.push(text!(ctx, slot.get_name(), 590., y, OnClickEvent::None));
+        list.push(text!(ctx, worn, 614., y + 40., OnClickEvent::ToEquipScreen(EquipScreen::Candidates(id, *slot), (0, 0))));
+      }
+      OnClickEvent::ToEquipScreen(EquipScreen::Characters, (0, equipment::get_member_row(party, id)))
+    },
+    EquipScreen::Candidates(id, slot) => {
+      let character = party.get_member(id);
+      containers.push(MenuContainer::new(ctx, 10. , 120., 520., 590.));
+      containers.push(MenuContainer::new(ctx, 545., 120., 525., 590.));
+      unselectable_items.push(text!(ctx, format!("{} - {}", character.name, slot.get_name()), 55., 50., OnClickEvent::None));
+      push_equip_stats(ctx, &mut unselectable_items, character, None);
+      for (row, index) in equipment::get_candidates(party, slot).into_iter().enumerate() {
+        let row_text = format!("{:<15}x{:>2}", party.inventory[index].get_name(), party.inventory[index].get_amount());
+        list.push(text!(ctx, row_text, 590., 160. + row as f32 * 40., OnClickEvent::ToEquipScreen(EquipScreen::Compare(id, slot, Some(index)), (0, 0))));
+      }
+      if character.equipped.get(slot).is_some() {
+        let y = 160. + list.len() as f32 * 40.;
+        list.push(text!(ctx, "Remove", 590., y, OnClickEvent::ToEquipScreen(EquipScreen::Compare(id, slot, None), (0, 0))));
+      }
+      if list.is_empty() {
+        list.push(text!(ctx, "Nothing to equip", 590., 160., OnClickEvent::None));
+      }
+      OnClickEvent::ToEquipScreen(EquipScreen::Slots(id), (0, slot as usize))
+    },
+    EquipScreen::Compare(id, slot, inventory_index) => {
+      let character = party.get_member(id);
+      containers.push(MenuContainer::new(ctx, 10. , 120., 520., 590.));
+      containers.push(MenuContainer::new(ctx, 545., 120., 525., 590.));
+      let candidate = match inventory_index.map(|index| &party.inventory[index]) {
+        Some(InventoryElement::Equipment(equipment, _)) => Some(equipment.clone()),
+        _ => None
+      };
+      let mut equipped = character.equipped.clone();
+      let title = match &candidate {
+        Some(equipment) => format!("{} - {}", character.name, equipment.name),
+        None => format!("{} - Remove {}", character.name, slot.get_name())
+      };
+      equipped.replace(slot, candidate);
+      unselectable_items.push(text!(ctx, title, 55., 50., OnClickEvent::None));
+      push_equip_stats(ctx, &mut unselectable_items, character, Some(&equipped.get_bonus()));
+      list.push(text!(ctx, "Equip" , 590., 160., OnClickEvent::Equip(id, slot, inventory_index)));
+      list.push(text!(ctx, "Cancel", 590., 200., OnClickEvent::ToEquipScreen(EquipScreen::Candidates(id, slot), (0, 0))));
+      let row = match inventory_index {
+        Some(index) => equipment::get_candidates(party, slot).iter().position(|candidate| *candidate == index).unwrap_or(0),
+        None => equipment::get_candidates(party, slot).len()
+      };
+      OnClickEvent::ToEquipScreen(EquipScreen::Candidates(id, slot), (0, row))
+    }
+  };
+  let cursor_start = (0, cursor_start.1.min(list.len().saturating_sub(1)));
+  MenuScreen::new(ctx, true, containers, vec![list], unselectable_items, cursor_start, MenuMovement::Grid, return_action)
+}
+
+// The stats with the equipment worn now, and with the other equipment after an arrow when comparing
+fn push_equip_stats(ctx: &mut Context, unselectables: &mut Vec<MenuItem>, character: &Character, compared: Option<&EquipmentBonus>) {
+  for (row, (stat, name)) in EQUIP_STATS.iter().enumerate() {
+    let row_text = match compared {
+      Some(bonus) => format!("{:<11}{:>3} >{:>3}", name, character.state.get_equipped(*stat), bonus.apply(*stat, character.state.get_base(*stat))),
+      None => format!("{:<11}{:>3}", name, character.state.get_equipped(*stat))
+    };
+    unselectables.push(text!(ctx, row_text, 55., 160. + row as f32 * 40., OnClickEvent::None));
+  }
+}
+
+// Shown when the whole party falls in a battle it wasn't allowed to lose
+pub fn game_over(ctx: &mut Context) -> MenuScreen {
+  let container = MenuContainer::new(ctx, 290., 200., 500., 310.);
+  let last_save = match save::get_last_slot(ctx) {
+    Some(slot) => OnClickEvent::LoadGame(slot),
+    None => OnClickEvent::None
+  };
+  let selectable_items = vec![
+    text!(ctx, "Retry battle"  , 380., 320., OnClickEvent::RetryBattle),
+    text!(ctx, "Load last save", 380., 370., last_save),
+    text!(ctx, "Title"         , 380., 420., OnClickEvent::ToMenuScreen(title_menu, (0, 0)))
+  ];
+  let title = text!(ctx, "Game over", 430., 240., OnClickEvent::None);
+  MenuScreen::new(ctx, true, vec![container], vec![selectable_items], vec![title], (0, 0), MenuMovement::Grid, OnClickEvent::None)
+}
+
 pub fn to_target_selection(
   ctx: &mut Context,
   party: &mut Party,
@@ -256,24 +571,106 @@ pub fn to_target_selection(
   battle_target_selection(ctx, party, enemies, (1, 0), action_parameters, cursor_memory)
 }
 
+pub fn to_enemy_target_selection(
+  ctx: &mut Context,
+  party: &mut Party,
+  enemies: &Vec<Vec<Enemy>>,
+  action_parameters: &ActionParameters,
+  cursor_memory: (usize, usize)
+) -> MenuScreen {
+  let mut menu = battle_target_selection(ctx, party, enemies, (0, 0), action_parameters, cursor_memory);
+  menu.selectable_items.remove(0);
+  menu
+}
+
+pub fn to_ally_target_selection(
+  ctx: &mut Context,
+  party: &mut Party,
+  enemies: &Vec<Vec<Enemy>>,
+  action_parameters: &ActionParameters,
+  cursor_memory: (usize, usize)
+) -> MenuScreen {
+  let mut menu = battle_target_selection(ctx, party, enemies, (0, 0), action_parameters, cursor_memory);
+  menu.selectable_items.truncate(1);
+  menu
+}
+
+// Magic only shows up for characters that know spells
+fn battle_commands(ctx: &mut Context, character: &Character) -> Vec<MenuItem> {
+  let mut battle_commands = vec![character.get_attack_ability(ctx), character.get_primary_ability(ctx), character.get_secondary_ability(ctx)];
+  if !character.get_spells().is_empty() {
+    let on_click = match character.state.can_cast() {
+      true  => OnClickEvent::ToMenuScreen(battle_magic_menu, (0, 3)),
+      false => OnClickEvent::None
+    };
+    battle_commands.push(text!(ctx, "Magic", 55., 560., on_click));
+  }
+  let item_row = battle_commands.len();
+  battle_commands.push(text!(ctx, "Item"  , 55., 440. + item_row as f32 * 40., OnClickEvent::ToMenuScreen(battle_item_menu, (0, item_row))));
+  battle_commands.push(text!(ctx, "Defend", 55., 480. + item_row as f32 * 40., OnClickEvent::None));
+  battle_commands.push(text!(ctx, "Row"   , 55., 520. + item_row as f32 * 40., OnClickEvent::BattleAction(characters::row_action)));
+  battle_commands
+}
+
 pub fn battle_main(ctx: &mut Context, character: &mut Character, cursor_start: (usize, usize)) -> MenuScreen {
   let commands = MenuContainer::new(ctx, 10., 400., 280., 300.);
-  let attack_ability    = character.get_attack_ability(ctx);
-  let primary_ability   = character.get_primary_ability(ctx);
-  let secondary_ability = character.get_secondary_ability(ctx);
-  let item       = text!(ctx, "Item"  , 55., 560., OnClickEvent::ToMenuScreen(battle_item_menu, (0, 3)));
-  let defend     = text!(ctx, "Defend", 55., 600., OnClickEvent::None);
-  let row_change = text!(ctx, "Change", 55., 640., OnClickEvent::None);
-  MenuScreen::new(
+  let battle_commands = battle_commands(ctx, character);
+  let mut menu = MenuScreen::new(
     ctx,
     true,
     vec![commands],
-    vec![vec![attack_ability, primary_ability, secondary_ability, item, defend, row_change]],
+    vec![battle_commands],
     Vec::new(),
     cursor_start,
     MenuMovement::Grid,
     OnClickEvent::None
-  )
+  );
+  menu.set_visible_rows(6);
+  menu
+}
+
+pub fn battle_magic_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  fn not_enough_mp(menu: &mut MenuScreen, _party: &mut Party) -> GameResult<()> {
+    menu.unselectable_items[0].text = "Not enough MP".to_owned();
+    menu.mutation = MenuMutation::None;
+    Ok(())
+  }
+  let mp_container = MenuContainer::new(ctx, 10., 320., 360., 70.);
+  let commands     = MenuContainer::new(ctx, 10., 400., 360., 300.);
+  let content = party.content.clone();
+  let character = party.get_active();
+  let mut selectable_items = Vec::new();
+  for spell in character.get_spells().iter().filter_map(|name| content.spells.iter().find(|spell| &spell.ability.name == name)) {
+    let on_click = match (character.state.mp >= spell.mp_cost, characters::get_spell_parameters(spell)) {
+      (true, Some(action_parameters)) => {
+        let target_selection = match spell.target.as_str() {
+          "Enemy" => to_enemy_target_selection,
+          "Ally"  => to_ally_target_selection,
+          _ => to_target_selection
+        };
+        OnClickEvent::ToTargetSelection(target_selection, action_parameters, cursor_start)
+      },
+      _ => OnClickEvent::MutateMenu(not_enough_mp)
+    };
+    let row = selectable_items.len();
+    selectable_items.push(text!(ctx, format!("{:<9}{:>3}", spell.ability.name, spell.mp_cost), 55., 440. + row as f32 * 40., on_click));
+  }
+  if selectable_items.is_empty() {
+    selectable_items.push(MenuItem::new(ctx, "/empty.png".to_owned(), "".to_owned(), (0., 0.), 0., OnClickEvent::None));
+  }
+  let mp = text!(ctx, format!("MP {}/{}", character.state.mp, character.state.max_mp), 30., 343., OnClickEvent::None);
+  let mut menu = MenuScreen::new(
+    ctx,
+    true,
+    vec![mp_container, commands],
+    vec![selectable_items],
+    vec![mp],
+    (0, 0),
+    MenuMovement::Grid,
+    OnClickEvent::ToMenuScreen(to_battle_main, cursor_start)
+  );
+  menu.set_visible_rows(6);
+  menu
 }
 
 fn to_battle_main(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
@@ -293,7 +690,8 @@ pub fn battle_item_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Par
           unselectable_items.push(text!(ctx, format!("x{}", amount), 220., 440. + index as f32 * 40., OnClickEvent::None));
           index += 1;
         }
-      }
+      },
+      InventoryElement::Equipment(_, _) => ()
     };
   }
   if selectable_items.len() == 0 {
@@ -321,29 +719,25 @@ pub fn battle_target_selection(
 ) -> MenuScreen {
   let commands = MenuContainer::new(ctx, 10., 400., 280., 300.);
   let target_positions = battle_target_positions!(ctx, party, enemies, action_parameters);
-  let attack_ability    = party.get_active().get_attack_ability(ctx);
-  let primary_ability   = party.get_active().get_primary_ability(ctx);
-  let secondary_ability = party.get_active().get_secondary_ability(ctx);
-  let item       = text!(ctx, "Item"  , 55., 560., OnClickEvent::None);
-  let defend     = text!(ctx, "Defend", 55., 600., OnClickEvent::None);
-  let row_change = text!(ctx, "Change", 55., 640., OnClickEvent::None);
+  let mut battle_commands = battle_commands(ctx, party.get_active());
+  battle_commands.truncate(6);
   MenuScreen::new(
     ctx,
     true,
     vec![commands],
     target_positions,
-    vec![attack_ability, primary_ability, secondary_ability, item, defend, row_change],
+    battle_commands,
     cursor_pos,
     MenuMovement::Grid,
     OnClickEvent::ToMenuScreen(to_battle_main, cursor_memory)
   )
 }
 
-pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) -> MenuScreen {
+pub fn battle_won(ctx: &mut Context, party: &mut Party, rewards: &mut Rewards) -> MenuScreen {
   fn start_exp_count(menu: &mut MenuScreen, _party: &mut Party) -> GameResult<()> {
     fn count_experience(menu: &mut MenuScreen, party: &mut Party) -> GameResult<()> {
       fn finish_exp_count(menu: &mut MenuScreen, _party: &mut Party) -> GameResult<()> {
-        fn end_exp_cound(menu: &mut MenuScreen, _party: &mut Party) -> GameResult<()> {
+        fn end_exp_cound(menu: &mut MenuScreen, party: &mut Party) -> GameResult<()> {
           let exp_left = menu.unselectable_items[1].text.parse::<u32>().unwrap();
           if exp_left > 0 {
             menu.unselectable_items[1].text = format!("{}", 0);
@@ -354,7 +748,10 @@ pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) ->
           } else {
             menu.mutation = MenuMutation::None;
           }
-          menu.selectable_items[0][0].on_click = OnClickEvent::Transition(GameMode::Map);
+          menu.selectable_items[0][0].on_click = match party.can_level_up() {
+            true  => OnClickEvent::ToMenuScreen(level_up_menu, (0, 0)),
+            false => OnClickEvent::Transition(GameMode::Map)
+          };
           Ok(())
         }
         menu.mutation = MenuMutation::DefaultMutation(end_exp_cound);
@@ -384,7 +781,9 @@ pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) ->
     menu.mutation = MenuMutation::DefaultMutation(count_experience);
     Ok(())
   }
+  let experience = &mut rewards.experience;
   let experience_container   = MenuContainer::new(ctx, 50. , 10. , 500. , 100.);
+  let gold_container         = MenuContainer::new(ctx, 590., 10. , 470. , 100.);
   let character_container    = MenuContainer::new(ctx, 10. , 120., 1060., 240.);
   let items_string_container = MenuContainer::new(ctx, 50. , 370., 200. , 100.);
   let items_container        = MenuContainer::new(ctx, 10. , 480., 1060., 120.);
@@ -426,7 +825,19 @@ pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) ->
     true  => format!("{}", party.fourth.state.experience),
     false => " ".to_owned()
   }, 840., 260., OnClickEvent::None);
+  let gained_gold_string = text!(ctx, "Gold", 610., 50. , OnClickEvent::None);
+  let gained_gold = text!(ctx, format!("{}", rewards.gold), 850., 50. , OnClickEvent::None);
   let gained_items_string = text!(ctx, "Items", 90. , 410., OnClickEvent::None);
+  let mut gained_items = Vec::new();
+  for (index, (item, amount)) in rewards.items.iter().enumerate().take(6) { // Two columns of three fit the container
+    let item_x = (index % 2) as f32 * 540.;
+    let item_height = (index / 2) as f32 * 30. + 510.;
+    gained_items.push(text!(ctx, item.get_name(), 50. + item_x, item_height, OnClickEvent::None));
+    gained_items.push(text!(ctx, format!("{}", amount), 400. + item_x, item_height, OnClickEvent::None));
+  }
+  if gained_items.is_empty() {
+    gained_items.push(text!(ctx, "None", 50., 510., OnClickEvent::None));
+  }
   let continue_button = text!(ctx, "Continue", 805., 650., OnClickEvent::MutateMenu(start_exp_count));
   if party.first .name.len() > 0 {party.first .state.experience += *experience / party.get_alive_size();}
   if party.second.name.len() > 0 {party.second.state.experience += *experience / party.get_alive_size();}
@@ -435,7 +846,7 @@ pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) ->
   MenuScreen::new(
     ctx,
     true,
-    vec![experience_container, character_container, items_string_container, items_container, continue_container],
+    vec![experience_container, gold_container, character_container, items_string_container, items_container, continue_container],
     vec![vec![continue_button]],
     vec![
       gained_exp_string, gained_exp,
@@ -443,10 +854,40 @@ pub fn battle_won(ctx: &mut Context, party: &mut Party, experience: &mut u32) ->
       second_character_name, second_character_exp,
        third_character_name,  third_character_exp,
       fourth_character_name, fourth_character_exp,
-      gained_items_string
-    ],
+      gained_items_string, gained_gold_string, gained_gold
+    ].into_iter().chain(gained_items).collect(),
     (0, 0),
     MenuMovement::Grid,
     OnClickEvent::None
   )
+}
+
+// Applies the level ups earned in battle and lists the stats each character gained
+pub fn level_up_menu(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
+  let mut containers = vec![MenuContainer::new(ctx, 50., 10., 500., 100.)];
+  let mut unselectable_items = vec![text!(ctx, "Level up", 90., 50., OnClickEvent::None)];
+  for character in [&mut party.first, &mut party.second, &mut party.third, &mut party.fourth].iter_mut() {
+    if let Some(level_up) = character.level_up(ctx) {
+      let row_height = 120. + (containers.len() - 1) as f32 * 120.;
+      containers.push(MenuContainer::new(ctx, 10., row_height, 1060., 110.));
+      unselectable_items.push(text!(ctx, character.name, 50., row_height + 25., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, format!("Lvl {} > {}", level_up.from_level, level_up.to_level), 500., row_height + 25., OnClickEvent::None));
+      unselectable_items.push(text!(ctx, format!(
+        "Hp+{} Mp+{} Atk+{} Def+{} Mag+{} Res+{} Agi+{}",
+        level_up.hp, level_up.mp, level_up.attack, level_up.defence, level_up.magic, level_up.resistance, level_up.agility
+      ), 50., row_height + 65., OnClickEvent::None));
+    }
+  }
+  containers.push(MenuContainer::new(ctx, 760., 610., 280., 100.));
+  let continue_button = text!(ctx, "Continue", 805., 650., OnClickEvent::Transition(GameMode::Map));
+  MenuScreen::new(
+    ctx,
+    true,
+    containers,
+    vec![vec![continue_button]],
+    unselectable_items,
+    cursor_start,
+    MenuMovement::Grid,
+    OnClickEvent::None
+  )
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 #[macro_use] mod macros;
 pub mod characters;
+pub mod content;
 pub mod enemy_formations;
 pub mod enemies;
+pub mod equipment;
 pub mod font;
 pub mod menus;
+pub mod shops;
 pub mod tilemaps;
\ No newline at end of file
@@ -0,0 +1,42 @@
+use crate::data::equipment;
+use crate::data::content::GameContent;
+use crate::party::{Party, InventoryElement};
+use crate::party::item::InventoryItem;
+
+pub struct Shop {
+  pub stock: Vec<(InventoryElement, u32)> // (one of the goods, price)
+}
+
+// Which page of the shop a menu shows, the amount pages keep the index of the stock or inventory item picked
+#[derive(Clone, Copy)]
+pub enum ShopScreen {
+  Main,
+  Buy,
+  Sell,
+  BuyAmount(usize),
+  SellAmount(usize)
+}
+
+// Inventory indices of the items the party has any of, the rows of the sell list
+pub fn get_sellable(party: &Party) -> Vec<usize> {
+  party.inventory.iter().enumerate()
+    .filter(|(_, inventory_element)| inventory_element.get_amount() > 0)
+    .map(|(index, _)| index)
+    .collect()
+}
+
+pub fn get_shop(content: &GameContent, name: &str) -> Option<Shop> {
+  let definition = content.shops.iter().find(|shop| shop.name == name)?;
+  let mut stock = Vec::new();
+  for shop_item in definition.items.iter() {
+    let goods = match InventoryItem::from_name(&shop_item.item) {
+      Some(item) => (item.get_price(), InventoryElement::Item(item, 1)),
+      None => match equipment::get_equipment(content, &shop_item.item) {
+        Some(equipment) => (equipment.price, InventoryElement::Equipment(equipment, 1)),
+        None => continue
+      }
+    };
+    stock.push((goods.1, shop_item.price.unwrap_or(goods.0)));
+  }
+  Some(Shop {stock})
+}
\ No newline at end of file
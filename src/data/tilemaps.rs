@@ -1,21 +1,12 @@
-use ggez::Context;
-use crate::tilemap::Tilemap;
-use crate::tilemap::tile::Tile;
-use crate::tilemap::tile::EntityOnTile::*;
-use crate::data::enemy_formations;
+use ggez::{Context, GameResult};
+use crate::tilemap::{Tilemap, tiled};
 
-pub fn test_room(ctx: &mut Context) -> Tilemap {
-  Tilemap::new(
-    ctx,
-    "/test-tileset.png".to_owned(),
-    tiles![
-      [(0., 0., Solid), (1., 0., Solid), (1., 0., Solid), (1., 0., Solid ), (1., 0., Solid), (1., 0., Solid), (1., 0., Solid), (2., 0., Solid)]
-      [(0., 1., Solid), (1., 1., None ), (1., 1., None ), (1., 1., None  ), (1., 1., None ), (1., 1., None ), (1., 1., None ), (2., 1., Solid)]
-      [(0., 1., Solid), (1., 1., None ), (1., 1., None ), (1., 1., Player), (1., 1., None ), (1., 1., None ), (1., 1., None ), (2., 1., Solid)]
-      [(0., 1., Solid), (1., 1., None ), (1., 1., None ), (1., 1., None  ), (1., 1., None ), (1., 1., None ), (1., 1., None ), (2., 1., Solid)]
-      [(0., 2., Solid), (1., 2., Solid), (1., 2., Solid), (1., 2., Solid ), (1., 2., Solid), (1., 2., Solid), (1., 2., Solid), (2., 2., Solid)]
-    ],
-    0.90,
-    enemy_formations::test_room
-  )
+pub const STARTING_MAP: &str = "test_room";
+
+pub fn get_tilemap_path(name: &str) -> String {
+  format!("/maps/{}.json", name)
+}
+
+pub fn get_tilemap(ctx: &mut Context, name: &str) -> GameResult<Tilemap> {
+  tiled::load(ctx, name.to_owned(), &get_tilemap_path(name))
 }
\ No newline at end of file
@@ -1 +1,2 @@
-pub const WINDOW_SIZE: (f32, f32) = (1080., 720.);
\ No newline at end of file
+pub const WINDOW_SIZE: (f32, f32) = (1080., 720.);
+pub const RESOURCES_DIR: &str = "./resources";
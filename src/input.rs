@@ -0,0 +1,246 @@
+use ggez::Context;
+use ggez::event::KeyCode;
+use ggez::input::keyboard;
+use std::fs;
+use crate::save::settings::{self, AtbMode, Settings, BATTLE_SPEEDS};
+
+// The keys the game reads, with the letter each one has in replay files. D toggles group targeting and is G, as Down already took D.
+const KEYS: [(KeyCode, char); 8] = [
+  (KeyCode::Up, 'U'), (KeyCode::Down, 'D'), (KeyCode::Left, 'L'), (KeyCode::Right, 'R'),
+  (KeyCode::A, 'A'), (KeyCode::S, 'S'), (KeyCode::D, 'G'), (KeyCode::F, 'F')
+];
+
+// Keys held down during one frame. The game polls this instead of the keyboard, so a replay can stand in for the player.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Input {
+  keys: u8
+}
+
+impl Input {
+  fn from_keyboard(ctx: &Context) -> Input {
+    let mut input = Input::default();
+    for (index, (key, _)) in KEYS.iter().enumerate() {
+      if keyboard::is_key_pressed(ctx, *key) {
+        input.keys |= 1 << index;
+      }
+    }
+    input
+  }
+
+  fn from_letters(letters: &str) -> Result<Input, String> {
+    let mut input = Input::default();
+    for letter in letters.chars().filter(|letter| *letter != '-') {
+      match KEYS.iter().position(|(_, key_letter)| *key_letter == letter) {
+        Some(index) => input.keys |= 1 << index,
+        None => return Err(format!("Unknown key {}", letter))
+      }
+    }
+    Ok(input)
+  }
+
+  fn to_letters(self) -> String {
+    let letters: String = KEYS.iter().enumerate()
+      .filter(|(index, _)| self.keys & (1 << index) != 0)
+      .map(|(_, (_, letter))| *letter)
+      .collect();
+    match letters.is_empty() {
+      true  => "-".to_owned(),
+      false => letters
+    }
+  }
+
+  pub fn is_key_pressed(&self, key: KeyCode) -> bool {
+    match KEYS.iter().position(|(known_key, _)| *known_key == key) {
+      Some(index) => self.keys & (1 << index) != 0,
+      None => false
+    }
+  }
+}
+
+enum InputMode {
+  Live,
+  Record(String),
+  Replay(usize)
+}
+
+// Where each frame's input comes from. A replay file holds the seed, the battle settings and one line per run of identical frames:
+//   seed 1234
+//   atb_mode Active
+//   battle_speed 3
+//   120 -
+//   12 R
+//   1 A
+// The game only advances by frames, so the same seed and inputs play out the same way, as long as loaded save slots match too.
+pub struct InputRecorder {
+  pub seed: u64,
+  settings: Option<Settings>, // The replay's own, or the player's once read
+  mode: InputMode,
+  frames: Vec<(u32, Input)>,
+  frame: u32
+}
+
+impl InputRecorder {
+  pub fn live(seed: u64) -> InputRecorder {
+    InputRecorder {seed, settings: None, mode: InputMode::Live, frames: Vec::new(), frame: 0}
+  }
+
+  pub fn record(seed: u64, path: String) -> InputRecorder {
+    InputRecorder {seed, settings: None, mode: InputMode::Record(path), frames: Vec::new(), frame: 0}
+  }
+
+  pub fn replay(path: &str) -> Result<InputRecorder, String> {
+    let contents = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    parse_replay(&contents).map_err(|error| format!("{}: {}", path, error))
+  }
+
+  // Settings the game starts with. A replay brings its own so it plays out the same anywhere, otherwise they're
+  // read from the player's settings file and a recording writes them into its header.
+  pub fn get_settings(&mut self, ctx: &mut Context) -> Settings {
+    *self.settings.get_or_insert_with(|| settings::read_settings(ctx).unwrap_or_default())
+  }
+
+  // Input of the next frame, called once per update
+  pub fn next(&mut self, ctx: &Context) -> Input {
+    match &self.mode {
+      InputMode::Live => Input::from_keyboard(ctx),
+      InputMode::Record(_) => {
+        let input = Input::from_keyboard(ctx);
+        self.push(input);
+        input
+      },
+      InputMode::Replay(_) => match self.next_replayed() {
+        Some(input) => input,
+        None => {
+          self.mode = InputMode::Live;
+          Input::from_keyboard(ctx)
+        }
+      }
+    }
+  }
+
+  fn push(&mut self, input: Input) {
+    match self.frames.last_mut() {
+      Some((count, last_input)) if *last_input == input => *count += 1,
+      _ => self.frames.push((1, input))
+    }
+  }
+
+  // None once the replay has run out, the keyboard takes over from there
+  fn next_replayed(&mut self) -> Option<Input> {
+    let position = match &mut self.mode {
+      InputMode::Replay(position) => position,
+      _ => return None
+    };
+    while let Some((count, _)) = self.frames.get(*position) {
+      if self.frame < *count {
+        break;
+      }
+      *position += 1;
+      self.frame = 0;
+    }
+    let (_, input) = self.frames.get(*position)?;
+    self.frame += 1;
+    Some(*input)
+  }
+
+  // Writes the recording, does nothing unless recording
+  pub fn save(&self) -> Result<(), String> {
+    if let InputMode::Record(path) = &self.mode {
+      fs::write(path, self.to_replay()).map_err(|error| format!("{}: {}", path, error))?;
+    }
+    Ok(())
+  }
+
+  fn to_replay(&self) -> String {
+    let settings = self.settings.unwrap_or_default();
+    let mut contents = format!("seed {}\natb_mode {}\nbattle_speed {}\n", self.seed, settings.atb_mode.get_name(), settings.battle_speed);
+    for (count, input) in self.frames.iter() {
+      contents.push_str(&format!("{} {}\n", count, input.to_letters()));
+    }
+    contents
+  }
+}
+
+// The value of a header line like seed 1234, or None if the line is something else
+fn parse_header<'a>(line: Option<&'a str>, name: &str) -> Option<&'a str> {
+  match line.map(|line| line.split_whitespace().collect::<Vec<&str>>()) {
+    Some(words) if words.len() == 2 && words[0] == name => Some(words[1]),
+    _ => None
+  }
+}
+
+// A replay of the seed, settings and runs of frames in a replay file, see InputRecorder
+fn parse_replay(contents: &str) -> Result<InputRecorder, String> {
+  let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+  let seed = parse_header(lines.next(), "seed").ok_or("first line must be seed <number>")?
+    .parse().map_err(|_| "bad seed".to_owned())?;
+  let atb_mode = parse_header(lines.next(), "atb_mode").ok_or("second line must be atb_mode Active|Wait")?;
+  let atb_mode = AtbMode::from_name(atb_mode).ok_or("bad atb_mode")?;
+  let battle_speed = match parse_header(lines.next(), "battle_speed").map(|speed| speed.parse()) {
+    Some(Ok(speed)) if (1..=BATTLE_SPEEDS).contains(&speed) => speed,
+    Some(_) => return Err("bad battle_speed".to_owned()),
+    None => return Err(format!("third line must be battle_speed <1-{}>", BATTLE_SPEEDS))
+  };
+  let mut frames = Vec::new();
+  for (index, line) in lines.enumerate() {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let count = match words.first().and_then(|count| count.parse().ok()) {
+      Some(count) if words.len() == 2 => count,
+      _ => return Err(format!("line {} must be <frames> <keys>", index + 4))
+    };
+    let input = Input::from_letters(words[1]).map_err(|error| format!("line {}: {}", index + 4, error))?;
+    frames.push((count, input));
+  }
+  Ok(InputRecorder {seed, settings: Some(Settings {atb_mode, battle_speed}), mode: InputMode::Replay(0), frames, frame: 0})
+}
+
+// Every frame of a replay in order, for tests that play recorded input through other parts of the game
+#[cfg(test)]
+pub fn replay_frames(contents: &str) -> Vec<Input> {
+  let mut replay = parse_replay(contents).unwrap();
+  std::iter::from_fn(|| replay.next_replayed()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn keys(letters: &str) -> Input {
+    Input::from_letters(letters).unwrap()
+  }
+
+  #[test]
+  fn recording_survives_a_save_and_replay() {
+    let played = vec![keys("-"), keys("-"), keys("R"), keys("RA"), keys("RA"), keys("-"), keys("UDLRASGF")];
+    let mut recorder = InputRecorder::record(1234, "unused.txt".to_owned());
+    recorder.settings = Some(Settings {atb_mode: AtbMode::Wait, battle_speed: 5});
+    for input in played.iter() {
+      recorder.push(*input);
+    }
+    let contents = recorder.to_replay();
+    assert_eq!(contents, "seed 1234\natb_mode Wait\nbattle_speed 5\n2 -\n1 R\n2 RA\n1 -\n1 UDLRASGF\n");
+    let mut replay = parse_replay(&contents).unwrap();
+    let settings = replay.settings.unwrap();
+    assert_eq!(replay.seed, 1234);
+    assert!(settings.atb_mode == AtbMode::Wait);
+    assert_eq!(settings.battle_speed, 5);
+    let replayed: Vec<Input> = std::iter::from_fn(|| replay.next_replayed()).collect();
+    assert!(replayed == played);
+  }
+
+  #[test]
+  fn malformed_lines_are_rejected() {
+    let header = "seed 1\natb_mode Active\nbattle_speed 3\n";
+    assert_eq!(parse_replay("").err(), Some("first line must be seed <number>".to_owned()));
+    assert_eq!(parse_replay("seed").err(), Some("first line must be seed <number>".to_owned()));
+    assert_eq!(parse_replay("seed many").err(), Some("bad seed".to_owned()));
+    assert_eq!(parse_replay("seed 1\n12 A").err(), Some("second line must be atb_mode Active|Wait".to_owned()));
+    assert_eq!(parse_replay("seed 1\natb_mode Fast").err(), Some("bad atb_mode".to_owned()));
+    assert_eq!(parse_replay("seed 1\natb_mode Wait").err(), Some("third line must be battle_speed <1-5>".to_owned()));
+    assert_eq!(parse_replay("seed 1\natb_mode Wait\nbattle_speed 6").err(), Some("bad battle_speed".to_owned()));
+    assert_eq!(parse_replay(&format!("{}12", header)).err(), Some("line 4 must be <frames> <keys>".to_owned()));
+    assert_eq!(parse_replay(&format!("{}-1 A", header)).err(), Some("line 4 must be <frames> <keys>".to_owned()));
+    assert_eq!(parse_replay(&format!("{}3 A\n2 A S", header)).err(), Some("line 5 must be <frames> <keys>".to_owned()));
+    assert_eq!(parse_replay(&format!("{}3 A\n2 X", header)).err(), Some("line 5: Unknown key X".to_owned()));
+  }
+}
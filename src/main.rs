@@ -5,19 +5,27 @@ use ggez::conf::{WindowSetup, WindowMode, FullscreenType, NumSamples};
 use ggez::timer::sleep;
 use std::time::Duration;
 use std::path;
+use std::env;
+use std::rc::Rc;
 mod globals;
-use globals::WINDOW_SIZE;
+use globals::{WINDOW_SIZE, RESOURCES_DIR};
 mod menu;
 use menu::MenuScreen;
 mod tilemap;
 use tilemap::Tilemap;
 mod data;
+use data::content;
 mod battle;
 use battle::Battle;
 mod party;
 use party::Party;
 mod transition;
 use transition::{Transition, TransitionStyle};
+mod save;
+mod script;
+mod random;
+mod input;
+use input::InputRecorder;
 
 #[derive(Clone)]
 pub enum GameMode {
@@ -41,34 +49,41 @@ struct GameState {
   party: Party,
   battle: Battle,
   battle_menu: MenuScreen,
-  transition: Transition
+  transition: Transition,
+  input: InputRecorder
 }
 
 impl GameState {
-  pub fn new(ctx: &mut Context) -> GameState {
-    let mut party = Party::new(ctx);
-    let mut menu = data::menus::none_menu(ctx);
-    let battle = Battle::new(ctx, vec![Vec::new()], &mut party, &mut menu);
-    GameState {
+  // Content is parsed once here, a missing or broken definition stops the game before it starts
+  pub fn new(ctx: &mut Context, mut input: InputRecorder) -> GameResult<GameState> {
+    let battle_content = content::load_battle_content(ctx)?;
+    let game_content = content::load_game_content(ctx)?;
+    let settings = input.get_settings(ctx);
+    let party = Party::new(ctx, input.seed, settings, battle::damage::get_formula(&battle_content.combat), Rc::new(game_content))?;
+    let menu = data::menus::none_menu(ctx);
+    let battle = Battle::none(ctx, Rc::new(battle_content), &party);
+    Ok(GameState {
       mode: GameMode::Map,
       menu,
-      map: data::tilemaps::test_room(ctx),
+      map: data::tilemaps::get_tilemap(ctx, data::tilemaps::STARTING_MAP).unwrap(),
       party,
       battle,
       battle_menu: data::menus::none_menu(ctx),
       transition: Transition::new(),
-    }
+      input
+    })
   }
 }
 
 impl EventHandler for GameState {
   fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+    let input = self.input.next(ctx);
     if self.transition.style == TransitionStyle::None {
-      self.battle.update(ctx, &mut self.mode, &mut self.party, &mut self.menu, &mut self.battle_menu, &mut self.transition)?;
-      self.map.update(ctx, &mut self.mode, &mut self.party, &mut self.battle, &mut self.menu, &mut self.transition)?;
-      self.menu.update(ctx, &mut self.mode, &mut self.party, &mut self.battle, &mut self.transition)?;
+      self.battle.update(ctx, &input, &mut self.mode, &mut self.party, &mut self.menu, &mut self.battle_menu, &mut self.map, &mut self.transition)?;
+      self.map.update(ctx, &input, &mut self.mode, &mut self.party, &mut self.battle, &mut self.menu, &mut self.transition)?;
+      self.menu.update(ctx, &input, &mut self.mode, &mut self.party, &mut self.battle, &mut self.map, &mut self.transition)?;
     } else {
-      self.transition.update(ctx, &mut self.mode, &mut self.menu, &mut self.party, &self.battle.enemies)?;
+      self.transition.update(ctx, &mut self.mode, &mut self.menu, &mut self.party, &self.battle.enemies, &mut self.map)?;
     }
     Ok(())
   }
@@ -89,8 +104,24 @@ impl EventHandler for GameState {
 }
 
 fn main() {
+  let args: Vec<String> = env::args().collect();
+  if args.get(1).map(|arg| arg.as_str()) == Some("simulate") {
+    match battle::simulator::run_from_args(&args[2..]) {
+      Ok(summary) => println!("{}", summary),
+      Err(error) => println!("Error: {}", error)
+    }
+    return;
+  }
+  let input = match get_input_recorder(&args) {
+    Ok(input) => input,
+    Err(error) => {
+      println!("Error: {}", error);
+      return;
+    }
+  };
+  println!("Seed: {}", input.seed);
   let (mut ctx, mut event_loop) = ContextBuilder::new("game_name", "author_name")
-    .add_resource_path(path::PathBuf::from("./resources"))
+    .add_resource_path(path::PathBuf::from(RESOURCES_DIR))
     .window_setup(WindowSetup {
       title: "THE ULTIMATE GAMING EXPERIENCE".to_owned(),
       samples: NumSamples::Zero,
@@ -112,9 +143,41 @@ fn main() {
     })
     .build()
     .unwrap();
-  let mut game = GameState::new(&mut ctx);
+  let content_errors = data::content::validate(&mut ctx);
+  if !content_errors.is_empty() {
+    for content_error in content_errors {
+      println!("Content error: {}", content_error);
+    }
+    return;
+  }
+  let mut game = match GameState::new(&mut ctx, input) {
+    Ok(game) => game,
+    Err(error) => {
+      println!("Content error: {}", error);
+      return;
+    }
+  };
   match run(&mut ctx, &mut event_loop, &mut game) {
     Ok(_) => println!("Finished"),
     Err(e) => println!("Error:\n{}", e)
   }
+  if let Err(error) = game.input.save() {
+    println!("Could not save the recording: {}", error);
+  }
+}
+
+// [--seed N] [--record file | --replay file], a replay brings its own seed and battle settings
+fn get_input_recorder(args: &[String]) -> Result<InputRecorder, String> {
+  let get_value = |name: &str| args.iter().position(|arg| arg == name).map(|index| args.get(index + 1));
+  if let Some(path) = get_value("--replay") {
+    return InputRecorder::replay(path.ok_or("--replay needs a file")?);
+  }
+  let seed = match get_value("--seed") {
+    Some(value) => value.and_then(|value| value.parse().ok()).ok_or("--seed needs a number")?,
+    None => random::new_seed()
+  };
+  match get_value("--record") {
+    Some(path) => Ok(InputRecorder::record(seed, path.ok_or("--record needs a file")?.to_owned())),
+    None => Ok(InputRecorder::live(seed))
+  }
 }
\ No newline at end of file
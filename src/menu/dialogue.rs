@@ -0,0 +1,103 @@
+use ggez::{Context, GameResult};
+use ggez::event::KeyCode;
+use crate::globals::WINDOW_SIZE;
+use crate::input::Input;
+use crate::menu::container::MenuContainer;
+use crate::menu::item::{MenuItem, OnClickEvent};
+
+const LINE_LENGTH: usize = 40;
+const LINES_PER_PAGE: usize = 3;
+
+pub struct Dialogue {
+  container: MenuContainer,
+  avatar: Option<MenuItem>,
+  speaker: MenuItem,
+  lines: Vec<MenuItem>,
+  pages: Vec<Vec<String>>,
+  page: usize,
+  input_cooldown: bool
+}
+
+impl Dialogue {
+  pub fn new(ctx: &mut Context, speaker: String, avatar: Option<String>, text: &[String]) -> Dialogue {
+    let text_x = match avatar {
+      Some(_) => 200.,
+      None => 50.
+    };
+    let line_length = LINE_LENGTH - ((text_x - 50.) / 24.) as usize;
+    let mut pages = Vec::new();
+    for paragraph in text.iter() {
+      for page in wrap_text(paragraph, line_length).chunks(LINES_PER_PAGE) {
+        pages.push(page.to_vec());
+      }
+    }
+    if pages.is_empty() {
+      pages.push(vec!["...".to_owned()]);
+    }
+    let top = WINDOW_SIZE.1 - 230.;
+    let mut dialogue = Dialogue {
+      container: MenuContainer::new(ctx, 10., top, WINDOW_SIZE.0 - 20., 220.),
+      avatar: avatar.map(|spritefile| MenuItem::new(ctx, spritefile, "".to_owned(), (40., top + 46.), 128., OnClickEvent::None)),
+      speaker: MenuItem::new(ctx, "".to_owned(), speaker, (text_x, top + 30.), 24., OnClickEvent::None),
+      lines: Vec::new(),
+      pages,
+      page: 0,
+      input_cooldown: true
+    };
+    dialogue.show_page(ctx);
+    dialogue
+  }
+
+  fn show_page(&mut self, ctx: &mut Context) {
+    let (text_x, top) = (self.speaker.screen_pos.0, self.container.y);
+    self.lines = self.pages[self.page].iter().enumerate()
+      .map(|(index, line)| MenuItem::new(ctx, "".to_owned(), line.to_owned(), (text_x + 24., top + 80. + index as f32 * 40.), 24., OnClickEvent::None))
+      .collect();
+  }
+
+  // Returns true once the last page has been confirmed
+  pub fn update(&mut self, ctx: &mut Context, input: &Input) -> GameResult<bool> {
+    if input.is_key_pressed(KeyCode::A) && !self.input_cooldown {
+      self.input_cooldown = true;
+      if self.page + 1 >= self.pages.len() {
+        return Ok(true);
+      }
+      self.page += 1;
+      self.show_page(ctx);
+    } else if !input.is_key_pressed(KeyCode::A) {
+      self.input_cooldown = false;
+    }
+    Ok(false)
+  }
+
+  pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+    self.container.draw(ctx)?;
+    if let Some(avatar) = &mut self.avatar {
+      avatar.draw(ctx)?;
+    }
+    self.speaker.draw(ctx)?;
+    for line in self.lines.iter_mut() {
+      line.draw(ctx)?;
+    }
+    Ok(())
+  }
+}
+
+fn wrap_text(text: &str, line_length: usize) -> Vec<String> {
+  let mut lines = Vec::new();
+  let mut line = String::new();
+  for word in text.split_whitespace() {
+    if !line.is_empty() && line.len() + 1 + word.len() > line_length {
+      lines.push(line);
+      line = String::new();
+    }
+    if !line.is_empty() {
+      line.push(' ');
+    }
+    line.push_str(word);
+  }
+  if !line.is_empty() {
+    lines.push(line);
+  }
+  lines
+}
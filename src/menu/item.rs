@@ -8,6 +8,11 @@ use crate::battle::Battle;
 use crate::battle::action::ActionParameters;
 use crate::battle::enemy::Enemy;
 use crate::menu::MenuScreen;
+use crate::save::settings::Setting;
+use crate::data::shops::{Shop, ShopScreen};
+use crate::data::equipment::EquipScreen;
+use crate::party::equipment::Slot;
+use std::rc::Rc;
 
 pub enum OnClickEvent {
   None,
@@ -34,7 +39,17 @@ pub enum OnClickEvent {
     Vec<u8>,
     (usize, usize)
   ),
-  BattleAction(for<'r, 's, 't> fn(&'r mut Context, &'s mut Party, &'t mut Battle) -> GameResult<()>)
+  BattleAction(for<'r, 's, 't> fn(&'r mut Context, &'s mut Party, &'t mut Battle) -> GameResult<()>),
+  SaveGame(u8),
+  LoadGame(u8),
+  ChangeSetting(Setting),
+  RetryBattle,
+  NewGame,
+  ToShopScreen(Rc<Shop>, ShopScreen, (usize, usize)),
+  BuyItem(Rc<Shop>, usize, u8), // (shop, stock index, amount)
+  SellItem(Rc<Shop>, usize, u8), // (shop, inventory index, amount)
+  ToEquipScreen(EquipScreen, (usize, usize)),
+  Equip(u8, Slot, Option<usize>) // (party member id, slot, inventory index of the equipment or None to take it off)
 }
 
 impl Clone for OnClickEvent {
@@ -50,7 +65,17 @@ impl Clone for OnClickEvent {
       OnClickEvent::Transition(new_mode)                              => OnClickEvent::Transition(new_mode.clone()),
       OnClickEvent::MenuTransition(new_menu, cursor_start)            => OnClickEvent::MenuTransition(*new_menu, *cursor_start),
       OnClickEvent::UseItemInMenu(new_menu, targets, item_cursor_pos) => OnClickEvent::UseItemInMenu(*new_menu, targets.to_vec(), *item_cursor_pos),
-      OnClickEvent::BattleAction(action)                              => OnClickEvent::BattleAction(*action)
+      OnClickEvent::BattleAction(action)                              => OnClickEvent::BattleAction(*action),
+      OnClickEvent::SaveGame(slot)                                    => OnClickEvent::SaveGame(*slot),
+      OnClickEvent::LoadGame(slot)                                    => OnClickEvent::LoadGame(*slot),
+      OnClickEvent::ChangeSetting(setting)                            => OnClickEvent::ChangeSetting(*setting),
+      OnClickEvent::RetryBattle                                       => OnClickEvent::RetryBattle,
+      OnClickEvent::NewGame                                           => OnClickEvent::NewGame,
+      OnClickEvent::ToShopScreen(shop, screen, cursor_start)          => OnClickEvent::ToShopScreen(shop.clone(), *screen, *cursor_start),
+      OnClickEvent::BuyItem(shop, index, amount)                      => OnClickEvent::BuyItem(shop.clone(), *index, *amount),
+      OnClickEvent::SellItem(shop, index, amount)                     => OnClickEvent::SellItem(shop.clone(), *index, *amount),
+      OnClickEvent::ToEquipScreen(screen, cursor_start)               => OnClickEvent::ToEquipScreen(*screen, *cursor_start),
+      OnClickEvent::Equip(id, slot, index)                            => OnClickEvent::Equip(*id, *slot, *index)
     }
   }
 }
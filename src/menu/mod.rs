@@ -1,8 +1,8 @@
 use ggez::graphics::{spritebatch, Image, DrawParam, draw};
 use ggez::nalgebra::Point2;
 use ggez::event::KeyCode;
-use ggez::input::keyboard;
 use ggez::{Context, GameResult};
+use rand::Rng;
 pub mod item;
 use item::{MenuItem, OnClickEvent};
 pub mod container;
@@ -10,12 +10,18 @@ use container::MenuContainer;
 pub mod input_cooldowns;
 use input_cooldowns::InputCooldowns;
 pub mod notification;
+pub mod dialogue;
 use crate::GameMode;
+use crate::input::Input;
 use crate::party::Party;
 use crate::battle::Battle;
 use crate::battle::enemy::Enemy;
+use crate::tilemap::Tilemap;
 use crate::transition::{Transition, TransitionStyle};
-use crate::data::menus;
+use crate::data::{menus, tilemaps};
+use crate::data::shops::{self, ShopScreen};
+use crate::data::equipment::EquipScreen;
+use crate::save::{self, settings};
 
 pub enum MenuMovement {
   Grid, ColumnOfRows, RowOfColumns
@@ -49,7 +55,8 @@ pub struct MenuScreen {
   cursor_movement_style: MenuMovement,
   input_cooldowns: InputCooldowns,
   return_action: OnClickEvent,
-  pub mutation: MenuMutation
+  pub mutation: MenuMutation,
+  scroll: Option<(usize, usize)> // (visible rows, first visible row) for lists longer than their container
 }
 
 impl MenuScreen {
@@ -73,25 +80,54 @@ impl MenuScreen {
       cursor_movement_style,
       input_cooldowns: InputCooldowns::new(),
       return_action,
-      mutation: MenuMutation::None
+      mutation: MenuMutation::None,
+      scroll: None
+    }
+  }
+
+  // Only shows this many rows at a time and scrolls them along with the cursor
+  pub fn set_visible_rows(&mut self, visible_rows: usize) {
+    self.scroll = Some((visible_rows.max(1), 0));
+    self.scroll_to_cursor();
+  }
+
+  fn scroll_to_cursor(&mut self) {
+    if let Some((visible_rows, first_row)) = self.scroll {
+      let row = self.cursor_pos.1;
+      let new_first_row = if row < first_row {
+        row
+      } else if row >= first_row + visible_rows {
+        row + 1 - visible_rows
+      } else {
+        first_row
+      };
+      for column in self.selectable_items.iter_mut().filter(|column| column.len() > 1) {
+        let row_height = column[1].screen_pos.1 - column[0].screen_pos.1;
+        for item in column.iter_mut() {
+          item.screen_pos.1 -= (new_first_row as f32 - first_row as f32) * row_height;
+        }
+      }
+      self.scroll = Some((visible_rows, new_first_row));
     }
   }
 
   pub fn update(
     &mut self,
     ctx: &mut Context,
+    input: &Input,
     mode: &mut GameMode,
     party: &mut Party,
     battle: &mut Battle,
+    map: &mut Tilemap,
     transition: &mut Transition
   ) -> GameResult<()> {
-    if *mode == GameMode::Map && keyboard::is_key_pressed(ctx, KeyCode::F) {
+    if *mode == GameMode::Map && map.script.is_none() && input.is_key_pressed(KeyCode::F) {
       fn to_main_menu(ctx: &mut Context, mode: &mut GameMode, party: &mut Party, enemies: &Vec<Vec<Enemy>>, cursor_start: (usize, usize)) -> MenuScreen {
         menus::main_menu(ctx, mode, party, enemies, cursor_start)
       }
       transition.set(TransitionStyle::MenuIn(to_main_menu, (0, 0)))?;
     } else if self.open {
-      if keyboard::is_key_pressed(ctx, KeyCode::A) && !self.input_cooldowns.a {
+      if input.is_key_pressed(KeyCode::A) && !self.input_cooldowns.a {
         self.input_cooldowns.a = true;
         match &mut self.selectable_items[self.cursor_pos.0][self.cursor_pos.1].on_click {
           OnClickEvent::ToMenuScreen(new_menu, cursor_start) => *self = new_menu(ctx, mode, party, &battle.enemies, *cursor_start),
@@ -100,7 +136,13 @@ impl MenuScreen {
           },
           OnClickEvent::ActOnTarget(target, action_parameters) => {
             self.open = false;
-            party.battle_turn_action(ctx, battle, *target, action_parameters)?;
+            if !party.battle_turn_action(ctx, battle, *target, action_parameters)? {
+              let cursor_memory = match self.return_action {
+                OnClickEvent::ToMenuScreen(_, cursor_memory) => cursor_memory,
+                _ => (0, 0)
+              };
+              *self = menus::battle_magic_menu(ctx, mode, party, &battle.enemies, cursor_memory);
+            }
           },
           OnClickEvent::MutateMenu(mutation)                              => self.mutation = MenuMutation::DefaultMutation(*mutation),
           OnClickEvent::Transition(new_mode)                              => transition.set(TransitionStyle::BlackInFast(new_mode.clone()))?,
@@ -110,67 +152,62 @@ impl MenuScreen {
             self.open = false;
             action(ctx, party, battle)?;
           },
+          OnClickEvent::SaveGame(slot) => {
+            save::save_game(ctx, *slot, party, map)?;
+            *self = menus::save_menu(ctx, mode, party, &battle.enemies, self.cursor_pos);
+          },
+          OnClickEvent::LoadGame(slot) => {
+            save::load_game(ctx, *slot, party, map)?;
+            transition.set(TransitionStyle::BlackInFast(GameMode::Map))?;
+          },
+          OnClickEvent::RetryBattle => battle.retry(ctx, party, self, transition)?,
+          OnClickEvent::NewGame => {
+            *party = Party::new(ctx, party.rng.gen(), party.settings, party.damage_formula.clone(), party.content.clone())?;
+            *map = tilemaps::get_tilemap(ctx, tilemaps::STARTING_MAP)?;
+            transition.set(TransitionStyle::BlackInFast(GameMode::Map))?;
+          },
+          OnClickEvent::ChangeSetting(setting) => {
+            party.settings.change(*setting);
+            settings::write_settings(ctx, &party.settings)?;
+            *self = menus::config_menu(ctx, mode, party, &battle.enemies, self.cursor_pos);
+          },
+          OnClickEvent::ToShopScreen(shop, screen, cursor_start) => *self = menus::shop_menu(ctx, party, shop.clone(), *screen, *cursor_start),
+          OnClickEvent::BuyItem(shop, index, amount) => {
+            let (item, price) = &shop.stock[*index];
+            party.buy(item, *amount, *price);
+            *self = menus::shop_menu(ctx, party, shop.clone(), ShopScreen::Buy, (0, *index));
+          },
+          OnClickEvent::SellItem(shop, index, amount) => {
+            let row = shops::get_sellable(party).iter().position(|sellable| sellable == index).unwrap_or(0);
+            party.sell(*index, *amount);
+            *self = menus::shop_menu(ctx, party, shop.clone(), ShopScreen::Sell, (0, row));
+          },
+          OnClickEvent::ToEquipScreen(screen, cursor_start) => *self = menus::equip_menu(ctx, party, *screen, *cursor_start),
+          OnClickEvent::Equip(id, slot, index) => {
+            party.equip(*id, *slot, *index);
+            *self = menus::equip_menu(ctx, party, EquipScreen::Slots(*id), (0, *slot as usize));
+          },
           OnClickEvent::None => ()
         }
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::A) {
+      } else if !input.is_key_pressed(KeyCode::A) {
         self.input_cooldowns.a = false;
       }
-      if keyboard::is_key_pressed(ctx, KeyCode::S) && !self.input_cooldowns.s {
+      press_group_toggle(input, &mut self.input_cooldowns.d, self.selectable_items.iter_mut().flatten().map(|item| &mut item.on_click));
+      if input.is_key_pressed(KeyCode::S) && !self.input_cooldowns.s {
         self.input_cooldowns.s = true;
         match &self.return_action {
           OnClickEvent::ToMenuScreen(new_menu, cursor_start)   => *self = new_menu(ctx, mode, party, &battle.enemies, *cursor_start),
           OnClickEvent::Transition(new_mode)                   => transition.set(TransitionStyle::BlackInFast(new_mode.clone()))?,
           OnClickEvent::MenuTransition(new_menu, cursor_start) => transition.set(TransitionStyle::MenuIn(*new_menu, *cursor_start))?,
+          OnClickEvent::ToShopScreen(shop, screen, cursor_start) => *self = menus::shop_menu(ctx, party, shop.clone(), *screen, *cursor_start),
+          OnClickEvent::ToEquipScreen(screen, cursor_start)    => *self = menus::equip_menu(ctx, party, *screen, *cursor_start),
           _ => ()
         }
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::S) {
+      } else if !input.is_key_pressed(KeyCode::S) {
         self.input_cooldowns.s = false;
       }
-      if keyboard::is_key_pressed(ctx, KeyCode::Up) && !self.input_cooldowns.up {
-        self.input_cooldowns.up = true;
-        match self.cursor_movement_style {
-          MenuMovement::Grid         => {if self.cursor_pos.1 > 0 {self.cursor_pos.1 -= 1;}},
-          MenuMovement::ColumnOfRows => {if self.cursor_pos.1 > 0 {self.cursor_pos = (0, self.cursor_pos.1 - 1);}},
-          MenuMovement::RowOfColumns => {if self.cursor_pos.1 > 0 {self.cursor_pos.1 -= 1;}}
-        }
+      if self.move_cursor(input) {
         return Ok(());
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::Up) {
-        self.input_cooldowns.up = false;
-      }
-      if keyboard::is_key_pressed(ctx, KeyCode::Down) && !self.input_cooldowns.down {
-        self.input_cooldowns.down = true;
-        match self.cursor_movement_style {
-          MenuMovement::Grid         => {if self.cursor_pos.1 < self.selectable_items[self.cursor_pos.0].len() - 1 {self.cursor_pos.1 += 1;}},
-          MenuMovement::ColumnOfRows => {if self.cursor_pos.1 < self.selectable_items.len() - 1 {self.cursor_pos = (0, self.cursor_pos.1 + 1)}},
-          MenuMovement::RowOfColumns => {if self.cursor_pos.1 < self.selectable_items[self.cursor_pos.0].len() - 1 {self.cursor_pos.1 += 1;}}
-        }
-        return Ok(());
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::Down) {
-        self.input_cooldowns.down = false;
-      }
-      if keyboard::is_key_pressed(ctx, KeyCode::Left) && !self.input_cooldowns.left {
-        self.input_cooldowns.left = true;
-        match self.cursor_movement_style {
-          MenuMovement::Grid         => {if self.cursor_pos.0 > 0 && self.selectable_items[self.cursor_pos.0 - 1].len() >= self.cursor_pos.1 {self.cursor_pos.0 -= 1;}},
-          MenuMovement::ColumnOfRows => {if self.cursor_pos.0 > 0 {self.cursor_pos.0 -= 1;}},
-          MenuMovement::RowOfColumns => {if self.cursor_pos.0 > 0 {self.cursor_pos = (self.cursor_pos.0 - 1, 0);}}
-        }
-        return Ok(());
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::Left) {
-        self.input_cooldowns.left = false;
-      }
-      if keyboard::is_key_pressed(ctx, KeyCode::Right) && !self.input_cooldowns.right {
-        self.input_cooldowns.right = true;
-        match self.cursor_movement_style {
-          MenuMovement::Grid => {
-            if self.cursor_pos.0 < self.selectable_items.len() - 1 && self.selectable_items[self.cursor_pos.0 + 1].len() >= self.cursor_pos.1 {self.cursor_pos.0 += 1;}
-          },
-          MenuMovement::ColumnOfRows => {if self.cursor_pos.0 < self.selectable_items[self.cursor_pos.1].len() - 1 {self.cursor_pos.0 += 1;}},
-          MenuMovement::RowOfColumns => {if self.cursor_pos.0 < self.selectable_items.len() - 1 {self.cursor_pos = (self.cursor_pos.0 + 1, 0)}}
-        }
-        return Ok(());
-      } else if !keyboard::is_key_pressed(ctx, KeyCode::Right) {
-        self.input_cooldowns.right = false;
       }
       match &self.mutation {
         MenuMutation::None => (),
@@ -185,25 +222,155 @@ impl MenuScreen {
     Ok(())
   }
 
+  // Anything opened from another menu, like the battle commands' magic list or target selection
+  pub fn is_submenu(&self) -> bool {
+    !matches!(self.return_action, OnClickEvent::None)
+  }
+
+  // Items the cursor points at, every target of the scope when group targeting
+  fn get_cursor_items(&self) -> Vec<&MenuItem> {
+    let selected_item = &self.selectable_items[self.cursor_pos.0][self.cursor_pos.1];
+    match &selected_item.on_click {
+      OnClickEvent::ActOnTarget(selected, action_parameters) if action_parameters.group && action_parameters.scope.is_toggleable() => {
+        self.selectable_items.iter().flatten().filter(|item| match &item.on_click {
+          OnClickEvent::ActOnTarget(position, _) => action_parameters.scope.includes(*selected, *position),
+          _ => false
+        }).collect()
+      },
+      _ => vec![selected_item]
+    }
+  }
+
+  // Moves the cursor with the arrow keys, returns true if it moved
+  pub fn move_cursor(&mut self, input: &Input) -> bool {
+    if input.is_key_pressed(KeyCode::Up) && !self.input_cooldowns.up {
+      self.input_cooldowns.up = true;
+      match self.cursor_movement_style {
+        MenuMovement::Grid         => {if self.cursor_pos.1 > 0 {self.cursor_pos.1 -= 1;}},
+        MenuMovement::ColumnOfRows => {if self.cursor_pos.1 > 0 {self.cursor_pos = (0, self.cursor_pos.1 - 1);}},
+        MenuMovement::RowOfColumns => {if self.cursor_pos.1 > 0 {self.cursor_pos.1 -= 1;}}
+      }
+      self.scroll_to_cursor();
+      return true;
+    } else if !input.is_key_pressed(KeyCode::Up) {
+      self.input_cooldowns.up = false;
+    }
+    if input.is_key_pressed(KeyCode::Down) && !self.input_cooldowns.down {
+      self.input_cooldowns.down = true;
+      match self.cursor_movement_style {
+        MenuMovement::Grid         => {if self.cursor_pos.1 < self.selectable_items[self.cursor_pos.0].len() - 1 {self.cursor_pos.1 += 1;}},
+        MenuMovement::ColumnOfRows => {if self.cursor_pos.1 < self.selectable_items.len() - 1 {self.cursor_pos = (0, self.cursor_pos.1 + 1)}},
+        MenuMovement::RowOfColumns => {if self.cursor_pos.1 < self.selectable_items[self.cursor_pos.0].len() - 1 {self.cursor_pos.1 += 1;}}
+      }
+      self.scroll_to_cursor();
+      return true;
+    } else if !input.is_key_pressed(KeyCode::Down) {
+      self.input_cooldowns.down = false;
+    }
+    if input.is_key_pressed(KeyCode::Left) && !self.input_cooldowns.left {
+      self.input_cooldowns.left = true;
+      match self.cursor_movement_style {
+        MenuMovement::Grid         => {if self.cursor_pos.0 > 0 && self.selectable_items[self.cursor_pos.0 - 1].len() >= self.cursor_pos.1 {self.cursor_pos.0 -= 1;}},
+        MenuMovement::ColumnOfRows => {if self.cursor_pos.0 > 0 {self.cursor_pos.0 -= 1;}},
+        MenuMovement::RowOfColumns => {if self.cursor_pos.0 > 0 {self.cursor_pos = (self.cursor_pos.0 - 1, 0);}}
+      }
+      self.scroll_to_cursor();
+      return true;
+    } else if !input.is_key_pressed(KeyCode::Left) {
+      self.input_cooldowns.left = false;
+    }
+    if input.is_key_pressed(KeyCode::Right) && !self.input_cooldowns.right {
+      self.input_cooldowns.right = true;
+      match self.cursor_movement_style {
+        MenuMovement::Grid => {
+          if self.cursor_pos.0 < self.selectable_items.len() - 1 && self.selectable_items[self.cursor_pos.0 + 1].len() >= self.cursor_pos.1 {self.cursor_pos.0 += 1;}
+        },
+        MenuMovement::ColumnOfRows => {if self.cursor_pos.0 < self.selectable_items[self.cursor_pos.1].len() - 1 {self.cursor_pos.0 += 1;}},
+        MenuMovement::RowOfColumns => {if self.cursor_pos.0 < self.selectable_items.len() - 1 {self.cursor_pos = (self.cursor_pos.0 + 1, 0)}}
+      }
+      self.scroll_to_cursor();
+      return true;
+    } else if !input.is_key_pressed(KeyCode::Right) {
+      self.input_cooldowns.right = false;
+    }
+    false
+  }
+
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
     if self.open {
       for menu_container in self.containers.iter_mut() {
         menu_container.draw(ctx)?;
       }
-      for item in self.selectable_items.iter_mut().flatten() {
-        item.draw(ctx)?;
+      let scroll = self.scroll;
+      for column in self.selectable_items.iter_mut() {
+        for (row, item) in column.iter_mut().enumerate() {
+          if is_row_visible(scroll, row) {
+            item.draw(ctx)?;
+          }
+        }
       }
       for item in self.unselectable_items.iter_mut() {
         item.draw(ctx)?;
       }
       if self.selectable_items.first().unwrap().len() > 0 {
-        let selected_item = &self.selectable_items[self.cursor_pos.0][self.cursor_pos.1];
-        let cursor_pos = selected_item.screen_pos;
         self.cursor.add(DrawParam::new());
-        let param = DrawParam::new().dest(Point2::new(cursor_pos.0 - 50., cursor_pos.1 + selected_item.sprite_height / 2. - 12.));
-        draw(ctx, &self.cursor, param)?;
+        for item in self.get_cursor_items() {
+          let param = DrawParam::new().dest(Point2::new(item.screen_pos.0 - 50., item.screen_pos.1 + item.sprite_height / 2. - 12.));
+          draw(ctx, &self.cursor, param)?;
+        }
       }
     }
     Ok(())
   }
-}
\ No newline at end of file
+}
+
+// Switches target selection between the selected target and the action's whole scope, once per press of D
+fn press_group_toggle<'a>(input: &Input, cooldown: &mut bool, on_clicks: impl Iterator<Item = &'a mut OnClickEvent>) {
+  if input.is_key_pressed(KeyCode::D) && !*cooldown {
+    *cooldown = true;
+    for on_click in on_clicks {
+      if let OnClickEvent::ActOnTarget(_, action_parameters) = on_click {
+        if action_parameters.scope.is_toggleable() {
+          action_parameters.group = !action_parameters.group;
+        }
+      }
+    }
+  } else if !input.is_key_pressed(KeyCode::D) {
+    *cooldown = false;
+  }
+}
+
+fn is_row_visible(scroll: Option<(usize, usize)>, row: usize) -> bool {
+  match scroll {
+    Some((visible_rows, first_row)) => row >= first_row && row < first_row + visible_rows,
+    None => true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::press_group_toggle;
+  use super::item::OnClickEvent;
+  use crate::battle::action::{ActionParameters, DamageType, TargetScope};
+  use crate::input;
+
+  fn is_group(on_click: &OnClickEvent) -> bool {
+    match on_click {
+      OnClickEvent::ActOnTarget(_, action_parameters) => action_parameters.group,
+      _ => false
+    }
+  }
+
+  #[test]
+  fn recorded_d_press_targets_the_whole_group() {
+    let action_parameters = ActionParameters {scope: TargetScope::All, ..ActionParameters::new(DamageType::Magical, 10, 0., false, Vec::new(), Vec::new())};
+    let mut on_clicks = [OnClickEvent::ActOnTarget((1, 0), action_parameters.clone()), OnClickEvent::ActOnTarget((1, 1), action_parameters)];
+    let mut cooldown = true; // Like a freshly opened menu, the key has to be let go first
+    let mut groups = Vec::new();
+    for input in input::replay_frames("seed 1\natb_mode Active\nbattle_speed 3\n2 -\n3 G\n1 -\n1 G\n") {
+      press_group_toggle(&input, &mut cooldown, on_clicks.iter_mut());
+      groups.push(on_clicks.iter().all(is_group));
+    }
+    assert_eq!(groups, vec![false, false, true, true, true, true, false]);
+  }
+}
@@ -1,17 +1,24 @@
 use ggez::graphics::{spritebatch, Image, DrawParam, Rect, draw, Color};
-use ggez::nalgebra::Point2;
+use ggez::nalgebra::{Point2, Vector2};
 use ggez::{Context, GameResult};
 use ggez::timer::ticks;
 use crate::GameMode;
-use crate::battle::action::{ActionParameters, DamageType};
+use crate::battle::action::ActionParameters;
+use crate::battle::encounter::Encounter;
 use crate::battle::state::BattleState;
+use crate::battle::turn::{self, ActionOutcome};
 use crate::party::InventoryElement;
 use crate::party::character_info::CharacterInfo;
+use crate::party::equipment::{Equipment, Equipped, Slot};
 use crate::menu::MenuScreen;
 use crate::menu::item::{MenuItem, OnClickEvent};
 use crate::menu::notification::Notification;
+use crate::random::GameRng;
 use crate::transition::{Transition, TransitionStyle};
 use crate::data;
+use crate::data::content::GrowthDefinition;
+
+const SPRITE_WIDTH: f32 = 64.; // One frame of the sprite sheet
 
 pub enum Animation {
   StartTurn, // 12 frames
@@ -22,8 +29,9 @@ pub enum Animation {
   Flee // 80 frames
 }
 
+#[derive(Clone, Copy)]
 pub enum Sprite {
-  _StandLeft,
+  StandLeft,
   WalkLeft,
   StandRight,
   WalkRight,
@@ -36,6 +44,20 @@ pub enum Sprite {
   Victory
 }
 
+impl Sprite {
+  // The same pose facing the other way, poses without a left facing row are flipped when drawn
+  fn mirrored(self) -> (Sprite, bool) {
+    match self {
+      Sprite::StandLeft  => (Sprite::StandRight, false),
+      Sprite::WalkLeft   => (Sprite::WalkRight, false),
+      Sprite::StandRight => (Sprite::StandLeft, false),
+      Sprite::WalkRight  => (Sprite::WalkLeft, false),
+      Sprite::Dead | Sprite::Attack | Sprite::Victory => (self, true),
+      _ => (self, false)
+    }
+  }
+}
+
 pub struct Character {
   spritebatch: spritebatch::SpriteBatch,
   pub avatar_spritefile: String,
@@ -43,12 +65,32 @@ pub struct Character {
   pub animation: (Animation, usize, usize), // (Animation, length, starting tick)
   pub sprite: Sprite,
   frame: f32,
-  pub x_offset: f32,
+  pub x_offset: f32, // Towards the enemies, negative in the back row
+  battle_x: f32, // Where the offset counts from, set by the encounter
+  mirrored: bool, // Faces left in a back attack
+  swapped_row: bool, // A back attack swapped the row for the battle
   pub name: String,
   pub state: BattleState,
   attack_ability: (String, OnClickEvent),
   primary_ability: (String, OnClickEvent),
-  secondary_ability: (String, OnClickEvent)
+  secondary_ability: (String, OnClickEvent),
+  pub growth: GrowthDefinition,
+  pub spells: Vec<String>,
+  pub equipped: Equipped,
+  pub forced_turn: bool // A status effect picks this turn's action instead of the battle menu
+}
+
+// Levels and stats gained after a battle
+pub struct LevelUp {
+  pub from_level: u8,
+  pub to_level: u8,
+  pub hp: u16,
+  pub mp: u16,
+  pub attack: u16,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8
 }
 
 impl Character {
@@ -78,6 +120,7 @@ impl Character {
     if back_row {
       x_offset -= 50.;
     }
+    let (battle_x, mirrored) = Encounter::Normal.get_party_position();
     Character {
       spritebatch: batch,
       avatar_spritefile,
@@ -86,25 +129,134 @@ impl Character {
       sprite: Sprite::StandRight,
       frame: 0.,
       x_offset,
+      battle_x,
+      mirrored,
+      swapped_row: false,
       name,
-      state: BattleState::new(id, level, hp, mp, attack, defence, magic, resistance, agility, 0, 0, 0, back_row, None, None, Some(character_info)),
+      state: BattleState::new(id, level, hp, mp, attack, defence, magic, resistance, agility, 0, Vec::new(), Vec::new(), back_row, None, None, Some(character_info)),
       attack_ability,
       primary_ability,
-      secondary_ability
+      secondary_ability,
+      growth: GrowthDefinition::default(),
+      spells: Vec::new(),
+      equipped: Equipped::default(),
+      forced_turn: false
+    }
+  }
+
+  // Back row characters stand further from the enemies
+  pub fn change_row(&mut self) {
+    self.state.back_row = !self.state.back_row;
+    match self.state.back_row {
+      true  => self.x_offset -= 50.,
+      false => self.x_offset += 50.
+    }
+  }
+
+  // Where the character stands and how full the gauge starts for the way the battle started
+  pub fn start_battle(&mut self, encounter: Encounter) {
+    let (battle_x, mirrored) = encounter.get_party_position();
+    self.battle_x = battle_x;
+    self.mirrored = mirrored;
+    if encounter.start_party_member(&mut self.state) {
+      self.change_row();
+      self.swapped_row = true;
     }
   }
 
+  // Effects that only last a battle wear off and a row swapped by a back attack goes back
+  pub fn end_battle(&mut self, ctx: &mut Context) -> GameResult<()> {
+    if self.swapped_row {
+      self.change_row();
+      self.swapped_row = false;
+    }
+    self.state.end_battle(ctx)
+  }
+
+  // Top left corner of the sprite in battle
+  pub fn get_screen_pos(&self) -> (f32, f32) {
+    let x = match self.mirrored {
+      true  => self.battle_x - self.x_offset,
+      false => self.battle_x + self.x_offset
+    };
+    (x, 50. + self.state.id as f32 * 66.)
+  }
+
+  // Puts the equipment on, or takes the slot's equipment off, and returns what was worn
+  pub fn equip(&mut self, slot: Slot, equipment: Option<Equipment>) -> Option<Equipment> {
+    let worn = self.equipped.replace(slot, equipment);
+    self.state.equipment = self.equipped.get_bonus();
+    worn
+  }
+
+  // Own spells followed by the ones the equipment teaches
+  pub fn get_spells(&self) -> Vec<String> {
+    let mut spells = self.spells.clone();
+    for spell in self.state.equipment.spells.iter() {
+      if !spells.contains(spell) {
+        spells.push(spell.to_owned());
+      }
+    }
+    spells
+  }
+
+  pub fn can_level_up(&self) -> bool {
+    match self.growth.experience_for_level(self.state.level.saturating_add(1)) {
+      Some(experience) => !self.name.is_empty() && self.state.experience >= experience,
+      None => false
+    }
+  }
+
+  // Raises the level as far as the experience reaches, None if it doesn't reach the next one
+  pub fn level_up(&mut self, ctx: &mut Context) -> Option<LevelUp> {
+    if !self.can_level_up() {
+      return None;
+    }
+    let mut level_up = LevelUp {from_level: self.state.level, to_level: self.state.level, hp: 0, mp: 0, attack: 0, defence: 0, magic: 0, resistance: 0, agility: 0};
+    while self.can_level_up() {
+      let level = self.state.level + 1;
+      level_up.hp         = level_up.hp        .saturating_add(GrowthDefinition::gain(&self.growth.hp, level));
+      level_up.mp         = level_up.mp        .saturating_add(GrowthDefinition::gain(&self.growth.mp, level));
+      level_up.attack     = level_up.attack    .saturating_add(GrowthDefinition::gain(&self.growth.attack, level));
+      level_up.defence    = level_up.defence   .saturating_add(GrowthDefinition::gain(&self.growth.defence, level));
+      level_up.magic      = level_up.magic     .saturating_add(GrowthDefinition::gain(&self.growth.magic, level));
+      level_up.resistance = level_up.resistance.saturating_add(GrowthDefinition::gain(&self.growth.resistance, level));
+      level_up.agility    = level_up.agility   .saturating_add(GrowthDefinition::gain(&self.growth.agility, level));
+      self.state.level = level;
+    }
+    level_up.to_level = self.state.level;
+    let state = &mut self.state;
+    state.max_hp     = state.max_hp    .saturating_add(level_up.hp);
+    state.max_mp     = state.max_mp    .saturating_add(level_up.mp);
+    state.attack     = state.attack    .saturating_add(level_up.attack);
+    state.defence    = state.defence   .saturating_add(level_up.defence);
+    state.magic      = state.magic     .saturating_add(level_up.magic);
+    state.resistance = state.resistance.saturating_add(level_up.resistance);
+    state.agility    = state.agility   .saturating_add(level_up.agility);
+    if state.hp > 0 {
+      state.hp = state.hp.saturating_add(level_up.hp);
+    }
+    state.mp = state.mp.saturating_add(level_up.mp);
+    let mut info = CharacterInfo::new(ctx, state.id, &self.name, state.max_hp, state.max_mp);
+    info.hp.text = format!("{}/", state.hp);
+    info.mp.text = format!("{}/", state.mp);
+    if let Some(old_info) = state.character_info.take() {
+      info.status_effects = old_info.status_effects;
+    }
+    state.character_info = Some(info);
+    Some(level_up)
+  }
+
   pub fn update(
     &mut self,
     ctx: &mut Context,
     battle_menu: &mut MenuScreen,
-    active_turns: &mut Vec<u8>,
     current_turn: &mut u8,
     notification: &mut Option<Notification>,
     transition: &mut Transition
   ) -> GameResult<()> {
     if self.name.len() > 0 {
-      self.state.update(current_turn, active_turns)?;
+      self.state.update()?;
       if *current_turn == self.state.id && !self.state.turn_active {
         self.state.turn_active = true;
         self.sprite = Sprite::StandRight;
@@ -141,13 +293,16 @@ impl Character {
           match self.animation.0 {
             Animation::StartTurn => {
               self.sprite = Sprite::StandRight;
-              *battle_menu = data::menus::battle_main(ctx, self, (0, 0));
+              match self.state.get_forced_action() {
+                Some(_) => self.forced_turn = true,
+                None => *battle_menu = data::menus::battle_main(ctx, self, (0, 0))
+              }
             },
             Animation::EndTurn => {
               self.sprite = Sprite::StandRight;
               self.state.turn_active = false;
               *current_turn = 0;
-              self.state.end_turn(ctx, notification, &self.name, (200. + self.x_offset, 50. + self.state.id as f32 * 66.))?;
+              self.state.end_turn(ctx, notification, &self.name, self.get_screen_pos())?;
               if self.state.hp == 0 {
                 self.animation = (Animation::Dead, 20, ticks(ctx));
                 *notification = Some(Notification::new(ctx, format!("{} dead", self.name)));
@@ -182,38 +337,26 @@ impl Character {
   pub fn receive_battle_action(
     &mut self,
     ctx: &mut Context,
-    inventory: &mut Vec<InventoryElement>,
+    inventory: &mut [InventoryElement],
+    rng: &mut GameRng,
     notification: &mut Option<Notification>,
-    action_parameters: &mut ActionParameters,
+    action_parameters: &ActionParameters
   ) -> GameResult<()> {
-    match &action_parameters.damage_type {
-      DamageType::None(action) => self.state.receive_none_type_action(ctx, inventory, action_parameters, *action, notification),
-      DamageType::Item(used_item) => {
-        *notification = Some(Notification::new(ctx, used_item.get_name()));
-        let position = self.state.get_damage_position((200. + self.x_offset, 50. + self.state.id as f32 * 66.));
-        for inventory_element in inventory {
-          match inventory_element {
-            InventoryElement::Item(item, amount) => {
-              if used_item.get_name() == item.get_name() && *amount > 0 {
-                *amount -= 1;
-              }
-            }
-          }
-        }
-        used_item.apply_item_effect(ctx, &mut self.state, position)
-      },
-      DamageType::Healing => self.state.receive_healing(ctx, action_parameters, (200. + self.x_offset, 50. + self.state.id as f32 * 66.)),
-      _ => {
-        self.animation = (Animation::Hurt, 60, ticks(ctx));
-        self.state.receive_damage(ctx, notification, &self.name, action_parameters, (200. + self.x_offset, 50. + self.state.id as f32 * 66.))
-      }
+    let outcome = turn::apply_action(rng, inventory, &mut self.state, action_parameters);
+    if let ActionOutcome::Damage(_) = outcome {
+      self.animation = (Animation::Hurt, 60, ticks(ctx));
     }
+    self.state.show_outcome(ctx, notification, &self.name, action_parameters, &outcome, self.get_screen_pos())
   }
 
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
     if self.name.len() > 0 {
-      let (spritesheet_x, spritesheet_y, anim_loop_len) = match self.sprite {
-        Sprite::_StandLeft  => (0., 1., 1.),
+      let (sprite, flipped) = match self.mirrored {
+        true  => self.sprite.mirrored(),
+        false => (self.sprite, false)
+      };
+      let (spritesheet_x, spritesheet_y, anim_loop_len) = match sprite {
+        Sprite::StandLeft  => (0., 1., 1.),
         Sprite::WalkLeft   => (0., 1., 3.),
         Sprite::StandRight => (0., 2., 1.),
         Sprite::WalkRight  => (0., 2., 3.),
@@ -229,8 +372,11 @@ impl Character {
         .src(Rect::new(0.333333333333333 * (spritesheet_x + (self.frame % anim_loop_len)), 0.2 * spritesheet_y, 0.333333333333333, 0.2))
         .color(Color::new(1., 1., 1., self.opacity));
       self.spritebatch.add(p);
-      let param = DrawParam::new()
-        .dest(Point2::new(200. + self.x_offset, 50. + self.state.id as f32 * 66.));
+      let (x, y) = self.get_screen_pos();
+      let param = match flipped {
+        true  => DrawParam::new().dest(Point2::new(x + SPRITE_WIDTH, y)).scale(Vector2::new(-1., 1.)),
+        false => DrawParam::new().dest(Point2::new(x, y))
+      };
       draw(ctx, &self.spritebatch, param)?;
       self.spritebatch.clear();
       self.state.draw(ctx)?;
@@ -238,10 +384,32 @@ impl Character {
     Ok(())
   }
 
+  // Brings a fallen character back with 1 HP
+  pub fn revive(&mut self) -> GameResult<()> {
+    if self.name.is_empty() || self.state.hp > 0 {
+      return Ok(());
+    }
+    self.state.hp = 1;
+    self.sprite = Sprite::StandRight;
+    self.opacity = 1.;
+    if let Some(info) = &mut self.state.character_info {
+      info.hp.text = format!("{}/", self.state.hp);
+    }
+    Ok(())
+  }
+
   pub fn get_avatar(&self) -> String {
     self.avatar_spritefile.to_owned()
   }
 
+  // What the attack command does, used when a status effect makes the character attack on their own
+  pub fn get_attack_parameters(&self) -> Option<ActionParameters> {
+    match &self.attack_ability.1 {
+      OnClickEvent::ToTargetSelection(_, action_parameters, _) => Some(action_parameters.clone()),
+      _ => None
+    }
+  }
+
   pub fn get_attack_ability(&self, ctx: &mut Context) -> MenuItem {
     MenuItem::new(ctx, "".to_owned(), self.attack_ability.0.to_owned(), (55., 440.), 24., self.attack_ability.1.clone())
   }
@@ -51,13 +51,10 @@ impl CharacterInfo {
   }
 
   pub fn remove_effect(&mut self, effect_name: String) -> GameResult<()> {
-    let mut index = 0;
-    for (i, effect) in self.status_effects.iter_mut().enumerate() {
-      if effect.0 == effect_name {
-        index = i;
-        break;
-      }
-    }
+    let index = match self.status_effects.iter().position(|effect| effect.0 == effect_name) {
+      Some(index) => index,
+      None => return Ok(())
+    };
     self.status_effects.remove(index);
     for (i, effect) in self.status_effects.iter_mut().enumerate() {
       if i >= index {
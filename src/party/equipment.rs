@@ -0,0 +1,129 @@
+use std::cmp::{min, max};
+use crate::battle::element::Affinity;
+use crate::battle::status_effect::{StatusEffect, Stat};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Slot {
+  Weapon,
+  Armour,
+  Accessory
+}
+
+pub const SLOTS: [Slot; 3] = [Slot::Weapon, Slot::Armour, Slot::Accessory];
+
+impl Slot {
+  pub fn from_name(name: &str) -> Option<Slot> {
+    match name {
+      "weapon"    => Some(Slot::Weapon),
+      "armour"    => Some(Slot::Armour),
+      "accessory" => Some(Slot::Accessory),
+      _ => None
+    }
+  }
+
+  pub fn get_name(&self) -> &'static str {
+    match self {
+      Slot::Weapon    => "Weapon",
+      Slot::Armour    => "Armour",
+      Slot::Accessory => "Accessory"
+    }
+  }
+}
+
+// What a combatant's equipment adds on top of its own stats, counted by BattleState
+#[derive(Clone, Default)]
+pub struct EquipmentBonus {
+  pub attack: i16,
+  pub defence: i16,
+  pub magic: i16,
+  pub resistance: i16,
+  pub agility: i16,
+  pub affinities: Vec<Affinity>,
+  pub immunities: Vec<StatusEffect>,
+  pub spells: Vec<String>, // Added to the wearer's Magic menu
+  pub preemptive: f32,
+  pub alert: bool
+}
+
+impl EquipmentBonus {
+  pub fn get(&self, stat: Stat) -> i16 {
+    match stat {
+      Stat::Attack     => self.attack,
+      Stat::Defence    => self.defence,
+      Stat::Magic      => self.magic,
+      Stat::Resistance => self.resistance,
+      Stat::Agility    => self.agility,
+      Stat::Accuracy   => 0
+    }
+  }
+
+  // A stat with the bonus, which never goes below 0
+  pub fn apply(&self, stat: Stat, value: u16) -> u16 {
+    min(max(value as i32 + self.get(stat) as i32, 0), u16::MAX as i32) as u16
+  }
+
+  fn add(&mut self, other: &EquipmentBonus) {
+    self.attack     += other.attack;
+    self.defence    += other.defence;
+    self.magic      += other.magic;
+    self.resistance += other.resistance;
+    self.agility    += other.agility;
+    self.affinities.extend(other.affinities.iter().copied());
+    self.immunities.extend(other.immunities.iter().copied());
+    self.spells.extend(other.spells.iter().cloned());
+    self.preemptive += other.preemptive;
+    self.alert |= other.alert;
+  }
+}
+
+#[derive(Clone)]
+pub struct Equipment {
+  pub name: String,
+  pub slot: Slot,
+  pub price: u32, // 0 can't be sold
+  pub bonus: EquipmentBonus
+}
+
+impl Equipment {
+  pub fn get_sell_price(&self) -> u32 {
+    self.price / 2
+  }
+}
+
+// What a character wears, one piece per slot
+#[derive(Clone, Default)]
+pub struct Equipped {
+  weapon: Option<Equipment>,
+  armour: Option<Equipment>,
+  accessory: Option<Equipment>
+}
+
+impl Equipped {
+  pub fn get(&self, slot: Slot) -> Option<&Equipment> {
+    match slot {
+      Slot::Weapon    => self.weapon.as_ref(),
+      Slot::Armour    => self.armour.as_ref(),
+      Slot::Accessory => self.accessory.as_ref()
+    }
+  }
+
+  // Puts the equipment in its slot, or empties the slot, and returns what was there
+  pub fn replace(&mut self, slot: Slot, equipment: Option<Equipment>) -> Option<Equipment> {
+    let worn = match slot {
+      Slot::Weapon    => &mut self.weapon,
+      Slot::Armour    => &mut self.armour,
+      Slot::Accessory => &mut self.accessory
+    };
+    std::mem::replace(worn, equipment)
+  }
+
+  pub fn get_bonus(&self) -> EquipmentBonus {
+    let mut bonus = EquipmentBonus::default();
+    for slot in SLOTS.iter() {
+      if let Some(equipment) = self.get(*slot) {
+        bonus.add(&equipment.bonus);
+      }
+    }
+    bonus
+  }
+}
\ No newline at end of file
@@ -30,21 +30,47 @@ impl InventoryItem {
     }
   }
 
+  pub fn from_name(name: &str) -> Option<InventoryItem> {
+    match name {
+      "Potion" => Some(InventoryItem::new(ItemVariant::Potion)),
+      "Ether"  => Some(InventoryItem::new(ItemVariant::Ether)),
+      _ => None
+    }
+  }
+
+  // Returns how much HP or MP was restored
+  pub fn apply_to_state(&self, target: &mut BattleState) -> u16 {
+    match self.variant {
+      ItemVariant::Potion => {
+        target.hp = min(target.hp.saturating_add(100), target.max_hp);
+        100
+      },
+      ItemVariant::Ether => {
+        target.mp = min(target.mp.saturating_add(25), target.max_mp);
+        25
+      }
+    }
+  }
+
   pub fn apply_item_effect(&self, ctx: &mut Context, target: &mut BattleState, position: (f32, f32)) -> GameResult<()> { // negative position.0 mean no battle
-    let (print_damage, print_color) = match self.variant {
+    let restored = self.apply_to_state(target);
+    self.show_item_effect(ctx, target, restored, position)
+  }
+
+  // Updates the target's info and prints the amount restored, see apply_to_state
+  pub fn show_item_effect(&self, ctx: &mut Context, target: &mut BattleState, restored: u16, position: (f32, f32)) -> GameResult<()> {
+    let print_color = match self.variant {
       ItemVariant::Potion => {
-        target.hp = min(target.hp + 100, target.max_hp);
         if let Some(info) = &mut target.character_info {info.hp.text = format!("{}/", target.hp);}
-        (100, Color::new(0., 1., 0., 1.))
+        Color::new(0., 1., 0., 1.)
       },
       ItemVariant::Ether => {
-        target.mp = min(target.mp + 25, target.max_mp);
         if let Some(info) = &mut target.character_info {info.mp.text = format!("{}/", target.mp);}
-        (25, Color::new(0., 0., 1., 1.))
+        Color::new(0., 0., 1., 1.)
       }
     };
     if position.0 >= 0. {
-      target.print_damage = Some(PrintDamage::new(ctx, print_damage, target.get_damage_position(position), print_color));
+      target.print_damage = Some(PrintDamage::new(ctx, restored, target.get_damage_position(position), print_color));
     }
     Ok(())
   }
@@ -56,6 +82,18 @@ impl InventoryItem {
     }
   }
 
+  // What shops ask when their own list doesn't set a price
+  pub fn get_price(&self) -> u32 {
+    match self.variant {
+      ItemVariant::Potion => 30,
+      ItemVariant::Ether  => 120
+    }
+  }
+
+  pub fn get_sell_price(&self) -> u32 {
+    self.get_price() / 2
+  }
+
   pub fn get_to_menu_click_event(&self, item_cursor_pos: (usize, usize)) -> OnClickEvent {
     fn select_single_target(ctx: &mut Context, _mode: &mut GameMode, party: &mut Party, _enemies: &Vec<Vec<Enemy>>, item_cursor_pos: (usize, usize)) -> MenuScreen {
       fn use_item(
@@ -87,7 +125,8 @@ impl InventoryItem {
                   }
                 }
               }
-            }
+            },
+            InventoryElement::Equipment(_, _) => ()
           }
         }
         if out_of_selected_item {
@@ -115,9 +154,7 @@ impl InventoryItem {
       }
       let mut unselectables = Vec::new();
       for (index, element) in party.inventory.iter_mut().enumerate() {
-        let (element_name, element_amount) = match element {
-          InventoryElement::Item(item, amount) => (item.get_name(), amount)
-        };
+        let (element_name, element_amount) = (element.get_name(), element.get_amount());
         let item_height = (index / 2) as f32 * 24. + 50.;
         if item_cursor_pos.0 == index % 2 && item_cursor_pos.1 == index / 2 {
           unselectables.push(MenuItem::new(ctx, "".to_owned(), format!("{} x{}", element_name, element_amount), (590., 90.), 24., OnClickEvent::None));
@@ -146,17 +183,11 @@ impl InventoryItem {
   }
 
   pub fn get_target_selection_click_event(&self, item_index: usize) -> OnClickEvent {
-    match self.variant {
-      ItemVariant::Potion => OnClickEvent::ToTargetSelection(
-        menus::to_target_selection,
-        ActionParameters::new(DamageType::Item(self.clone()), 0, 0., false, 0., false, 0., false),
-        (0, item_index)
-      ),
-      ItemVariant::Ether => OnClickEvent::ToTargetSelection(
-        menus::to_target_selection,
-        ActionParameters::new(DamageType::Item(self.clone()), 0, 0., false, 0., false, 0., false),
-        (0, item_index)
-      )
-    }
+    OnClickEvent::ToTargetSelection(menus::to_target_selection, self.get_action_parameters(), (0, item_index))
   }
+
+  pub fn get_action_parameters(&self) -> ActionParameters {
+    ActionParameters::new(DamageType::Item(self.clone()), 0, 0., false, Vec::new(), Vec::new())
+  }
+
 }
\ No newline at end of file
@@ -1,20 +1,69 @@
 use ggez::{Context, GameResult};
 use ggez::timer::ticks;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cmp::min;
 pub mod character_info;
+pub mod equipment;
 pub mod character;
 use character::{Character, Animation, Sprite};
 pub mod item;
 use item::{InventoryItem, ItemVariant};
-use crate::battle::Battle;
+use equipment::{Equipment, Slot};
+use crate::battle::{Battle, Rewards};
 use crate::battle::action::ActionParameters;
+use crate::battle::damage::DamageFormula;
+use crate::battle::encounter::Encounter;
+use crate::battle::turn;
+use crate::battle::status_effect::ForcedAction;
 use crate::menu::MenuScreen;
 use crate::menu::notification::Notification;
 use crate::GameMode;
 use crate::transition::{Transition, TransitionStyle};
 use crate::data::{characters, menus};
+use crate::data::content::GameContent;
+use crate::random::{self, GameRng};
+use crate::save::settings::Settings;
 
 pub enum InventoryElement {
-  Item(InventoryItem, u8)
+  Item(InventoryItem, u8),
+  Equipment(Equipment, u8) // Spare pieces, the worn ones are on the characters
+}
+
+impl InventoryElement {
+  pub fn get_name(&self) -> String {
+    match self {
+      InventoryElement::Item(item, _) => item.get_name(),
+      InventoryElement::Equipment(equipment, _) => equipment.name.to_owned()
+    }
+  }
+
+  pub fn get_amount(&self) -> u8 {
+    match self {
+      InventoryElement::Item(_, amount) | InventoryElement::Equipment(_, amount) => *amount
+    }
+  }
+
+  pub fn get_amount_mut(&mut self) -> &mut u8 {
+    match self {
+      InventoryElement::Item(_, amount) | InventoryElement::Equipment(_, amount) => amount
+    }
+  }
+
+  pub fn get_sell_price(&self) -> u32 {
+    match self {
+      InventoryElement::Item(item, _) => item.get_sell_price(),
+      InventoryElement::Equipment(equipment, _) => equipment.get_sell_price()
+    }
+  }
+
+  // The same thing in another amount
+  pub fn with_amount(&self, amount: u8) -> InventoryElement {
+    match self {
+      InventoryElement::Item(item, _) => InventoryElement::Item(item.clone(), amount),
+      InventoryElement::Equipment(equipment, _) => InventoryElement::Equipment(equipment.clone(), amount)
+    }
+  }
 }
 
 pub struct Party {
@@ -22,36 +71,179 @@ pub struct Party {
   pub second: Character,
   pub third:  Character,
   pub fourth: Character,
-  pub inventory: Vec<InventoryElement>
+  pub inventory: Vec<InventoryElement>,
+  pub flags: HashMap<String, i32>, // Story flags and variables set by scripts
+  pub rng: GameRng,
+  pub damage_formula: Rc<dyn DamageFormula>, // From combat.toml, used by both sides of a battle
+  pub content: Rc<GameContent>,
+  pub settings: Settings,
+  pub gold: u32
+}
+
+pub const STARTING_PARTY: [&str; 2] = ["Darrel", "Seraphine"];
+pub const ITEM_STACK_LIMIT: u8 = 99;
+pub const MAX_GOLD: u32 = 999_999;
+
+pub fn starting_inventory() -> Vec<InventoryElement> {
+  vec![ // Must have value 0 or more for every item
+    InventoryElement::Item(InventoryItem::new(ItemVariant::Potion), 2),
+    InventoryElement::Item(InventoryItem::new(ItemVariant::Ether) , 1)
+  ]
 }
 
 impl Party {
-  pub fn new(ctx: &mut Context) -> Party {
-    Party {
-      first:  characters::darrel_deen(ctx, 1),
-      second: characters::nurse_seraphine(ctx, 2),
+  pub fn new(ctx: &mut Context, seed: u64, settings: Settings, damage_formula: Rc<dyn DamageFormula>, content: Rc<GameContent>) -> GameResult<Party> {
+    Ok(Party {
+      first:  characters::get_character(ctx, &content, 1, STARTING_PARTY[0])?,
+      second: characters::get_character(ctx, &content, 2, STARTING_PARTY[1])?,
       third:  characters::none_character(ctx, 3),
       fourth: characters::none_character(ctx, 4),
-      inventory: vec![ // Must have value 0 or more for every item
-        InventoryElement::Item(InventoryItem::new(ItemVariant::Potion), 2),
-        InventoryElement::Item(InventoryItem::new(ItemVariant::Ether) , 1)
-      ]
+      inventory: starting_inventory(),
+      flags: HashMap::new(),
+      rng: random::from_seed(seed),
+      damage_formula,
+      content,
+      settings,
+      gold: 0
+    })
+  }
+
+  // Anything above the stack limit is lost
+  pub fn add_to_inventory(&mut self, new_element: InventoryElement) {
+    let new_amount = new_element.get_amount();
+    match self.inventory.iter_mut().find(|inventory_element| inventory_element.get_name() == new_element.get_name()) {
+      Some(inventory_element) => {
+        let amount = inventory_element.get_amount_mut();
+        *amount = min(amount.saturating_add(new_amount), ITEM_STACK_LIMIT);
+      },
+      None => self.inventory.push(new_element.with_amount(min(new_amount, ITEM_STACK_LIMIT)))
+    }
+  }
+
+  pub fn add_item(&mut self, new_item: InventoryItem, new_amount: u8) {
+    self.add_to_inventory(InventoryElement::Item(new_item, new_amount));
+  }
+
+  pub fn add_gold(&mut self, amount: u32) {
+    self.gold = min(self.gold.saturating_add(amount), MAX_GOLD);
+  }
+
+  pub fn get_item_amount(&self, name: &str) -> u8 {
+    self.inventory.iter()
+      .filter(|inventory_element| inventory_element.get_name() == name)
+      .map(|inventory_element| inventory_element.get_amount())
+      .sum()
+  }
+
+  // Only buys as many as the stack has room for. Returns false without buying anything if there's no room or the party can't pay.
+  pub fn buy(&mut self, goods: &InventoryElement, amount: u8, price: u32) -> bool {
+    let amount = min(amount, ITEM_STACK_LIMIT.saturating_sub(self.get_item_amount(&goods.get_name())));
+    let cost = match price.checked_mul(amount as u32) {
+      Some(cost) if amount > 0 && cost <= self.gold => cost,
+      _ => return false
+    };
+    self.gold -= cost;
+    self.add_to_inventory(goods.with_amount(amount));
+    true
+  }
+
+  pub fn sell(&mut self, inventory_index: usize, amount: u8) {
+    if let Some(inventory_element) = self.inventory.get_mut(inventory_index) {
+      let sold = min(amount, inventory_element.get_amount());
+      *inventory_element.get_amount_mut() -= sold;
+      let earned = inventory_element.get_sell_price() * sold as u32;
+      self.add_gold(earned);
+    }
+  }
+
+  // Puts on the spare equipment at the inventory index, or just takes the slot's equipment off without one.
+  // What was worn goes back to the inventory.
+  pub fn equip(&mut self, id: u8, slot: Slot, inventory_index: Option<usize>) {
+    let equipment = match inventory_index.and_then(|index| self.inventory.get_mut(index)) {
+      Some(InventoryElement::Equipment(equipment, amount)) if *amount > 0 && equipment.slot == slot => {
+        *amount -= 1;
+        Some(equipment.clone())
+      },
+      Some(_) => return,
+      None => None
+    };
+    if let Some(worn) = self.get_member_mut(id).equip(slot, equipment) {
+      self.add_to_inventory(InventoryElement::Equipment(worn, 1));
+    }
+  }
+
+  pub fn heal(&mut self) -> GameResult<()> {
+    self.first .state.full_heal()?;
+    self.second.state.full_heal()?;
+    self.third .state.full_heal()?;
+    self.fourth.state.full_heal()?;
+    Ok(())
+  }
+
+  // Fallen members get up with 1 HP after a battle the party was allowed to lose
+  pub fn revive(&mut self) -> GameResult<()> {
+    self.first .revive()?;
+    self.second.revive()?;
+    self.third .revive()?;
+    self.fourth.revive()?;
+    Ok(())
+  }
+
+  pub fn start_battle(&mut self, encounter: Encounter) {
+    self.first .start_battle(encounter);
+    self.second.start_battle(encounter);
+    self.third .start_battle(encounter);
+    self.fourth.start_battle(encounter);
+  }
+
+  pub fn end_battle(&mut self, ctx: &mut Context) -> GameResult<()> {
+    self.first .end_battle(ctx)?;
+    self.second.end_battle(ctx)?;
+    self.third .end_battle(ctx)?;
+    self.fourth.end_battle(ctx)?;
+    Ok(())
+  }
+
+  pub fn can_level_up(&self) -> bool {
+    self.first.can_level_up() || self.second.can_level_up() || self.third.can_level_up() || self.fourth.can_level_up()
+  }
+
+  pub fn get_member(&self, id: u8) -> &Character {
+    match id {
+      1 => &self.first,
+      2 => &self.second,
+      3 => &self.third,
+      _ => &self.fourth
     }
   }
 
+  pub fn get_member_mut(&mut self, id: u8) -> &mut Character {
+    match id {
+      1 => &mut self.first,
+      2 => &mut self.second,
+      3 => &mut self.third,
+      _ => &mut self.fourth
+    }
+  }
+
+  pub fn get_character(&self, name: &str) -> Option<&Character> {
+    [&self.first, &self.second, &self.third, &self.fourth].iter()
+      .find(|character| !character.name.is_empty() && character.name == name)
+      .copied()
+  }
+
   pub fn update(
     &mut self,
     ctx: &mut Context,
     battle_menu: &mut MenuScreen,
-    active_turns: &mut Vec<u8>,
     current_turn: &mut u8,
     notification: &mut Option<Notification>,
     transition: &mut Transition
   ) -> GameResult<()> {
-    self.first .update(ctx, battle_menu, active_turns, current_turn, notification, transition)?;
-    self.second.update(ctx, battle_menu, active_turns, current_turn, notification, transition)?;
-    self.third .update(ctx, battle_menu, active_turns, current_turn, notification, transition)?;
-    self.fourth.update(ctx, battle_menu, active_turns, current_turn, notification, transition)?;
+    self.first .update(ctx, battle_menu, current_turn, notification, transition)?;
+    self.second.update(ctx, battle_menu, current_turn, notification, transition)?;
+    self.third .update(ctx, battle_menu, current_turn, notification, transition)?;
+    self.fourth.update(ctx, battle_menu, current_turn, notification, transition)?;
     Ok(())
   }
 
@@ -60,7 +252,7 @@ impl Party {
     ctx: &mut Context,
     menu: &mut MenuScreen,
     ending_tick: usize,
-    experience: &mut u32,
+    rewards: &mut Rewards,
     transition: &mut Transition
   ) -> GameResult<()> {
     if ticks(ctx) - ending_tick < 120 {
@@ -69,31 +261,58 @@ impl Party {
       if self.third .state.hp > 0 {self.third .sprite = Sprite::Victory}
       if self.fourth.state.hp > 0 {self.fourth.sprite = Sprite::Victory}
     } else if ticks(ctx) - ending_tick == 120 {
-      *menu = menus::battle_won(ctx, self, experience);
+      self.end_battle(ctx)?;
+      self.add_gold(rewards.gold);
+      for (item, amount) in rewards.items.iter() {
+        self.add_item(item.clone(), *amount);
+      }
+      *menu = menus::battle_won(ctx, self, rewards);
       transition.set(TransitionStyle::BlackInFast(GameMode::Menu))?;
     }
     Ok(())
   }
 
+  // Returns false without taking the turn when the active character can't pay the MP
   pub fn battle_turn_action(
     &mut self,
     ctx: &mut Context,
     battle: &mut Battle,
     target_pos: (usize, usize),
-    action_parameters: &mut ActionParameters
-  ) -> GameResult<()> {
-    let mut character = self.get_active();
+    action_parameters: &ActionParameters
+  ) -> GameResult<bool> {
+    if !self.get_active().state.spend_mp(action_parameters.mp_cost) {
+      battle.notification = Some(Notification::new(ctx, "Not enough MP".to_owned()));
+      return Ok(false);
+    }
+    // Random scopes roll their targets, so they're only picked once the action is sure to happen
+    let targets = battle.get_targets(self, target_pos, action_parameters);
+    let damage_formula = self.damage_formula.clone();
+    let character = self.get_active();
     character.animation = (Animation::Attack, 60, ticks(ctx));
     character.sprite = Sprite::Attack;
+    let action_parameters = turn::prepare_action(&character.state, &damage_formula, action_parameters, targets.len());
+    for target in targets {
+      self.act_on_position(ctx, battle, target, &action_parameters)?;
+    }
+    Ok(true)
+  }
+
+  fn act_on_position(
+    &mut self,
+    ctx: &mut Context,
+    battle: &mut Battle,
+    target_pos: (usize, usize),
+    action_parameters: &ActionParameters
+  ) -> GameResult<()> {
     match target_pos.0 {
       0 => match target_pos.1 {
-        0 => self.first .receive_battle_action(ctx, &mut self.inventory, &mut battle.notification, action_parameters),
-        1 => self.second.receive_battle_action(ctx, &mut self.inventory, &mut battle.notification, action_parameters),
-        2 => self.third .receive_battle_action(ctx, &mut self.inventory, &mut battle.notification, action_parameters),
-        _ => self.fourth.receive_battle_action(ctx, &mut self.inventory, &mut battle.notification, action_parameters),
+        0 => self.first .receive_battle_action(ctx, &mut self.inventory, &mut self.rng, &mut battle.notification, action_parameters),
+        1 => self.second.receive_battle_action(ctx, &mut self.inventory, &mut self.rng, &mut battle.notification, action_parameters),
+        2 => self.third .receive_battle_action(ctx, &mut self.inventory, &mut self.rng, &mut battle.notification, action_parameters),
+        _ => self.fourth.receive_battle_action(ctx, &mut self.inventory, &mut self.rng, &mut battle.notification, action_parameters),
       }
       _ => battle.enemies[target_pos.0 - 1][target_pos.1].receive_battle_action(
-        ctx, &mut self.inventory, &mut battle.notification, action_parameters, battle.enemies_start_draw_height
+        ctx, &mut self.inventory, &mut self.rng, &mut battle.notification, action_parameters, battle.enemies_start_draw_height
       )
     }
   }
@@ -106,6 +325,14 @@ impl Party {
     Ok(())
   }
 
+  // Target selection positions of the party members still standing
+  pub fn get_alive_positions(&self) -> Vec<(usize, usize)> {
+    [&self.first, &self.second, &self.third, &self.fourth].iter().enumerate()
+      .filter(|(_, character)| !character.name.is_empty() && character.state.hp > 0)
+      .map(|(index, _)| (0, index))
+      .collect()
+  }
+
   pub fn get_alive_size(&mut self) -> u32 {
     let mut party_size = 0;
     if self.first .name.len() > 0 && self.first .state.hp > 0 {party_size += 1;}
@@ -115,6 +342,23 @@ impl Party {
     party_size
   }
 
+  // The attack of the acting character when a status effect takes the turn out of the player's hands
+  pub fn take_forced_turn(&mut self, ctx: &mut Context) -> Option<(ForcedAction, ActionParameters)> {
+    let character = self.get_active();
+    if !character.state.turn_active || !character.forced_turn {
+      return None;
+    }
+    character.forced_turn = false;
+    match (character.state.get_forced_action(), character.get_attack_parameters()) {
+      (Some(forced_action), Some(action_parameters)) => Some((forced_action, action_parameters)),
+      _ => {
+        character.animation = (Animation::EndTurn, 12, ticks(ctx));
+        character.sprite = Sprite::WalkLeft;
+        None
+      }
+    }
+  }
+
   pub fn get_active(&mut self) -> &mut Character {
     if self.first.state.turn_active {
       return &mut self.first;
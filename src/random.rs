@@ -0,0 +1,18 @@
+use rand::{SeedableRng, random};
+use rand::rngs::StdRng;
+
+// Every random roll of the game goes through one of these, so a seed reproduces the same fights and encounters
+pub type GameRng = StdRng;
+
+pub fn new_seed() -> u64 {
+  random()
+}
+
+pub fn from_seed(seed: u64) -> GameRng {
+  StdRng::seed_from_u64(seed)
+}
+
+// A generator of its own for rolls that mustn't shift the party's, like wandering NPCs, the same name always gives the same rolls
+pub fn from_name(name: &str) -> GameRng {
+  from_seed(name.bytes().fold(0, |seed: u64, byte| seed.wrapping_mul(31).wrapping_add(byte as u64)))
+}
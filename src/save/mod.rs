@@ -0,0 +1,250 @@
+use ggez::{Context, GameResult, GameError};
+use ggez::filesystem;
+use serde_derive::{Serialize, Deserialize};
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use crate::party::{Party, InventoryElement};
+use crate::party::character::Character;
+use crate::party::character_info::CharacterInfo;
+use crate::party::item::InventoryItem;
+use crate::party::equipment::SLOTS;
+use crate::tilemap::Tilemap;
+use crate::data::{characters, equipment, tilemaps};
+use crate::data::content::GameContent;
+use crate::random;
+pub mod settings;
+
+pub const SAVE_VERSION: u32 = 1;
+pub const SAVE_SLOTS: u8 = 3;
+
+#[derive(Serialize, Deserialize)]
+pub struct CharacterSave {
+  pub name: String,
+  pub level: u8,
+  pub experience: u32,
+  pub max_hp: u16,
+  pub max_mp: u16,
+  pub hp: u16,
+  pub mp: u16,
+  pub attack: u16,
+  pub defence: u16,
+  pub magic: u16,
+  pub resistance: u16,
+  pub agility: u8,
+  pub back_row: bool,
+  #[serde(default)] pub equipment: Option<Vec<String>> // Worn pieces, saves from before equipment keep the starting ones
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InventorySave {
+  pub item: String,
+  pub amount: u8
+}
+
+// The party, inventory and gold without the map, also what a retried battle starts from
+pub struct PartySnapshot {
+  pub party: Vec<CharacterSave>,
+  pub inventory: Vec<InventorySave>,
+  pub gold: u32
+}
+
+impl PartySnapshot {
+  pub fn new(party: &Party) -> PartySnapshot {
+    let mut inventory = Vec::new();
+    for inventory_element in party.inventory.iter() {
+      inventory.push(InventorySave {item: inventory_element.get_name(), amount: inventory_element.get_amount()});
+    }
+    PartySnapshot {
+      party: vec![
+        character_save(&party.first),
+        character_save(&party.second),
+        character_save(&party.third),
+        character_save(&party.fourth)
+      ],
+      inventory,
+      gold: party.gold
+    }
+  }
+
+  pub fn restore(&self, ctx: &mut Context, party: &mut Party) -> GameResult<()> {
+    let content = party.content.clone();
+    party.first  = restore_character(ctx, &content, 1, self.party.first())?;
+    party.second = restore_character(ctx, &content, 2, self.party.get(1))?;
+    party.third  = restore_character(ctx, &content, 3, self.party.get(2))?;
+    party.fourth = restore_character(ctx, &content, 4, self.party.get(3))?;
+    party.gold   = self.gold;
+    for inventory_element in party.inventory.iter_mut() {
+      *inventory_element.get_amount_mut() = 0;
+    }
+    for inventory_save in self.inventory.iter() {
+      match party.inventory.iter_mut().find(|inventory_element| inventory_element.get_name() == inventory_save.item) {
+        Some(inventory_element) => *inventory_element.get_amount_mut() = inventory_save.amount,
+        None => {
+          let inventory_element = match InventoryItem::from_name(&inventory_save.item) {
+            Some(item) => Some(InventoryElement::Item(item, inventory_save.amount)),
+            None => equipment::get_equipment(&content, &inventory_save.item).map(|equipment| InventoryElement::Equipment(equipment, inventory_save.amount))
+          };
+          if let Some(inventory_element) = inventory_element {
+            party.inventory.push(inventory_element);
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+  pub version: u32, // Fields added after version 1 need #[serde(default)], so older saves keep loading
+  pub map: String,
+  pub position: (f32, f32),
+  pub party: Vec<CharacterSave>,
+  pub inventory: Vec<InventorySave>,
+  #[serde(default)] pub gold: u32,
+  #[serde(default)] pub flags: HashMap<String, i32>,
+  #[serde(default)] pub seed: Option<u64>, // Random rolls after loading continue from this seed
+  #[serde(default)] pub saved_at: u64 // Seconds since the Unix epoch, the newest slot is the last save
+}
+
+impl SaveFile {
+  pub fn new(party: &Party, map: &Tilemap, seed: u64) -> SaveFile {
+    let snapshot = PartySnapshot::new(party);
+    SaveFile {
+      version: SAVE_VERSION,
+      map: map.name.to_owned(),
+      position: map.get_player_position(),
+      party: snapshot.party,
+      inventory: snapshot.inventory,
+      gold: snapshot.gold,
+      flags: party.flags.clone(),
+      seed: Some(seed),
+      saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+    }
+  }
+
+  pub fn get_description(&self) -> String {
+    match self.party.iter().find(|character| !character.name.is_empty()) {
+      Some(leader) => format!("{} Lvl {}", leader.name, leader.level),
+      None => "Empty party".to_owned()
+    }
+  }
+}
+
+fn character_save(character: &Character) -> CharacterSave {
+  CharacterSave {
+    name: character.name.to_owned(),
+    level: character.state.level,
+    experience: character.state.experience,
+    max_hp: character.state.max_hp,
+    max_mp: character.state.max_mp,
+    hp: character.state.hp,
+    mp: character.state.mp,
+    attack: character.state.attack,
+    defence: character.state.defence,
+    magic: character.state.magic,
+    resistance: character.state.resistance,
+    agility: character.state.agility,
+    back_row: character.state.back_row,
+    equipment: Some(SLOTS.iter().filter_map(|slot| character.equipped.get(*slot)).map(|equipment| equipment.name.to_owned()).collect())
+  }
+}
+
+// Empty party slots are saved with an empty name
+fn restore_character(ctx: &mut Context, content: &GameContent, id: u8, save: Option<&CharacterSave>) -> GameResult<Character> {
+  let save = match save {
+    Some(save) if !save.name.is_empty() => save,
+    _ => return Ok(characters::none_character(ctx, id))
+  };
+  let mut character = characters::get_character(ctx, content, id, &save.name)?;
+  character.state.level      = save.level;
+  character.state.experience = save.experience;
+  character.state.max_hp     = save.max_hp;
+  character.state.max_mp     = save.max_mp;
+  character.state.hp         = save.hp;
+  character.state.mp         = save.mp;
+  character.state.attack     = save.attack;
+  character.state.defence    = save.defence;
+  character.state.magic      = save.magic;
+  character.state.resistance = save.resistance;
+  character.state.agility    = save.agility;
+  character.state.back_row   = save.back_row;
+  if let Some(equipment_names) = &save.equipment {
+    for slot in SLOTS.iter() {
+      character.equip(*slot, None);
+    }
+    for equipment in equipment_names.iter().filter_map(|name| equipment::get_equipment(content, name)) {
+      character.equip(equipment.slot, Some(equipment));
+    }
+  }
+  character.x_offset = match save.back_row {
+    true  => -50.,
+    false => 0.
+  };
+  let mut info = CharacterInfo::new(ctx, id, &character.name, save.max_hp, save.max_mp);
+  info.hp.text = format!("{}/", save.hp);
+  info.mp.text = format!("{}/", save.mp);
+  character.state.character_info = Some(info);
+  Ok(character)
+}
+
+fn get_slot_path(slot: u8) -> String {
+  format!("/saves/slot{}.toml", slot)
+}
+
+pub fn read_save(ctx: &mut Context, slot: u8) -> GameResult<Option<SaveFile>> {
+  let path = get_slot_path(slot);
+  if !filesystem::exists(ctx, &path) {
+    return Ok(None);
+  }
+  let mut contents = String::new();
+  filesystem::open(ctx, &path)?.read_to_string(&mut contents)?;
+  let save: SaveFile = toml::from_str(&contents)?;
+  if save.version > SAVE_VERSION {
+    return Err(GameError::ResourceLoadError(format!("Save slot {} has unsupported version {}", slot, save.version)));
+  }
+  Ok(Some(save))
+}
+
+pub fn save_game(ctx: &mut Context, slot: u8, party: &mut Party, map: &Tilemap) -> GameResult<()> {
+  // Reseeding on save makes the game continue the same way whether or not the save gets loaded
+  let seed = party.rng.gen();
+  party.rng = random::from_seed(seed);
+  let contents = toml::to_string(&SaveFile::new(party, map, seed))?;
+  if !filesystem::is_dir(ctx, "/saves") {
+    filesystem::create_dir(ctx, "/saves")?;
+  }
+  filesystem::create(ctx, get_slot_path(slot))?.write_all(contents.as_bytes())?;
+  Ok(())
+}
+
+pub fn load_game(ctx: &mut Context, slot: u8, party: &mut Party, map: &mut Tilemap) -> GameResult<()> {
+  let save = match read_save(ctx, slot)? {
+    Some(save) => save,
+    None => return Err(GameError::ResourceLoadError(format!("Save slot {} is empty", slot)))
+  };
+  let mut new_map = tilemaps::get_tilemap(ctx, &save.map)?;
+  if new_map.is_free(save.position) { // A wandering NPC may start where the party saved, the map's spawn point stands in then
+    new_map.place_player(save.position)?;
+  }
+  *map = new_map;
+  PartySnapshot {party: save.party, inventory: save.inventory, gold: save.gold}.restore(ctx, party)?;
+  party.flags  = save.flags;
+  if let Some(seed) = save.seed {
+    party.rng = random::from_seed(seed);
+  }
+  Ok(())
+}
+
+// Slot of the newest readable save, older saves without a time count as oldest
+pub fn get_last_slot(ctx: &mut Context) -> Option<u8> {
+  (1..=SAVE_SLOTS)
+    .filter_map(|slot| match read_save(ctx, slot) {
+      Ok(Some(save)) => Some((save.saved_at, slot)),
+      _ => None
+    })
+    .max()
+    .map(|(_, slot)| slot)
+}
\ No newline at end of file
@@ -0,0 +1,85 @@
+use ggez::{Context, GameResult};
+use ggez::filesystem;
+use serde_derive::{Serialize, Deserialize};
+use std::io::{Read, Write};
+
+const SETTINGS_PATH: &str = "/settings.toml";
+pub const BATTLE_SPEEDS: u8 = 5;
+
+// Active keeps the gauges filling while a command is picked, Wait stops them inside submenus and target selection
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum AtbMode {
+  #[default] Active,
+  Wait
+}
+
+impl AtbMode {
+  pub fn from_name(name: &str) -> Option<AtbMode> {
+    match name {
+      "Active" => Some(AtbMode::Active),
+      "Wait"   => Some(AtbMode::Wait),
+      _ => None
+    }
+  }
+
+  pub fn get_name(self) -> &'static str {
+    match self {
+      AtbMode::Active => "Active",
+      AtbMode::Wait   => "Wait"
+    }
+  }
+}
+
+// Changed from the Config menu, one press steps through the values
+#[derive(Clone, Copy)]
+pub enum Setting {
+  AtbMode,
+  BattleSpeed
+}
+
+// Shared by every save slot
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Settings {
+  #[serde(default)] pub atb_mode: AtbMode,
+  #[serde(default = "default_battle_speed")] pub battle_speed: u8 // 1 is the slowest, BATTLE_SPEEDS the fastest
+}
+
+fn default_battle_speed() -> u8 {
+  3
+}
+
+impl Default for Settings {
+  fn default() -> Settings {
+    Settings {atb_mode: AtbMode::default(), battle_speed: default_battle_speed()}
+  }
+}
+
+impl Settings {
+  pub fn change(&mut self, setting: Setting) {
+    match setting {
+      Setting::AtbMode => self.atb_mode = match self.atb_mode {
+        AtbMode::Active => AtbMode::Wait,
+        AtbMode::Wait   => AtbMode::Active
+      },
+      Setting::BattleSpeed => self.battle_speed = self.battle_speed % BATTLE_SPEEDS + 1
+    }
+  }
+}
+
+// Defaults when the file doesn't exist yet, a speed out of range is clamped
+pub fn read_settings(ctx: &mut Context) -> GameResult<Settings> {
+  if !filesystem::exists(ctx, SETTINGS_PATH) {
+    return Ok(Settings::default());
+  }
+  let mut contents = String::new();
+  filesystem::open(ctx, SETTINGS_PATH)?.read_to_string(&mut contents)?;
+  let mut settings: Settings = toml::from_str(&contents)?;
+  settings.battle_speed = settings.battle_speed.clamp(1, BATTLE_SPEEDS);
+  Ok(settings)
+}
+
+pub fn write_settings(ctx: &mut Context, settings: &Settings) -> GameResult<()> {
+  let contents = toml::to_string(settings)?;
+  filesystem::create(ctx, SETTINGS_PATH)?.write_all(contents.as_bytes())?;
+  Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,374 @@
+// Scripts are plain text files in /scripts, run by talking to npcs or stepping on event tiles. One command per line:
+//
+//   # comment
+//   :label
+//   say Speaker: text          speaker is optional, party members get their avatar
+//   choice label: text         consecutive choices are shown together, picking one jumps to its label
+//   set flag [value]           value defaults to 1
+//   add variable amount
+//   if variable [op value] goto label    op is one of == != < > <= >=, without it the variable is tested for non-zero
+//   goto label
+//   give item [amount]         item or equipment
+//   heal
+//   battle formation_name [lose label]   with lose the party may fall, the script then jumps to label instead of game over
+//   shop shop_name             the script goes on once the shop is closed
+//   warp map x y
+//   end
+use ggez::{Context, GameResult, GameError};
+use ggez::filesystem;
+use ggez::event::KeyCode;
+use std::collections::HashMap;
+use std::io::Read;
+use std::rc::Rc;
+use crate::GameMode;
+use crate::globals::WINDOW_SIZE;
+use crate::input::Input;
+use crate::party::{Party, InventoryElement};
+use crate::party::item::InventoryItem;
+use crate::battle::Battle;
+use crate::battle::encounter::Encounter;
+use crate::menu::{MenuScreen, MenuMovement};
+use crate::menu::container::MenuContainer;
+use crate::menu::dialogue::Dialogue;
+use crate::menu::item::{MenuItem, OnClickEvent};
+use crate::tilemap::tile::Warp;
+use crate::transition::{Transition, TransitionStyle};
+use crate::data::{enemy_formations, equipment, menus};
+use crate::data::shops::{self, ShopScreen};
+
+const MAX_STEPS_PER_UPDATE: usize = 1000;
+
+#[derive(Clone)]
+pub enum Comparison {
+  Equal, NotEqual, Less, Greater, LessOrEqual, GreaterOrEqual
+}
+
+impl Comparison {
+  fn test(&self, a: i32, b: i32) -> bool {
+    match self {
+      Comparison::Equal          => a == b,
+      Comparison::NotEqual       => a != b,
+      Comparison::Less           => a < b,
+      Comparison::Greater        => a > b,
+      Comparison::LessOrEqual    => a <= b,
+      Comparison::GreaterOrEqual => a >= b
+    }
+  }
+}
+
+#[derive(Clone)]
+pub enum Command {
+  Say(String, String),
+  Choice(Vec<(usize, String)>), // (command index to jump to, text)
+  Set(String, i32),
+  Add(String, i32),
+  If(String, Comparison, i32, usize),
+  Goto(usize),
+  Give(String, u8),
+  Heal,
+  Battle(String, Option<usize>), // Command index to jump to after a loss, without it losing is game over
+  Shop(String),
+  Warp(Warp),
+  End
+}
+
+pub struct Script {
+  pub name: String,
+  pub commands: Vec<Command>
+}
+
+// Commands before label resolution, labels are still names here
+enum ParsedCommand {
+  Command(Command),
+  Choice(Vec<(String, String)>),
+  If(String, Comparison, i32, String),
+  Goto(String),
+  Battle(String, String)
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>, line_number: usize, default: Option<T>) -> Result<T, String> {
+  match word {
+    Some(word) => word.parse::<T>().map_err(|_| format!("line {}: {} is not a valid number", line_number, word)),
+    None => default.ok_or(format!("line {}: missing number", line_number))
+  }
+}
+
+fn parse_line(line: &str, line_number: usize) -> Result<ParsedCommand, String> {
+  let (keyword, rest) = match line.find(' ') {
+    Some(index) => (&line[..index], line[index + 1..].trim()),
+    None => (line, "")
+  };
+  let words: Vec<&str> = rest.split_whitespace().collect();
+  let command = match keyword {
+    "say" => match rest.find(':') {
+      Some(index) => Command::Say(rest[..index].trim().to_owned(), rest[index + 1..].trim().to_owned()),
+      None => Command::Say(String::new(), rest.to_owned())
+    },
+    "choice" => return match rest.find(':') {
+      Some(index) => Ok(ParsedCommand::Choice(vec![(rest[..index].trim().to_owned(), rest[index + 1..].trim().to_owned())])),
+      None => Err(format!("line {}: choice needs a label and a text separated by :", line_number))
+    },
+    "set" => match words.first() {
+      Some(flag) => Command::Set((*flag).to_owned(), parse_number(words.get(1).copied(), line_number, Some(1))?),
+      None => return Err(format!("line {}: set needs a flag", line_number))
+    },
+    "add" => match words.first() {
+      Some(variable) => Command::Add((*variable).to_owned(), parse_number(words.get(1).copied(), line_number, None)?),
+      None => return Err(format!("line {}: add needs a variable", line_number))
+    },
+    "if" => return match words.as_slice() {
+      [variable, "goto", label] => Ok(ParsedCommand::If((*variable).to_owned(), Comparison::NotEqual, 0, (*label).to_owned())),
+      [variable, comparison, value, "goto", label] => {
+        let comparison = match *comparison {
+          "==" => Comparison::Equal,
+          "!=" => Comparison::NotEqual,
+          "<"  => Comparison::Less,
+          ">"  => Comparison::Greater,
+          "<=" => Comparison::LessOrEqual,
+          ">=" => Comparison::GreaterOrEqual,
+          other => return Err(format!("line {}: unknown comparison {}", line_number, other))
+        };
+        Ok(ParsedCommand::If((*variable).to_owned(), comparison, parse_number(Some(value), line_number, None)?, (*label).to_owned()))
+      },
+      _ => Err(format!("line {}: expected if variable [op value] goto label", line_number))
+    },
+    "goto" => return match words.as_slice() {
+      [label] => Ok(ParsedCommand::Goto((*label).to_owned())),
+      _ => Err(format!("line {}: goto needs exactly one label", line_number))
+    },
+    "give" => match words.first() {
+      Some(item) => Command::Give((*item).to_owned(), parse_number(words.get(1).copied(), line_number, Some(1))?),
+      None => return Err(format!("line {}: give needs an item", line_number))
+    },
+    "heal" => Command::Heal,
+    "battle" => match words.as_slice() {
+      [formation] => Command::Battle((*formation).to_owned(), None),
+      [formation, "lose", label] => return Ok(ParsedCommand::Battle((*formation).to_owned(), (*label).to_owned())),
+      _ => return Err(format!("line {}: expected battle formation_name [lose label]", line_number))
+    },
+    "shop" => match words.as_slice() {
+      [shop] => Command::Shop((*shop).to_owned()),
+      _ => return Err(format!("line {}: shop needs exactly one shop name", line_number))
+    },
+    "warp" => match words.as_slice() {
+      [map, x, y] => Command::Warp(Warp {
+        map: (*map).to_owned(),
+        position: (parse_number::<u16>(Some(x), line_number, None)? as f32, parse_number::<u16>(Some(y), line_number, None)? as f32)
+      }),
+      _ => return Err(format!("line {}: expected warp map x y", line_number))
+    },
+    "end" => Command::End,
+    other => return Err(format!("line {}: unknown command {}", line_number, other))
+  };
+  Ok(ParsedCommand::Command(command))
+}
+
+impl Script {
+  pub fn parse(name: String, source: &str) -> Result<Script, String> {
+    let mut parsed_commands: Vec<ParsedCommand> = Vec::new();
+    let mut labels = HashMap::new();
+    for (index, line) in source.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      if let Some(label) = line.strip_prefix(':') {
+        if labels.insert(label.trim().to_owned(), parsed_commands.len()).is_some() {
+          return Err(format!("line {}: label {} is defined more than once", index + 1, label.trim()));
+        }
+        continue;
+      }
+      match (parse_line(line, index + 1)?, parsed_commands.last_mut()) {
+        (ParsedCommand::Choice(mut choice), Some(ParsedCommand::Choice(choices))) => choices.append(&mut choice),
+        (parsed_command, _) => parsed_commands.push(parsed_command)
+      }
+    }
+    let get_label = |label: &String| labels.get(label).copied().ok_or(format!("label {} does not exist", label));
+    let mut commands = Vec::new();
+    for parsed_command in parsed_commands.into_iter() {
+      commands.push(match parsed_command {
+        ParsedCommand::Command(command) => command,
+        ParsedCommand::Choice(choices) => Command::Choice(
+          choices.iter().map(|(label, text)| Ok((get_label(label)?, text.to_owned()))).collect::<Result<Vec<(usize, String)>, String>>()?
+        ),
+        ParsedCommand::If(variable, comparison, value, label) => Command::If(variable, comparison, value, get_label(&label)?),
+        ParsedCommand::Goto(label) => Command::Goto(get_label(&label)?),
+        ParsedCommand::Battle(formation, label) => Command::Battle(formation, Some(get_label(&label)?))
+      });
+    }
+    Ok(Script {
+      name,
+      commands
+    })
+  }
+
+  // Plain NPC dialogue without a script file
+  pub fn from_dialogue(speaker: &str, lines: &[String]) -> Script {
+    Script {
+      name: String::new(),
+      commands: lines.iter().map(|line| Command::Say(speaker.to_owned(), line.to_owned())).collect()
+    }
+  }
+}
+
+pub fn get_script_path(name: &str) -> String {
+  format!("/scripts/{}.txt", name)
+}
+
+pub fn load_script(ctx: &mut Context, name: &str) -> GameResult<Script> {
+  let path = get_script_path(name);
+  let mut source = String::new();
+  filesystem::open(ctx, &path)?.read_to_string(&mut source)?;
+  Script::parse(name.to_owned(), &source).map_err(|error| GameError::ResourceLoadError(format!("{}: {}", path, error)))
+}
+
+pub struct ScriptRunner {
+  script: Script,
+  position: usize,
+  dialogue: Option<Dialogue>,
+  prompt: Option<Dialogue>, // Last line stays on screen while a choice is open
+  choice: Option<(MenuScreen, Vec<usize>)>,
+  on_loss: Option<usize>, // Jump target while a battle the party may lose is going on
+  input_cooldown: bool
+}
+
+impl ScriptRunner {
+  pub fn new(script: Script) -> ScriptRunner {
+    ScriptRunner {
+      script,
+      position: 0,
+      dialogue: None,
+      prompt: None,
+      choice: None,
+      on_loss: None,
+      input_cooldown: true
+    }
+  }
+
+  // Returns true once the script has finished
+  pub fn update(
+    &mut self,
+    ctx: &mut Context,
+    input: &Input,
+    party: &mut Party,
+    battle: &mut Battle,
+    menu: &mut MenuScreen,
+    transition: &mut Transition
+  ) -> GameResult<bool> {
+    if let Some(target) = self.on_loss.take() {
+      if battle.lost {
+        self.position = target;
+      }
+    }
+    if let Some(dialogue) = &mut self.dialogue {
+      if !dialogue.update(ctx, input)? {
+        return Ok(false);
+      }
+      if let Some(Command::Choice(_)) = self.script.commands.get(self.position) {
+        self.prompt = self.dialogue.take();
+      }
+      self.dialogue = None;
+    }
+    if let Some((choice_menu, targets)) = &mut self.choice {
+      if input.is_key_pressed(KeyCode::A) && !self.input_cooldown {
+        self.input_cooldown = true;
+        self.position = targets[choice_menu.cursor_pos.1];
+        self.prompt = None;
+        self.choice = None;
+      } else {
+        if !input.is_key_pressed(KeyCode::A) {
+          self.input_cooldown = false;
+        }
+        choice_menu.move_cursor(input);
+        return Ok(false);
+      }
+    }
+    for _ in 0..MAX_STEPS_PER_UPDATE {
+      let command = match self.script.commands.get(self.position) {
+        Some(command) => command.clone(),
+        None => return Ok(true)
+      };
+      self.position += 1;
+      match command {
+        Command::Say(speaker, text) => {
+          let avatar = party.get_character(&speaker).map(|character| character.get_avatar());
+          self.dialogue = Some(Dialogue::new(ctx, speaker, avatar, &[text]));
+          return Ok(false);
+        },
+        Command::Choice(choices) => {
+          let height = choices.len() as f32 * 40. + 80.;
+          let top = WINDOW_SIZE.1 - 240. - height;
+          let items = choices.iter().enumerate()
+            .map(|(index, (_, text))| MenuItem::new(ctx, "".to_owned(), text.to_owned(), (100., top + 40. + index as f32 * 40.), 24., OnClickEvent::None))
+            .collect();
+          let container = MenuContainer::new(ctx, 10., top, WINDOW_SIZE.0 - 20., height);
+          let choice_menu = MenuScreen::new(
+            ctx,
+            true,
+            vec![container],
+            vec![items],
+            Vec::new(),
+            (0, 0),
+            MenuMovement::Grid,
+            OnClickEvent::None
+          );
+          self.choice = Some((choice_menu, choices.iter().map(|(target, _)| *target).collect()));
+          self.input_cooldown = true;
+          return Ok(false);
+        },
+        Command::Set(flag, value) => {party.flags.insert(flag, value);},
+        Command::Add(variable, amount) => *party.flags.entry(variable).or_insert(0) += amount,
+        Command::If(variable, comparison, value, target) => {
+          if comparison.test(party.flags.get(&variable).copied().unwrap_or(0), value) {
+            self.position = target;
+          }
+        },
+        Command::Goto(target) => self.position = target,
+        Command::Give(item_name, amount) => {
+          if let Some(item) = InventoryItem::from_name(&item_name) {
+            party.add_item(item, amount);
+          } else if let Some(equipment) = equipment::get_equipment(&party.content, &item_name) {
+            party.add_to_inventory(InventoryElement::Equipment(equipment, amount));
+          }
+        },
+        Command::Heal => party.heal()?,
+        Command::Battle(formation_name, on_loss) => {
+          let formation = enemy_formations::get_named_formation(&battle.content, &formation_name)?;
+          *battle = Battle::new(ctx, battle.content.clone(), formation, Encounter::Normal, party, menu)?;
+          battle.allow_loss = on_loss.is_some();
+          self.on_loss = on_loss;
+          transition.set(TransitionStyle::WhiteInFast(GameMode::Battle))?;
+          return Ok(false);
+        },
+        Command::Shop(shop_name) => {
+          let shop = match shops::get_shop(&party.content, &shop_name) {
+            Some(shop) => shop,
+            None => return Err(GameError::ResourceLoadError(format!("{}: unknown shop {}", get_script_path(&self.script.name), shop_name)))
+          };
+          *menu = menus::shop_menu(ctx, party, Rc::new(shop), ShopScreen::Main, (0, 0));
+          transition.set(TransitionStyle::BlackInFast(GameMode::Menu))?;
+          return Ok(false);
+        },
+        Command::Warp(warp) => {
+          transition.set(TransitionStyle::Warp(warp))?;
+          return Ok(false);
+        },
+        Command::End => return Ok(true)
+      }
+    }
+    Err(GameError::ResourceLoadError(format!("{}: script ran {} commands without waiting, is there an endless loop?", get_script_path(&self.script.name), MAX_STEPS_PER_UPDATE)))
+  }
+
+  pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+    if let Some(dialogue) = &mut self.dialogue {
+      dialogue.draw(ctx)?;
+    }
+    if let Some((choice_menu, _)) = &mut self.choice {
+      if let Some(prompt) = &mut self.prompt {
+        prompt.draw(ctx)?;
+      }
+      choice_menu.draw(ctx)?;
+    }
+    Ok(())
+  }
+}
@@ -1,31 +1,45 @@
 use ggez::graphics::{spritebatch, Image, DrawParam, Rect, draw};
 use ggez::nalgebra::Point2;
-use ggez::{Context, GameResult};
+use ggez::{Context, GameResult, GameError};
+use ggez::event::KeyCode;
 use crate::globals::WINDOW_SIZE;
+use crate::input::Input;
 pub mod tile;
 pub mod player;
+pub mod npc;
+pub mod tiled;
 use crate::GameMode;
 use crate::transition::{Transition, TransitionStyle};
 use crate::party::Party;
 use crate::battle::Battle;
-use crate::battle::enemy::Enemy;
 use crate::menu::MenuScreen;
+use crate::script::{self, Script, ScriptRunner};
+use crate::random::{self, GameRng};
 
 pub struct Tilemap {
   spritebatch: spritebatch::SpriteBatch,
+  pub name: String,
+  tileset_size: (f32, f32),
   pub tiles:  Vec<Vec<tile::Tile>>,
   pub encounter_rate: f32,
   player: player::Player,
-  enemy_formations: for<'r> fn(&'r mut Context) -> Vec<Vec<Enemy>>
+  pub npcs: Vec<npc::Npc>,
+  npc_rng: GameRng, // Kept apart from the party's rng, so time spent on the map doesn't change the next encounters
+  pub script: Option<ScriptRunner>,
+  interact_cooldown: bool,
+  pub enemy_formations: String
 }
 
 impl Tilemap {
   pub fn new(
     ctx: &mut Context,
+    name: String,
     spritefile: String,
+    tileset_size: (f32, f32),
     tiles: Vec<Vec<tile::Tile>>,
+    npcs: Vec<npc::Npc>,
     encounter_rate: f32,
-    enemy_formations: for<'r> fn(&'r mut Context) -> Vec<Vec<Enemy>>
+    enemy_formations: String
   ) -> Tilemap {
     let image = Image::new(ctx, spritefile).unwrap();
     let batch = spritebatch::SpriteBatch::new(image);
@@ -37,11 +51,18 @@ impl Tilemap {
         }
       }
     }
+    let npc_rng = random::from_name(&name);
     Tilemap {
       spritebatch: batch,
+      name,
+      tileset_size,
       tiles,
       encounter_rate,
       player: player::Player::new(ctx, "/characters/Darrel_Deen.png".to_owned(), player_pos),
+      npc_rng,
+      npcs,
+      script: None,
+      interact_cooldown: true,
       enemy_formations
     }
   }
@@ -49,6 +70,7 @@ impl Tilemap {
   pub fn update(
     &mut self,
     ctx: &mut Context,
+    input: &Input,
     mode: &mut GameMode,
     party: &mut Party,
     battle: &mut Battle,
@@ -56,20 +78,81 @@ impl Tilemap {
     transition: &mut Transition
   ) -> GameResult<()> {
     if *mode == GameMode::Map && transition.style == TransitionStyle::None {
-      self.player.update(ctx, &mut self.tiles, mode, party, battle, transition, menu, self.encounter_rate, self.enemy_formations)?;
+      if let Some(script) = &mut self.script {
+        if script.update(ctx, input, party, battle, menu, transition)? {
+          self.script = None;
+        }
+        return Ok(());
+      }
+      if input.is_key_pressed(KeyCode::A) && !self.interact_cooldown {
+        self.interact_cooldown = true;
+        if let Some((x, y)) = self.player.get_facing_tile(&self.tiles) {
+          if let tile::EntityOnTile::Npc(index) = self.tiles[y][x].entity {
+            let npc = &mut self.npcs[index];
+            if !npc.is_walking() {
+              npc.face((self.player.position.0 as i32 - x as i32, self.player.position.1 as i32 - y as i32));
+              let npc_script = match &npc.script {
+                Some(name) => script::load_script(ctx, name)?,
+                None => Script::from_dialogue(&npc.name, &npc.dialogue)
+              };
+              self.script = Some(ScriptRunner::new(npc_script));
+              return Ok(());
+            }
+          }
+        }
+      } else if !input.is_key_pressed(KeyCode::A) {
+        self.interact_cooldown = false;
+      }
+      for (index, npc) in self.npcs.iter_mut().enumerate() {
+        npc.update(ctx, &mut self.npc_rng, index, &mut self.tiles)?;
+      }
+      let event = self.player.update(ctx, input, &mut self.tiles, mode, party, battle, transition, menu, self.encounter_rate, &self.enemy_formations)?;
+      if let Some(name) = event {
+        self.script = Some(ScriptRunner::new(script::load_script(ctx, &name)?));
+      }
     }
     Ok(())
   }
 
+  pub fn get_player_position(&self) -> (f32, f32) {
+    self.player.position
+  }
+
+  // Inside the map on a tile that is neither solid nor taken by an NPC
+  pub fn is_free(&self, position: (f32, f32)) -> bool {
+    let tile = self.tiles.get(position.1 as usize).and_then(|row| row.get(position.0 as usize));
+    position.0 >= 0. && position.1 >= 0. && matches!(tile.map(|tile| &tile.entity), Some(tile::EntityOnTile::None) | Some(tile::EntityOnTile::Player))
+  }
+
+  // Fails without changing the map when the position isn't free
+  pub fn place_player(&mut self, position: (f32, f32)) -> GameResult<()> {
+    if !self.is_free(position) {
+      return Err(GameError::ResourceLoadError(format!("Can't place the player at {:?} on map {}, it is not walkable", position, self.name)));
+    }
+    for row in self.tiles.iter_mut() {
+      for tile in row.iter_mut() {
+        if tile.entity == tile::EntityOnTile::Player {
+          tile.entity = tile::EntityOnTile::None;
+        }
+      }
+    }
+    self.tiles[position.1 as usize][position.0 as usize].entity = tile::EntityOnTile::Player;
+    self.player.position = position;
+    Ok(())
+  }
+
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
     let mut j = 0.;
     for row in self.tiles.iter() {
       let mut i = 0.;
       for tile in row.iter() {
-        let p = DrawParam::new()
-          .src(Rect::new(tile.spritesheet_pos.0 * 0.25, tile.spritesheet_pos.1 * 0.333333333333333, 0.25, 0.333333333333333))
-          .dest(Point2::new((i - self.player.position.0) * 64., (j - self.player.position.1) * 64.));
-        self.spritebatch.add(p);
+        if tile.spritesheet_pos.0 >= 0. {
+          let (tile_width, tile_height) = (1. / self.tileset_size.0, 1. / self.tileset_size.1);
+          let p = DrawParam::new()
+            .src(Rect::new(tile.spritesheet_pos.0 * tile_width, tile.spritesheet_pos.1 * tile_height, tile_width, tile_height))
+            .dest(Point2::new((i - self.player.position.0) * 64., (j - self.player.position.1) * 64.));
+          self.spritebatch.add(p);
+        }
         i += 1.;
       }
       j += 1.;
@@ -77,8 +160,14 @@ impl Tilemap {
     let param = DrawParam::new()
       .dest(Point2::new(WINDOW_SIZE.0 / 2., WINDOW_SIZE.1 / 2.));
     draw(ctx, &self.spritebatch, param)?;
+    for npc in self.npcs.iter_mut() {
+      npc.draw(ctx, self.player.position)?;
+    }
     self.player.draw(ctx)?;
     self.spritebatch.clear();
+    if let Some(script) = &mut self.script {
+      script.draw(ctx)?;
+    }
     Ok(())
   }
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+use ggez::graphics::{spritebatch, Image, DrawParam, draw};
+use ggez::nalgebra::Point2;
+use ggez::{Context, GameResult};
+use ggez::timer::ticks;
+use rand::Rng;
+use crate::globals::WINDOW_SIZE;
+use crate::tilemap::tile::{Tile, EntityOnTile};
+use crate::tilemap::player::PlayerAnimation;
+use crate::random::GameRng;
+
+pub enum NpcBehaviour {
+  Stand, Wander
+}
+
+pub struct Npc {
+  pub name: String,
+  pub position: (f32, f32),
+  pub behaviour: NpcBehaviour,
+  pub dialogue: Vec<String>,
+  pub script: Option<String>,
+  spritebatch: spritebatch::SpriteBatch,
+  animation: PlayerAnimation,
+  frame: f32,
+  finish_animation: (usize, usize)
+}
+
+impl Npc {
+  pub fn new(ctx: &mut Context, name: String, spritefile: String, position: (f32, f32), behaviour: NpcBehaviour, dialogue: Vec<String>, script: Option<String>) -> Npc {
+    let image = Image::new(ctx, spritefile).unwrap();
+    let batch = spritebatch::SpriteBatch::new(image);
+    Npc {
+      name,
+      position,
+      behaviour,
+      dialogue,
+      script,
+      spritebatch: batch,
+      animation: PlayerAnimation::StandDown,
+      frame: 0.,
+      finish_animation: (0, 0)
+    }
+  }
+
+  pub fn update(&mut self, ctx: &mut Context, rng: &mut GameRng, index: usize, tiles: &mut [Vec<Tile>]) -> GameResult<()> {
+    if self.finish_animation.0 == 0 {
+      if let NpcBehaviour::Wander = self.behaviour {
+        if rng.gen::<f32>() < 0.01 {
+          let (animation, walk_animation) = match rng.gen_range(0, 4) {
+            0 => (PlayerAnimation::StandUp,    PlayerAnimation::WalkUp),
+            1 => (PlayerAnimation::StandDown,  PlayerAnimation::WalkDown),
+            2 => (PlayerAnimation::StandLeft,  PlayerAnimation::WalkLeft),
+            _ => (PlayerAnimation::StandRight, PlayerAnimation::WalkRight)
+          };
+          let facing = animation.get_facing();
+          let (x, y) = (self.position.0 as i32 + facing.0, self.position.1 as i32 + facing.1);
+          let walkable = x >= 0 && y >= 0 && (y as usize) < tiles.len() && (x as usize) < tiles[y as usize].len()
+            && tiles[y as usize][x as usize].entity == EntityOnTile::None
+            && tiles[y as usize][x as usize].warp.is_none();
+          if walkable {
+            tiles[y as usize][x as usize].entity = EntityOnTile::Npc(index);
+            self.animation = walk_animation;
+            self.finish_animation = (12, ticks(ctx));
+          } else {
+            self.animation = animation;
+          }
+        }
+      }
+    }
+    if self.finish_animation.0 > 0 {
+      self.finish_animation.0 -= 1;
+      match (ticks(ctx) - self.finish_animation.1) % 12 {
+        2  => self.frame = 1.,
+        5  => self.frame = 0.,
+        8  => self.frame = 2.,
+        11 => self.frame = 0.,
+        _  => ()
+      }
+      let facing = self.animation.get_facing();
+      self.position.0 += facing.0 as f32 / 12.;
+      self.position.1 += facing.1 as f32 / 12.;
+      if self.finish_animation.0 == 0 {
+        self.position.0 = self.position.0.round();
+        self.position.1 = self.position.1.round();
+        self.frame = 0.;
+        tiles[(self.position.1 as i32 - facing.1) as usize][(self.position.0 as i32 - facing.0) as usize].entity = EntityOnTile::None;
+        self.animation = match self.animation {
+          PlayerAnimation::WalkUp    => PlayerAnimation::StandUp,
+          PlayerAnimation::WalkDown  => PlayerAnimation::StandDown,
+          PlayerAnimation::WalkLeft  => PlayerAnimation::StandLeft,
+          PlayerAnimation::WalkRight => PlayerAnimation::StandRight,
+          _ => PlayerAnimation::StandDown
+        };
+      }
+    }
+    Ok(())
+  }
+
+  pub fn is_walking(&self) -> bool {
+    self.finish_animation.0 > 0
+  }
+
+  // Turns the NPC towards someone standing at the given offset
+  pub fn face(&mut self, direction: (i32, i32)) {
+    self.animation = match direction {
+      (-1, 0) => PlayerAnimation::StandLeft,
+      (1, 0)  => PlayerAnimation::StandRight,
+      (0, -1) => PlayerAnimation::StandUp,
+      _       => PlayerAnimation::StandDown
+    };
+  }
+
+  pub fn draw(&mut self, ctx: &mut Context, player_position: (f32, f32)) -> GameResult<()> {
+    let p = DrawParam::new()
+      .src(self.animation.get_source_rect(self.frame));
+    self.spritebatch.add(p);
+    let param = DrawParam::new()
+      .dest(Point2::new(
+        WINDOW_SIZE.0 / 2. + (self.position.0 - player_position.0) * 64.,
+        WINDOW_SIZE.1 / 2. + (self.position.1 - player_position.1) * 64.
+      ));
+    draw(ctx, &self.spritebatch, param)?;
+    self.spritebatch.clear();
+    Ok(())
+  }
+}
@@ -2,19 +2,19 @@ use ggez::graphics::{spritebatch, Image, DrawParam, Rect, draw};
 use ggez::nalgebra::Point2;
 use ggez::{Context, GameResult};
 use ggez::event::KeyCode;
-use ggez::input::keyboard;
 use ggez::timer::ticks;
-use rand::{Rng, thread_rng};
+use rand::Rng;
 use crate::globals::WINDOW_SIZE;
+use crate::input::Input;
 use crate::tilemap::tile::{Tile, EntityOnTile};
 use crate::GameMode;
 use crate::transition::{Transition, TransitionStyle};
 use crate::party::Party;
 use crate::battle::Battle;
-use crate::battle::enemy::Enemy;
 use crate::menu::MenuScreen;
+use crate::data::enemy_formations;
 
-enum PlayerAnimation {
+pub enum PlayerAnimation {
   StandLeft,
   WalkLeft,
   StandRight,
@@ -25,6 +25,32 @@ enum PlayerAnimation {
   WalkDown
 }
 
+impl PlayerAnimation {
+  pub fn get_source_rect(&self, frame: f32) -> Rect {
+    let (spritesheet_x, spritesheet_y, anim_loop_len) = match self {
+      PlayerAnimation::StandLeft  => (0., 1., 1.),
+      PlayerAnimation::WalkLeft   => (0., 1., 3.),
+      PlayerAnimation::StandRight => (0., 2., 1.),
+      PlayerAnimation::WalkRight  => (0., 2., 3.),
+      PlayerAnimation::StandUp    => (0., 3., 1.),
+      PlayerAnimation::WalkUp     => (0., 3., 3.),
+      PlayerAnimation::StandDown  => (0., 0., 1.),
+      PlayerAnimation::WalkDown   => (0., 0., 3.)
+    };
+    Rect::new((spritesheet_x + (frame % anim_loop_len)) / 3., 0.2 * spritesheet_y, 1. / 3., 0.2)
+  }
+
+  // Direction the sprite is facing as a tile offset
+  pub fn get_facing(&self) -> (i32, i32) {
+    match self {
+      PlayerAnimation::StandLeft  | PlayerAnimation::WalkLeft  => (-1, 0),
+      PlayerAnimation::StandRight | PlayerAnimation::WalkRight => (1, 0),
+      PlayerAnimation::StandUp    | PlayerAnimation::WalkUp    => (0, -1),
+      PlayerAnimation::StandDown  | PlayerAnimation::WalkDown  => (0, 1)
+    }
+  }
+}
+
 pub struct Player {
   pub position: (f32, f32),
   spritebatch: spritebatch::SpriteBatch,
@@ -49,6 +75,7 @@ impl Player {
   pub fn update(
     &mut self,
     ctx: &mut Context,
+    input: &Input,
     tiles: &mut Vec<Vec<Tile>>,
     mode: &mut GameMode,
     party: &mut Party,
@@ -56,31 +83,31 @@ impl Player {
     transition: &mut Transition,
     menu: &mut MenuScreen,
     encounter_rate: f32,
-    enemy_formations: for<'r> fn(&'r mut Context) -> Vec<Vec<Enemy>>
-  ) -> GameResult<()> {
+    enemy_formations: &str
+  ) -> GameResult<Option<String>> {
     if *mode == GameMode::Map {
       if self.finish_animation.1 == 0 {
-        if keyboard::is_key_pressed(ctx, KeyCode::Up) {
+        if input.is_key_pressed(KeyCode::Up) {
           self.animation = PlayerAnimation::WalkUp;
-          if tiles[self.position.1 as usize - 1][self.position.0 as usize].entity == EntityOnTile::None {
+          if self.position.1 as usize > 0 && tiles[self.position.1 as usize - 1][self.position.0 as usize].entity == EntityOnTile::None {
             tiles[self.position.1 as usize - 1][self.position.0 as usize].entity = EntityOnTile::Player;
             self.finish_animation = (PlayerAnimation::WalkUp, 12, ticks(ctx));
           }
-        } else if keyboard::is_key_pressed(ctx, KeyCode::Down) {
+        } else if input.is_key_pressed(KeyCode::Down) {
           self.animation = PlayerAnimation::WalkDown;
-          if tiles[self.position.1 as usize + 1][self.position.0 as usize].entity == EntityOnTile::None {
+          if self.position.1 as usize + 1 < tiles.len() && tiles[self.position.1 as usize + 1][self.position.0 as usize].entity == EntityOnTile::None {
             tiles[self.position.1 as usize + 1][self.position.0 as usize].entity = EntityOnTile::Player;
             self.finish_animation = (PlayerAnimation::WalkDown, 12, ticks(ctx));
           }
-        } else if keyboard::is_key_pressed(ctx, KeyCode::Left) {
+        } else if input.is_key_pressed(KeyCode::Left) {
           self.animation = PlayerAnimation::WalkLeft;
-          if tiles[self.position.1 as usize][self.position.0 as usize - 1].entity == EntityOnTile::None {
+          if self.position.0 as usize > 0 && tiles[self.position.1 as usize][self.position.0 as usize - 1].entity == EntityOnTile::None {
             tiles[self.position.1 as usize][self.position.0 as usize - 1].entity = EntityOnTile::Player;
             self.finish_animation = (PlayerAnimation::WalkLeft, 12, ticks(ctx));
           }
-        } else if keyboard::is_key_pressed(ctx, KeyCode::Right) {
+        } else if input.is_key_pressed(KeyCode::Right) {
           self.animation = PlayerAnimation::WalkRight;
-          if tiles[self.position.1 as usize][self.position.0 as usize + 1].entity == EntityOnTile::None {
+          if self.position.0 as usize + 1 < tiles[self.position.1 as usize].len() && tiles[self.position.1 as usize][self.position.0 as usize + 1].entity == EntityOnTile::None {
             tiles[self.position.1 as usize][self.position.0 as usize + 1].entity = EntityOnTile::Player;
             self.finish_animation = (PlayerAnimation::WalkRight, 12, ticks(ctx));
           }
@@ -125,30 +152,39 @@ impl Player {
             },
             _ => ()
           };
-          if thread_rng().gen::<f32>() < encounter_rate {
-            let enemy_formations = enemy_formations(ctx);
-            *battle = Battle::new(ctx, enemy_formations, party, menu);
+          let tile = &tiles[self.position.1 as usize][self.position.0 as usize];
+          if let Some(warp) = &tile.warp {
+            transition.set(TransitionStyle::Warp(warp.clone()))?;
+          } else if let Some(event) = &tile.event {
+            return Ok(Some(event.to_owned()));
+          } else if party.rng.gen::<f32>() < encounter_rate {
+            let formation = enemy_formations::get_formation(&battle.content, &mut party.rng, enemy_formations)?;
+            let encounter = enemy_formations::roll_encounter(&battle.content, party, &formation);
+            *battle = Battle::new(ctx, battle.content.clone(), formation, encounter, party, menu)?;
             transition.set(TransitionStyle::WhiteInFast(GameMode::Battle))?;
           }
         }
       }
     }
-    Ok(())
+    Ok(None)
+  }
+
+  // Tile in front of the player, if they are standing still
+  pub fn get_facing_tile(&self, tiles: &[Vec<Tile>]) -> Option<(usize, usize)> {
+    if self.finish_animation.1 > 0 {
+      return None;
+    }
+    let facing = self.animation.get_facing();
+    let (x, y) = (self.position.0 as i32 + facing.0, self.position.1 as i32 + facing.1);
+    if x < 0 || y < 0 || y as usize >= tiles.len() || x as usize >= tiles[y as usize].len() {
+      return None;
+    }
+    Some((x as usize, y as usize))
   }
 
   pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-    let (spritesheet_x, spritesheet_y, anim_loop_len) = match self.animation {
-      PlayerAnimation::StandLeft  => (0., 1., 1.),
-      PlayerAnimation::WalkLeft   => (0., 1., 3.),
-      PlayerAnimation::StandRight => (0., 2., 1.),
-      PlayerAnimation::WalkRight  => (0., 2., 3.),
-      PlayerAnimation::StandUp    => (0., 3., 1.),
-      PlayerAnimation::WalkUp     => (0., 3., 3.),
-      PlayerAnimation::StandDown  => (0., 0., 1.),
-      PlayerAnimation::WalkDown   => (0., 0., 3.)
-    };
     let p = DrawParam::new()
-      .src(Rect::new(0.333333333333333 * (spritesheet_x + (self.frame % anim_loop_len)), 0.2 * spritesheet_y, 0.333333333333333, 0.2));
+      .src(self.animation.get_source_rect(self.frame));
     self.spritebatch.add(p);
     let param = DrawParam::new()
       .dest(Point2::new(WINDOW_SIZE.0 / 2., WINDOW_SIZE.1 / 2.));
@@ -156,4 +192,4 @@ impl Player {
     self.spritebatch.clear();
     Ok(())
   }
-}
\ No newline at end of file
+}
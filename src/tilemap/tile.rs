@@ -1,27 +1,38 @@
 pub enum EntityOnTile {
-  None, Solid, Player
+  None, Solid, Player, Npc(usize) // Index into Tilemap::npcs
 }
 
 impl PartialEq for EntityOnTile {
   fn eq(&self, other: &Self) -> bool {
     match self {
-      EntityOnTile::None   => {match other {EntityOnTile::None   => true, _ => false}},
-      EntityOnTile::Player => {match other {EntityOnTile::Player => true, _ => false}},
-      EntityOnTile::Solid  => {match other {EntityOnTile::Solid  => true, _ => false}}
+      EntityOnTile::None   => matches!(other, EntityOnTile::None),
+      EntityOnTile::Player => matches!(other, EntityOnTile::Player),
+      EntityOnTile::Solid  => matches!(other, EntityOnTile::Solid),
+      EntityOnTile::Npc(a) => {match other {EntityOnTile::Npc(b) => a == b, _ => false}}
     }
   }
 }
 
+#[derive(Clone)]
+pub struct Warp {
+  pub map: String,
+  pub position: (f32, f32)
+}
+
 pub struct Tile {
   pub spritesheet_pos: (f32, f32),
-  pub entity: EntityOnTile
+  pub entity: EntityOnTile,
+  pub warp: Option<Warp>,
+  pub event: Option<String> // Script run when the player steps on the tile
 }
 
 impl Tile {
   pub fn new(spritesheet_pos: (f32, f32), entity: EntityOnTile) -> Tile {
     Tile {
       spritesheet_pos,
-      entity
+      entity,
+      warp: None,
+      event: None
     }
   }
 }
\ No newline at end of file
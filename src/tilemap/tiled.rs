@@ -0,0 +1,215 @@
+use ggez::{Context, GameResult, GameError};
+use ggez::filesystem;
+use serde_derive::Deserialize;
+use std::io::Read;
+use crate::tilemap::Tilemap;
+use crate::tilemap::tile::{Tile, EntityOnTile, Warp};
+use crate::tilemap::npc::{Npc, NpcBehaviour};
+
+#[derive(Deserialize)]
+struct TiledMap {
+  width: usize,
+  height: usize,
+  tilewidth: f32,
+  tileheight: f32,
+  layers: Vec<TiledLayer>,
+  tilesets: Vec<TiledTileset>,
+  #[serde(default)] properties: Vec<TiledProperty>
+}
+
+#[derive(Deserialize)]
+struct TiledLayer {
+  name: String,
+  #[serde(rename = "type")] layer_type: String,
+  #[serde(default)] data: Vec<u32>,
+  #[serde(default)] objects: Vec<TiledObject>
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+  #[serde(default)] name: String,
+  #[serde(default, rename = "type")] object_type: String,
+  #[serde(default)] class: String,
+  x: f32,
+  y: f32,
+  #[serde(default)] width: f32,
+  #[serde(default)] height: f32,
+  #[serde(default)] properties: Vec<TiledProperty>
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+  firstgid: u32,
+  #[serde(default)] image: Option<String>,
+  #[serde(default)] source: Option<String>,
+  #[serde(default)] columns: u32,
+  #[serde(default)] tilecount: u32
+}
+
+#[derive(Deserialize)]
+struct TiledProperty {
+  name: String,
+  value: serde_json::Value
+}
+
+const FLIP_FLAGS: u32 = 0xE000_0000;
+
+fn load_error(path: &str, message: String) -> GameError {
+  GameError::ResourceLoadError(format!("{}: {}", path, message))
+}
+
+fn resolve_path(map_path: &str, relative_path: &str) -> String {
+  let mut parts: Vec<&str> = map_path.split('/').filter(|part| !part.is_empty()).collect();
+  parts.pop();
+  for part in relative_path.split('/') {
+    match part {
+      "" | "." => (),
+      ".." => {parts.pop();},
+      _ => parts.push(part)
+    }
+  }
+  format!("/{}", parts.join("/"))
+}
+
+// Objects of one kind, told by their type, which newer versions of Tiled call class. The name is left for the object's own name.
+fn get_objects<'a>(map: &'a TiledMap, kind: &'a str) -> impl Iterator<Item = &'a TiledObject> {
+  map.layers.iter()
+    .filter(|layer| layer.layer_type == "objectgroup")
+    .flat_map(|layer| layer.objects.iter())
+    .filter(move |object| object.object_type == kind || object.class == kind)
+}
+
+// Runs fill on every tile the object's rectangle touches, a point object covers the tile it is on
+fn fill_tiles(map: &TiledMap, tiles: &mut [Vec<Tile>], object: &TiledObject, fill: impl Fn(&mut Tile)) {
+  let first_tile = ((object.x / map.tilewidth) as usize, (object.y / map.tileheight) as usize);
+  let last_tile = (
+    (((object.x + object.width) / map.tilewidth).ceil() as usize).max(first_tile.0 + 1),
+    (((object.y + object.height) / map.tileheight).ceil() as usize).max(first_tile.1 + 1)
+  );
+  for row in tiles.iter_mut().take(last_tile.1).skip(first_tile.1) {
+    for tile in row.iter_mut().take(last_tile.0).skip(first_tile.0) {
+      fill(tile);
+    }
+  }
+}
+
+fn get_property<'a>(properties: &'a [TiledProperty], name: &str) -> Option<&'a serde_json::Value> {
+  properties.iter().find(|property| property.name == name).map(|property| &property.value)
+}
+
+pub fn load(ctx: &mut Context, name: String, path: &str) -> GameResult<Tilemap> {
+  let mut contents = String::new();
+  filesystem::open(ctx, path)?.read_to_string(&mut contents)?;
+  let map: TiledMap = serde_json::from_str(&contents).map_err(|error| load_error(path, format!("{}", error)))?;
+  let tileset = match map.tilesets.first() {
+    Some(tileset) => tileset,
+    None => return Err(load_error(path, "map has no tileset".to_owned()))
+  };
+  if tileset.source.is_some() {
+    return Err(load_error(path, "external tilesets are not supported, embed the tileset in the map".to_owned()));
+  }
+  let tileset_image = match &tileset.image {
+    Some(image) => resolve_path(path, image),
+    None => return Err(load_error(path, "tileset has no image".to_owned()))
+  };
+  if !filesystem::is_file(ctx, &tileset_image) {
+    return Err(load_error(path, format!("tileset image {} does not exist", tileset_image)));
+  }
+  if tileset.columns == 0 || tileset.tilecount < tileset.columns {
+    return Err(load_error(path, "tileset must have at least one full row of tiles".to_owned()));
+  }
+  let tileset_size = (tileset.columns as f32, (tileset.tilecount / tileset.columns) as f32);
+  let tile_layers: Vec<&TiledLayer> = map.layers.iter().filter(|layer| layer.layer_type == "tilelayer").collect();
+  let ground = match tile_layers.iter().find(|layer| layer.name != "collision") {
+    Some(layer) => layer,
+    None => return Err(load_error(path, "map has no tile layer besides collision".to_owned()))
+  };
+  let collision = tile_layers.iter().find(|layer| layer.name == "collision");
+  for layer in tile_layers.iter() {
+    if layer.data.len() != map.width * map.height {
+      return Err(load_error(path, format!("layer {} must be an uncompressed CSV layer of {}x{} tiles", layer.name, map.width, map.height)));
+    }
+  }
+  let spawn_pos = match get_objects(&map, "spawn").next() {
+    Some(object) => ((object.x / map.tilewidth) as usize, (object.y / map.tileheight) as usize),
+    None => return Err(load_error(path, "map has no spawn object".to_owned()))
+  };
+  if spawn_pos.0 >= map.width || spawn_pos.1 >= map.height {
+    return Err(load_error(path, "spawn object is outside of the map".to_owned()));
+  }
+  let mut encounter_rate = 0.;
+  let mut enemy_formations = String::new();
+  for property in map.properties.iter() {
+    match property.name.as_str() {
+      "encounter_rate"  => encounter_rate = property.value.as_f64().unwrap_or(0.) as f32,
+      "formation_table" => enemy_formations = property.value.as_str().unwrap_or("").to_owned(),
+      _ => ()
+    }
+  }
+  let mut tiles = Vec::new();
+  for j in 0..map.height {
+    let mut row = Vec::new();
+    for i in 0..map.width {
+      let index = j * map.width + i;
+      let gid = ground.data[index] & !FLIP_FLAGS;
+      let spritesheet_pos = match gid.checked_sub(tileset.firstgid) {
+        Some(tile_id) => ((tile_id % tileset.columns) as f32, (tile_id / tileset.columns) as f32),
+        None => (-1., -1.) // Negative position means an empty tile
+      };
+      let entity = if (i, j) == spawn_pos {
+        EntityOnTile::Player
+      } else if collision.is_some_and(|layer| layer.data[index] != 0) {
+        EntityOnTile::Solid
+      } else {
+        EntityOnTile::None
+      };
+      row.push(Tile::new(spritesheet_pos, entity));
+    }
+    tiles.push(row);
+  }
+  for object in get_objects(&map, "warp") {
+    let destination_map = get_property(&object.properties, "map").and_then(|value| value.as_str());
+    let destination_x   = get_property(&object.properties, "spawn_x").and_then(|value| value.as_u64());
+    let destination_y   = get_property(&object.properties, "spawn_y").and_then(|value| value.as_u64());
+    let warp = match (destination_map, destination_x, destination_y) {
+      (Some(destination_map), Some(x), Some(y)) => Warp {map: destination_map.to_owned(), position: (x as f32, y as f32)},
+      _ => return Err(load_error(path, "warp objects need the properties map, spawn_x and spawn_y".to_owned()))
+    };
+    fill_tiles(&map, &mut tiles, object, |tile| tile.warp = Some(warp.clone()));
+  }
+  for object in get_objects(&map, "event") {
+    let script = match get_property(&object.properties, "script").and_then(|value| value.as_str()) {
+      Some(script) => script.to_owned(),
+      None => return Err(load_error(path, format!("event {} needs the property script", object.name)))
+    };
+    fill_tiles(&map, &mut tiles, object, |tile| tile.event = Some(script.to_owned()));
+  }
+  let mut npcs = Vec::new();
+  for object in get_objects(&map, "npc") {
+    let position = ((object.x / map.tilewidth) as usize, (object.y / map.tileheight) as usize);
+    if position.0 >= map.width || position.1 >= map.height || tiles[position.1][position.0].entity != EntityOnTile::None {
+      return Err(load_error(path, format!("npc {} must stand on a free tile inside the map", object.name)));
+    }
+    let spritefile = match get_property(&object.properties, "spritefile").and_then(|value| value.as_str()) {
+      Some(spritefile) => spritefile.to_owned(),
+      None => return Err(load_error(path, format!("npc {} needs the property spritefile", object.name)))
+    };
+    if !filesystem::is_file(ctx, &spritefile) {
+      return Err(load_error(path, format!("npc {} uses sprite {} which does not exist", object.name, spritefile)));
+    }
+    let behaviour = match get_property(&object.properties, "behaviour").and_then(|value| value.as_str()) {
+      Some("wander") => NpcBehaviour::Wander,
+      Some("stand") | None => NpcBehaviour::Stand,
+      Some(other) => return Err(load_error(path, format!("npc {} has unknown behaviour {}", object.name, other)))
+    };
+    // Every line of the dialogue property starts a new page
+    let dialogue = get_property(&object.properties, "dialogue")
+      .and_then(|value| value.as_str())
+      .map(|text| text.lines().filter(|line| !line.trim().is_empty()).map(|line| line.to_owned()).collect())
+      .unwrap_or_default();
+    let script = get_property(&object.properties, "script").and_then(|value| value.as_str()).map(|script| script.to_owned());
+    tiles[position.1][position.0].entity = EntityOnTile::Npc(npcs.len());
+    npcs.push(Npc::new(ctx, object.name.to_owned(), spritefile, (position.0 as f32, position.1 as f32), behaviour, dialogue, script));
+  }
+  Ok(Tilemap::new(ctx, name, tileset_image, tileset_size, tiles, npcs, encounter_rate, enemy_formations))
+}
\ No newline at end of file
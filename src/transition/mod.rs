@@ -4,9 +4,11 @@ use ggez::{Context, GameResult};
 use crate::battle::enemy::Enemy;
 use crate::menu::MenuScreen;
 use crate::party::Party;
+use crate::tilemap::Tilemap;
+use crate::tilemap::tile::Warp;
 use crate::globals::WINDOW_SIZE;
 use crate::GameMode;
-use crate::data::menus;
+use crate::data::{menus, tilemaps};
 
 pub enum TransitionStyle {
   None,
@@ -15,19 +17,21 @@ pub enum TransitionStyle {
   BlackInFast(GameMode),
   BlackOutFast,
   MenuIn(for<'r, 's, 't0, 't1> fn(&'r mut Context, &'s mut GameMode, &'t0 mut Party, &'t1 Vec<Vec<Enemy>>, (usize, usize)) -> MenuScreen, (usize, usize)),
-  MenuOut
+  MenuOut,
+  Warp(Warp)
 }
 
 impl PartialEq for TransitionStyle {
   fn eq(&self, other: &Self) -> bool {
     match self {
-      TransitionStyle::None           => {match other {TransitionStyle::None           => true, _ => false}},
-      TransitionStyle::WhiteInFast(_) => {match other {TransitionStyle::WhiteInFast(_) => true, _ => false}},
-      TransitionStyle::WhiteOutFast   => {match other {TransitionStyle::WhiteOutFast   => true, _ => false}},
-      TransitionStyle::BlackInFast(_) => {match other {TransitionStyle::BlackInFast(_) => true, _ => false}},
-      TransitionStyle::BlackOutFast   => {match other {TransitionStyle::BlackOutFast   => true, _ => false}},
-      TransitionStyle::MenuIn(_, _)   => {match other {TransitionStyle::MenuIn(_, _)   => true, _ => false}},
-      TransitionStyle::MenuOut        => {match other {TransitionStyle::MenuOut        => true, _ => false}}
+      TransitionStyle::None           => matches!(other, TransitionStyle::None),
+      TransitionStyle::WhiteInFast(_) => matches!(other, TransitionStyle::WhiteInFast(_)),
+      TransitionStyle::WhiteOutFast   => matches!(other, TransitionStyle::WhiteOutFast),
+      TransitionStyle::BlackInFast(_) => matches!(other, TransitionStyle::BlackInFast(_)),
+      TransitionStyle::BlackOutFast   => matches!(other, TransitionStyle::BlackOutFast),
+      TransitionStyle::MenuIn(_, _)   => matches!(other, TransitionStyle::MenuIn(_, _)),
+      TransitionStyle::MenuOut        => matches!(other, TransitionStyle::MenuOut),
+      TransitionStyle::Warp(_)        => matches!(other, TransitionStyle::Warp(_))
     }
   }
 }
@@ -57,7 +61,8 @@ impl Transition {
     mode: &mut GameMode,
     menu: &mut MenuScreen,
     party: &mut Party,
-    enemies: &Vec<Vec<Enemy>>
+    enemies: &Vec<Vec<Enemy>>,
+    map: &mut Tilemap
   ) -> GameResult<()> {
     let mut done = false;
     match &self.style {
@@ -107,6 +112,17 @@ impl Transition {
         if self.opacity < 0. {
           done = true;
         }
+      },
+      TransitionStyle::Warp(warp) => {
+        self.opacity += 0.1;
+        self.opacity *= 0.95;
+        if self.opacity > 1. {
+          let mut new_map = tilemaps::get_tilemap(ctx, &warp.map)?;
+          new_map.place_player(warp.position)?;
+          new_map.script = map.script.take(); // A script that warped keeps running on the new map
+          *map = new_map;
+          done = true;
+        }
       }
     }
     if done {
@@ -114,6 +130,7 @@ impl Transition {
         TransitionStyle::WhiteInFast(_) => TransitionStyle::WhiteOutFast,
         TransitionStyle::BlackInFast(_) => TransitionStyle::BlackOutFast,
         TransitionStyle::MenuIn(_, _)   => TransitionStyle::MenuOut,
+        TransitionStyle::Warp(_)        => TransitionStyle::BlackOutFast,
         _ => {
           match mode {
             GameMode::Map => *menu = menus::none_menu(ctx),
@@ -139,7 +156,8 @@ impl Transition {
       TransitionStyle::BlackInFast(_) |
       TransitionStyle::BlackOutFast   |
       TransitionStyle::MenuIn(_, _)   |
-      TransitionStyle::MenuOut => {
+      TransitionStyle::MenuOut        |
+      TransitionStyle::Warp(_) => {
         let rectangle = Rect::new(-WINDOW_SIZE.0, -WINDOW_SIZE.1, WINDOW_SIZE.0 * 2., WINDOW_SIZE.1 * 2.);
         let color = Color::new(0., 0., 0., self.opacity);
         let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rectangle, color).unwrap();